@@ -0,0 +1,693 @@
+//! Persistent auth volumes for agents whose logins refresh tokens, so a login
+//! made in the sandbox survives restarts without touching host dotfiles, and
+//! the `davy auth` commands that back them up and report on them.
+
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::Read;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+use base64::Engine as _;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Local};
+use clap::ValueEnum;
+use serde::Deserialize;
+use serde_json::Value;
+use users::{get_current_gid, get_current_uid};
+
+use crate::containers;
+use crate::engine::Engine;
+use crate::{DavyError, dry_run, run_checked, shell_command};
+
+/// Where an agent's auth state lives.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthStore {
+    /// Bind mount the host's auth directories
+    #[default]
+    Host,
+    /// Keep auth in a davy volume; log in inside the sandbox once
+    Volume,
+}
+
+/// An agent whose auth can live in a davy volume.
+#[derive(Debug, PartialEq, Eq)]
+pub struct AuthVolume {
+    /// Name used in `davy auth NAME reset` and in the volume name.
+    pub agent: &'static str,
+    /// Name shown in messages.
+    pub label: &'static str,
+    /// Directories under /home/dev that the volume backs.
+    pub dirs: &'static [&'static str],
+    /// Command that logs in inside the sandbox.
+    pub login: &'static str,
+}
+
+/// Codex keeps its login in `CODEX_HOME` and refreshes the token in place.
+pub const CODEX: AuthVolume = AuthVolume {
+    agent: "codex",
+    label: "Codex",
+    dirs: &[".codex"],
+    login: "codex login",
+};
+
+/// Gemini CLI keeps its OAuth login in `~/.gemini` and refreshes it as it runs.
+pub const GEMINI: AuthVolume = AuthVolume {
+    agent: "gemini",
+    label: "Gemini",
+    dirs: &[".gemini"],
+    login: "gemini",
+};
+
+/// Pi keeps its provider logins in `~/.pi/agent`.
+pub const PI: AuthVolume = AuthVolume {
+    agent: "pi",
+    label: "Pi",
+    dirs: &[".pi/agent"],
+    login: "pi",
+};
+
+/// OpenCode keeps `auth.json` and its sessions in its data directory.
+pub const OPENCODE: AuthVolume = AuthVolume {
+    agent: "opencode",
+    label: "OpenCode",
+    dirs: &[".local/share/opencode"],
+    login: "opencode auth login",
+};
+
+/// cursor-agent keeps its login in `~/.config/cursor` and its settings in
+/// `~/.cursor`, and refreshes the token as it runs.
+pub const CURSOR: AuthVolume = AuthVolume {
+    agent: "cursor",
+    label: "Cursor",
+    dirs: &[".cursor", ".config/cursor"],
+    login: "cursor-agent login",
+};
+
+impl AuthVolume {
+    /// Name of the volume for `host_uid`, in an auth profile if given.
+    pub fn volume_name(&self, host_uid: u32, profile: Option<&str>) -> String {
+        match profile {
+            Some(profile) => format!("davy-{}-auth-{host_uid}-{profile}", self.agent),
+            None => format!("davy-{}-auth-{host_uid}", self.agent),
+        }
+    }
+
+    /// Volume kind label, which marks it as an auth volume.
+    pub fn kind(&self) -> String {
+        format!("{}-auth", self.agent)
+    }
+
+    /// Where the volume is mounted in the sandbox.
+    pub fn mount_point(&self) -> String {
+        format!("/home/dev/.{}-auth", self.agent)
+    }
+
+    /// Script that replaces each directory with a link into the volume and
+    /// then runs its arguments.
+    pub fn link_script(&self) -> String {
+        let mount_point = self.mount_point();
+        let mut script = String::from("set -e\n");
+        for dir in self.dirs {
+            let stored = format!("{mount_point}/{dir}");
+            let home = format!("/home/dev/{dir}");
+            script.push_str(&format!(
+                "mkdir -p {stored} \"$(dirname {home})\"\n\
+                 if [ -e {home} ] && [ ! -L {home} ]; then\n  rm -rf {home}\nfi\n\
+                 ln -sfn {stored} {home}\n"
+            ));
+        }
+        script.push_str("exec \"$@\"");
+        script
+    }
+}
+
+/// Check an `--auth-profile` name, which becomes part of volume names.
+pub fn parse_profile(value: &str) -> Result<String, String> {
+    let valid = value
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphanumeric())
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_.-".contains(c));
+    if valid {
+        Ok(value.to_owned())
+    } else {
+        Err(format!(
+            "invalid auth profile '{value}' (use letters, digits, '_', '.', and '-')"
+        ))
+    }
+}
+
+/// Where an agent's auth comes from, if anywhere: a store named on the
+/// command line, then one from config, then the host when the flag is on.
+pub fn resolve_store(
+    store: Option<AuthStore>,
+    config_store: Option<AuthStore>,
+    enabled: bool,
+) -> Option<AuthStore> {
+    store
+        .or(config_store)
+        .or(enabled.then_some(AuthStore::Host))
+}
+
+/// How an auth backup is encrypted with `age`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Encryption {
+    /// `age` prompts for a passphrase.
+    Passphrase,
+    /// Encrypt to these age or SSH public keys.
+    Recipients(Vec<String>),
+}
+
+impl Encryption {
+    /// Arguments for `age` to encrypt stdin to stdout.
+    pub fn age_args(&self) -> Vec<String> {
+        match self {
+            Encryption::Passphrase => vec!["--passphrase".to_owned()],
+            Encryption::Recipients(keys) => keys
+                .iter()
+                .flat_map(|key| ["--recipient".to_owned(), key.clone()])
+                .collect(),
+        }
+    }
+}
+
+/// Whether `header` starts an age file, binary or armored.
+pub fn is_age_file(header: &[u8]) -> bool {
+    header.starts_with(b"age-encryption.org/")
+        || header.starts_with(b"-----BEGIN AGE ENCRYPTED FILE-----")
+}
+
+/// Write the contents of `volume` to `file` as a gzipped tarball, using a
+/// throwaway container from `image`, and encrypt it with `age` if asked.
+pub fn export_volume(
+    engine: &dyn Engine,
+    image: &str,
+    volume: &str,
+    file: &Path,
+    encryption: Option<&Encryption>,
+) -> Result<()> {
+    if !engine.volume_exists(volume)? {
+        bail!("auth volume '{volume}' does not exist");
+    }
+    let mut tar = engine.command();
+    tar.arg("run")
+        .arg("--rm")
+        .arg("--user")
+        .arg("0:0")
+        .arg("-v")
+        .arg(format!("{volume}:/auth:ro"))
+        .arg(image)
+        .args(["tar", "-C", "/auth", "-czf", "-", "."]);
+    let tar_name = format!("{} run (export {volume})", engine.name());
+    let mut age = encryption.map(|encryption| {
+        let mut age = Command::new("age");
+        age.args(encryption.age_args());
+        age
+    });
+    if !dry_run() {
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let out = options
+            .open(file)
+            .with_context(|| format!("failed to create {}", file.display()))?;
+        match &mut age {
+            Some(age) => age.stdout(out),
+            None => tar.stdout(out),
+        };
+    }
+    let result = match &mut age {
+        Some(age) => run_piped(&mut tar, &tar_name, age, "age"),
+        None => run_checked(&mut tar, &tar_name),
+    };
+    if result.is_err() && !dry_run() {
+        // Don't leave a truncated backup that looks usable.
+        let _ = fs::remove_file(file);
+    }
+    result
+}
+
+/// Restore a tarball made by [`export_volume`] into a new `volume`, owned by
+/// the current user. Encrypted files are decrypted with `age`, using `identity`
+/// if given. An existing volume is replaced only with `force`.
+pub fn import_volume(
+    engine: &dyn Engine,
+    image: &str,
+    volume: &str,
+    labels: &[String],
+    file: &Path,
+    identity: Option<&Path>,
+    force: bool,
+) -> Result<()> {
+    let encrypted = if dry_run() {
+        identity.is_some()
+    } else {
+        let mut header = [0; 34];
+        let len = File::open(file)
+            .and_then(|mut input| input.read(&mut header))
+            .with_context(|| format!("failed to open {}", file.display()))?;
+        is_age_file(&header[..len])
+    };
+    if identity.is_some() && !encrypted {
+        bail!("{} is not encrypted with age", file.display());
+    }
+    if engine.volume_exists(volume)? {
+        if !force {
+            bail!("auth volume '{volume}' already exists; pass --force to replace it");
+        }
+        engine.remove_volume(volume)?;
+    }
+    engine.create_volume(volume, labels)?;
+
+    let mut tar = engine.command();
+    tar.arg("run")
+        .arg("--rm")
+        .arg("-i")
+        .arg("--user")
+        .arg("0:0")
+        .arg("-v")
+        .arg(format!("{volume}:/auth"))
+        .arg(image)
+        .arg("sh")
+        .arg("-c")
+        .arg(format!(
+            "tar -C /auth -xzf - && chown -R {}:{} /auth",
+            get_current_uid(),
+            get_current_gid()
+        ));
+    let tar_name = format!("{} run (import {volume})", engine.name());
+    if encrypted {
+        let mut age = Command::new("age");
+        age.arg("--decrypt");
+        if let Some(identity) = identity {
+            age.arg("--identity").arg(identity);
+        }
+        age.arg(file);
+        return run_piped(&mut age, "age", &mut tar, &tar_name);
+    }
+    if !dry_run() {
+        let input =
+            File::open(file).with_context(|| format!("failed to open {}", file.display()))?;
+        tar.stdin(input);
+    }
+    run_checked(&mut tar, &tar_name)
+}
+
+/// Run `first | second`, failing if either does (or print the pipeline in
+/// dry-run mode).
+fn run_piped(
+    first: &mut Command,
+    first_name: &str,
+    second: &mut Command,
+    second_name: &str,
+) -> Result<()> {
+    let line = format!("{} | {}", shell_command(first), shell_command(second));
+    tracing::debug!("{first_name} | {second_name}: {line}");
+    if dry_run() {
+        println!("{line}");
+        return Ok(());
+    }
+    let mut producer = first
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to run {first_name}"))?;
+    let pipe = producer.stdout.take().expect("stdout is piped");
+    let consumer = second.stdin(pipe).status();
+    let consumer = match consumer {
+        Ok(status) => status,
+        Err(err) => {
+            let _ = producer.kill();
+            let _ = producer.wait();
+            return Err(err).with_context(|| format!("failed to run {second_name}"));
+        }
+    };
+    let producer = producer
+        .wait()
+        .with_context(|| format!("failed to wait for {first_name}"))?;
+    for (status, name) in [(producer, first_name), (consumer, second_name)] {
+        if !status.success() {
+            return Err(DavyError::CommandFailed {
+                command: name.to_owned(),
+                code: status.code(),
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// What `davy auth status` reports for one agent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgentStatus {
+    pub agent: &'static str,
+    /// What the auth flag mounts from the host, and whether it exists;
+    /// `None` for agents whose auth only lives in a volume.
+    pub host: Option<(PathBuf, bool)>,
+    /// The agent's auth volume in the current profile, if it exists.
+    pub volume: Option<String>,
+    /// When a file in the volume last changed, which logins and token
+    /// refreshes do.
+    pub last_used: Option<DateTime<Local>>,
+    /// Login state read from the host files, where their format is known.
+    pub token: Option<TokenState>,
+}
+
+/// Login state read from an agent's credential file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenState {
+    /// An API key rather than a login.
+    ApiKey,
+    /// A token that has not expired.
+    Valid { expires: DateTime<Local> },
+    /// An expired token, which the agent renews if it has a refresh token.
+    Expired {
+        at: DateTime<Local>,
+        refreshable: bool,
+    },
+    /// A token with no expiry we can read.
+    Present,
+    /// The credential file has no token.
+    Missing,
+}
+
+impl TokenState {
+    /// Short description relative to `now`.
+    pub fn describe(&self, now: DateTime<Local>) -> String {
+        match *self {
+            TokenState::ApiKey => "api key".to_owned(),
+            TokenState::Valid { expires } => {
+                format!("valid, {} left", containers::format_duration(expires - now))
+            }
+            TokenState::Expired { at, refreshable } => format!(
+                "expired {} ago{}",
+                containers::format_duration(now - at),
+                if refreshable { ", refreshable" } else { "" }
+            ),
+            TokenState::Present => "present".to_owned(),
+            TokenState::Missing => "none".to_owned(),
+        }
+    }
+}
+
+/// Report host auth, auth volumes in `profile`, and login state for every
+/// agent `--auth-all` covers. Reading the volumes runs a container from
+/// `image`; if that fails, their last-used times are left out.
+pub fn status(
+    engine: &dyn Engine,
+    image: &str,
+    profile: Option<&str>,
+    home: &Path,
+) -> Result<Vec<AgentStatus>> {
+    let volumes = containers::auth_volumes(engine)?
+        .into_iter()
+        .filter(|volume| volume.profile.as_deref() == profile)
+        .collect::<Vec<_>>();
+    let names = volumes
+        .iter()
+        .map(|volume| volume.name.as_str())
+        .collect::<Vec<_>>();
+    let last_used = volumes_last_used(engine, image, &names).unwrap_or_else(|err| {
+        eprintln!("davy: warning: could not read auth volumes: {err:#}");
+        vec![None; names.len()]
+    });
+    let now = Local::now();
+    Ok(host_auth_paths(home)
+        .into_iter()
+        .map(|(agent, path)| {
+            let index = volumes.iter().position(|volume| volume.agent == agent);
+            AgentStatus {
+                agent,
+                token: path
+                    .as_deref()
+                    .and_then(|path| host_token(agent, path, now)),
+                host: path.map(|path| {
+                    let exists = path.exists();
+                    (path, exists)
+                }),
+                volume: index.map(|index| volumes[index].name.clone()),
+                last_used: index.and_then(|index| last_used[index]),
+            }
+        })
+        .collect())
+}
+
+/// What each auth flag mounts from the host, honoring the same overrides as
+/// the sandbox, in `--auth-all` order.
+pub fn host_auth_paths(home: &Path) -> Vec<(&'static str, Option<PathBuf>)> {
+    let dir = |var: &str, default: &str| {
+        env::var_os(var)
+            .filter(|value| !value.is_empty())
+            .map_or_else(|| home.join(default), PathBuf::from)
+    };
+    vec![
+        ("pi", Some(home.join(".pi/agent"))),
+        ("codex", Some(home.join(".codex"))),
+        ("gemini", Some(home.join(".gemini"))),
+        ("claude", None),
+        ("gh", Some(dir("GH_CONFIG_DIR", ".config/gh"))),
+        ("glab", Some(dir("GLAB_CONFIG_DIR", ".config/glab-cli"))),
+        ("aws", Some(home.join(".aws"))),
+        ("gcloud", Some(dir("CLOUDSDK_CONFIG", ".config/gcloud"))),
+        ("npm", Some(dir("NPM_CONFIG_USERCONFIG", ".npmrc"))),
+        ("pip", Some(dir("PIP_CONFIG_FILE", ".config/pip/pip.conf"))),
+        (
+            "cargo",
+            Some(dir("CARGO_HOME", ".cargo").join("credentials.toml")),
+        ),
+        ("aider", Some(home.join(".aider.conf.yml"))),
+        ("goose", Some(home.join(".config/goose"))),
+        ("opencode", Some(home.join(".local/share/opencode"))),
+        ("cursor", None),
+        ("copilot", Some(dir("XDG_CONFIG_HOME", "").join(".copilot"))),
+        ("azure", Some(dir("AZURE_CONFIG_DIR", ".azure"))),
+    ]
+}
+
+/// Login state from the host credential files of agents whose format we know.
+fn host_token(agent: &str, dir: &Path, now: DateTime<Local>) -> Option<TokenState> {
+    let file = match agent {
+        "codex" => "auth.json",
+        "gemini" => "oauth_creds.json",
+        _ => return None,
+    };
+    let text = fs::read_to_string(dir.join(file)).ok()?;
+    let json = serde_json::from_str::<Value>(&text).ok()?;
+    Some(match agent {
+        "codex" => codex_token(&json, now),
+        _ => gemini_token(&json, now),
+    })
+}
+
+/// Codex's `auth.json`: an API key, or OAuth tokens whose access token is a
+/// JWT.
+fn codex_token(json: &Value, now: DateTime<Local>) -> TokenState {
+    if json["OPENAI_API_KEY"]
+        .as_str()
+        .is_some_and(|key| !key.is_empty())
+    {
+        return TokenState::ApiKey;
+    }
+    let Some(access) = json["tokens"]["access_token"].as_str() else {
+        return TokenState::Missing;
+    };
+    let refreshable = json["tokens"]["refresh_token"]
+        .as_str()
+        .is_some_and(|token| !token.is_empty());
+    jwt_expiry(access).map_or(TokenState::Present, |expires| {
+        token_state(expires, refreshable, now)
+    })
+}
+
+/// Gemini CLI's `oauth_creds.json`, with `expiry_date` in milliseconds.
+fn gemini_token(json: &Value, now: DateTime<Local>) -> TokenState {
+    if json["access_token"].as_str().is_none() {
+        return TokenState::Missing;
+    }
+    let refreshable = json["refresh_token"].as_str().is_some();
+    json["expiry_date"]
+        .as_i64()
+        .and_then(DateTime::from_timestamp_millis)
+        .map_or(TokenState::Present, |expires| {
+            token_state(expires.with_timezone(&Local), refreshable, now)
+        })
+}
+
+fn token_state(expires: DateTime<Local>, refreshable: bool, now: DateTime<Local>) -> TokenState {
+    if expires > now {
+        TokenState::Valid { expires }
+    } else {
+        TokenState::Expired {
+            at: expires,
+            refreshable,
+        }
+    }
+}
+
+/// The `exp` claim of a JWT, without checking its signature.
+fn jwt_expiry(token: &str) -> Option<DateTime<Local>> {
+    let payload = URL_SAFE_NO_PAD
+        .decode(token.split('.').nth(1)?.trim_end_matches('='))
+        .ok()?;
+    let claims = serde_json::from_slice::<Value>(&payload).ok()?;
+    let expires = DateTime::from_timestamp(claims["exp"].as_i64()?, 0)?;
+    Some(expires.with_timezone(&Local))
+}
+
+/// When a file in each of `volumes` last changed, from one container that
+/// mounts them all.
+fn volumes_last_used(
+    engine: &dyn Engine,
+    image: &str,
+    volumes: &[&str],
+) -> Result<Vec<Option<DateTime<Local>>>> {
+    if volumes.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut args = vec!["run".to_owned(), "--rm".to_owned()];
+    for (index, volume) in volumes.iter().enumerate() {
+        args.extend(["-v".to_owned(), format!("{volume}:/v/{index}:ro")]);
+    }
+    args.extend([image, "find", "/v", "-type", "f", "-printf", "%T@ %p\\n"].map(str::to_owned));
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    let lines = containers::engine_lines(engine, &args)?;
+    Ok(newest_by_mount(&lines, volumes.len()))
+}
+
+/// The newest modification time under each `/v/N` in `find -printf '%T@ %p'`
+/// output.
+fn newest_by_mount(lines: &[String], count: usize) -> Vec<Option<DateTime<Local>>> {
+    let mut newest = vec![None; count];
+    for line in lines {
+        let Some((time, path)) = line.split_once(' ') else {
+            continue;
+        };
+        let secs = time
+            .split_once('.')
+            .map_or(time, |(secs, _)| secs)
+            .parse::<i64>();
+        let index = path
+            .strip_prefix("/v/")
+            .and_then(|rest| rest.split('/').next())
+            .and_then(|index| index.parse::<usize>().ok());
+        let (Ok(secs), Some(index)) = (secs, index) else {
+            continue;
+        };
+        if let (Some(slot), Some(time)) = (newest.get_mut(index), DateTime::from_timestamp(secs, 0))
+        {
+            let time = time.with_timezone(&Local);
+            if slot.is_none_or(|current| time > current) {
+                *slot = Some(time);
+            }
+        }
+    }
+    newest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_dirs_into_the_volume() {
+        assert_eq!(OPENCODE.volume_name(1000, None), "davy-opencode-auth-1000");
+        assert_eq!(
+            CODEX.volume_name(1000, Some("work")),
+            "davy-codex-auth-1000-work"
+        );
+        assert!(crate::containers::is_auth_volume_kind(&OPENCODE.kind()));
+        let script = OPENCODE.link_script();
+        assert!(script.contains(
+            "ln -sfn /home/dev/.opencode-auth/.local/share/opencode /home/dev/.local/share/opencode\n"
+        ));
+        assert!(script.ends_with("exec \"$@\""));
+    }
+
+    #[test]
+    fn checks_profile_names() {
+        assert_eq!(parse_profile("work-2").as_deref(), Ok("work-2"));
+        assert!(parse_profile("").is_err());
+        assert!(parse_profile("-x").is_err());
+        assert!(parse_profile("a/b").is_err());
+    }
+
+    #[test]
+    fn encrypts_backups_with_age() {
+        assert_eq!(Encryption::Passphrase.age_args(), ["--passphrase"]);
+        assert_eq!(
+            Encryption::Recipients(vec!["age1abc".to_owned(), "ssh-ed25519 AAAA".to_owned()])
+                .age_args(),
+            ["--recipient", "age1abc", "--recipient", "ssh-ed25519 AAAA"]
+        );
+        assert!(is_age_file(b"age-encryption.org/v1\n-> X25519"));
+        assert!(is_age_file(b"-----BEGIN AGE ENCRYPTED FILE-----\n"));
+        assert!(!is_age_file(&[0x1f, 0x8b, 0x08]));
+    }
+
+    #[test]
+    fn reads_token_expiry() {
+        let now = Local::now();
+        let hour_ago = (now.timestamp() - 3_600) * 1_000;
+        let gemini = serde_json::json!({
+            "access_token": "ya29.x",
+            "refresh_token": "1//x",
+            "expiry_date": hour_ago,
+        });
+        assert!(matches!(
+            gemini_token(&gemini, now),
+            TokenState::Expired {
+                refreshable: true,
+                ..
+            }
+        ));
+        assert_eq!(
+            codex_token(&serde_json::json!({"OPENAI_API_KEY": "sk-x"}), now),
+            TokenState::ApiKey
+        );
+        let payload = URL_SAFE_NO_PAD.encode(format!("{{\"exp\":{}}}", now.timestamp() + 7_200));
+        let codex = serde_json::json!({
+            "OPENAI_API_KEY": null,
+            "tokens": {"access_token": format!("e30.{payload}.sig")},
+        });
+        assert!(matches!(codex_token(&codex, now), TokenState::Valid { .. }));
+        assert_eq!(
+            codex_token(&serde_json::json!({}), now),
+            TokenState::Missing
+        );
+    }
+
+    #[test]
+    fn finds_newest_file_per_volume() {
+        let lines = [
+            "1700000000.5 /v/0/.codex/auth.json",
+            "1700000100.0 /v/0/.codex/config.toml",
+            "1600000000.0 /v/2/.claude.json",
+        ]
+        .map(str::to_owned);
+        let newest = newest_by_mount(&lines, 3);
+        assert_eq!(newest[0].map(|time| time.timestamp()), Some(1_700_000_100));
+        assert_eq!(newest[1], None);
+        assert_eq!(newest[2].map(|time| time.timestamp()), Some(1_600_000_000));
+    }
+
+    #[test]
+    fn named_stores_win_over_flags() {
+        assert_eq!(resolve_store(None, None, false), None);
+        assert_eq!(resolve_store(None, None, true), Some(AuthStore::Host));
+        assert_eq!(
+            resolve_store(None, Some(AuthStore::Volume), true),
+            Some(AuthStore::Volume)
+        );
+        assert_eq!(
+            resolve_store(Some(AuthStore::Host), Some(AuthStore::Volume), false),
+            Some(AuthStore::Host)
+        );
+    }
+}
@@ -0,0 +1,184 @@
+//! Volumes shared by all of a user's sandboxes that keep package manager and
+//! build caches warm across fresh containers.
+
+use std::str::FromStr;
+
+/// A tool cache made of one or more volumes.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Cache {
+    /// Name used in flags and volume names.
+    pub name: &'static str,
+    /// Volume name suffix and mount point of each volume.
+    pub mounts: &'static [(&'static str, &'static str)],
+    /// `KEY=VALUE` settings that point the tool at the mounts.
+    pub env: &'static [&'static str],
+    /// Startup script for settings that depend on the image.
+    pub script: Option<&'static str>,
+}
+
+/// Cargo's crates.io index and downloaded crates, and git dependencies.
+pub const CARGO: Cache = Cache {
+    name: "cargo",
+    mounts: &[
+        ("registry", "/home/dev/.cargo/registry"),
+        ("git", "/home/dev/.cargo/git"),
+    ],
+    env: &[],
+    script: None,
+};
+
+/// npm's cache, pnpm's home (which holds its store), and Yarn's cache.
+pub const NODE: Cache = Cache {
+    name: "node",
+    mounts: &[
+        ("npm", "/home/dev/.npm"),
+        ("pnpm", "/home/dev/.local/share/pnpm"),
+        ("yarn", "/home/dev/.cache/yarn"),
+    ],
+    env: &[],
+    script: None,
+};
+
+/// pip's and uv's download and wheel caches.
+pub const PYTHON: Cache = Cache {
+    name: "python",
+    mounts: &[
+        ("pip", "/home/dev/.cache/pip"),
+        ("uv", "/home/dev/.cache/uv"),
+    ],
+    env: &[],
+    script: None,
+};
+
+/// Go's module and build caches, kept under `~/.cache` so `~/go` is not
+/// created by the mount.
+pub const GO: Cache = Cache {
+    name: "go",
+    mounts: &[
+        ("mod", "/home/dev/.cache/go-mod"),
+        ("build", "/home/dev/.cache/go-build"),
+    ],
+    env: &[
+        "GOMODCACHE=/home/dev/.cache/go-mod",
+        "GOCACHE=/home/dev/.cache/go-build",
+    ],
+    script: None,
+};
+
+/// ccache's and sccache's object caches in one volume. `RUSTC_WRAPPER` is
+/// only set when the image has sccache, since cargo fails without it.
+pub const CC: Cache = Cache {
+    name: "cc",
+    mounts: &[("objects", "/home/dev/.cache/davy-cc")],
+    env: &[
+        "CCACHE_DIR=/home/dev/.cache/davy-cc/ccache",
+        "SCCACHE_DIR=/home/dev/.cache/davy-cc/sccache",
+    ],
+    script: Some(
+        r#"if [ -z "${RUSTC_WRAPPER:-}" ] && command -v sccache >/dev/null 2>&1; then
+  export RUSTC_WRAPPER=sccache
+fi
+exec "$@""#,
+    ),
+};
+
+impl Cache {
+    /// Volume names and mount points for `host_uid`.
+    pub fn volumes(&self, host_uid: u32) -> Vec<(String, String)> {
+        self.mounts
+            .iter()
+            .map(|(suffix, path)| {
+                (
+                    volume_name(host_uid, &format!("{}-{suffix}", self.name)),
+                    (*path).to_owned(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// A `--cache NAME:PATH` volume for any tool's cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheMount {
+    pub name: String,
+    /// Absolute path in the sandbox.
+    pub path: String,
+}
+
+impl FromStr for CacheMount {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<CacheMount, String> {
+        let invalid = || format!("invalid cache '{value}' (expected NAME:/PATH)");
+        let (name, path) = value.split_once(':').ok_or_else(invalid)?;
+        let valid_name = name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphanumeric())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "_.-".contains(c));
+        if !valid_name {
+            return Err(format!(
+                "invalid cache name '{name}' (use letters, digits, '_', '.', and '-')"
+            ));
+        }
+        if !path.starts_with('/') || path == "/" || path.contains(',') {
+            return Err(invalid());
+        }
+        Ok(CacheMount {
+            name: name.to_owned(),
+            path: path.trim_end_matches('/').to_owned(),
+        })
+    }
+}
+
+/// Name of the cache volume `name` for `host_uid`.
+pub fn volume_name(host_uid: u32, name: &str) -> String {
+    format!("davy-cache-{host_uid}-{name}")
+}
+
+/// Cache name of a cache volume, e.g. `cargo-registry` for
+/// `davy-cache-1000-cargo-registry`.
+pub fn cache_name(host_uid: u32, volume: &str) -> Option<&str> {
+    volume.strip_prefix(&format!("davy-cache-{host_uid}-"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cache_mounts() {
+        assert_eq!(
+            "bundler:/home/dev/.bundle/cache/".parse::<CacheMount>(),
+            Ok(CacheMount {
+                name: "bundler".to_owned(),
+                path: "/home/dev/.bundle/cache".to_owned()
+            })
+        );
+        assert!("bundler".parse::<CacheMount>().is_err());
+        assert!("bundler:relative".parse::<CacheMount>().is_err());
+        assert!("-x:/cache".parse::<CacheMount>().is_err());
+        assert!("a/b:/cache".parse::<CacheMount>().is_err());
+        assert_eq!(cache_name(1000, "davy-cache-1000-bundler"), Some("bundler"));
+        assert_eq!(cache_name(1000, "davy-home-1000"), None);
+    }
+
+    #[test]
+    fn names_cache_volumes() {
+        assert_eq!(
+            CARGO.volumes(1000),
+            [
+                (
+                    "davy-cache-1000-cargo-registry".to_owned(),
+                    "/home/dev/.cargo/registry".to_owned()
+                ),
+                (
+                    "davy-cache-1000-cargo-git".to_owned(),
+                    "/home/dev/.cargo/git".to_owned()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,82 @@
+//! Extra CA certificates, such as a TLS-intercepting proxy's, added to the
+//! sandbox's trust store at startup.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+
+/// Where the certificates are mounted read-only.
+pub const MOUNT_DIR: &str = "/run/davy/ca-certs";
+
+/// Adds the mounted certificates to the system store with the distro's tool
+/// and points Node, Python, and OpenSSL at the result. Without sudo (say,
+/// with `--hardened`), only Node is given them.
+pub const INSTALL_SCRIPT: &str = r#"set -e
+if [ -d /etc/pki/ca-trust/source/anchors ]; then
+  anchors=/etc/pki/ca-trust/source/anchors
+  update="update-ca-trust extract"
+  bundle=/etc/pki/tls/certs/ca-bundle.crt
+else
+  anchors=/usr/local/share/ca-certificates
+  update=update-ca-certificates
+  bundle=/etc/ssl/certs/ca-certificates.crt
+fi
+if sudo -n cp /run/davy/ca-certs/*.crt "$anchors/" 2>/dev/null && sudo -n $update >/dev/null 2>&1; then
+  export SSL_CERT_FILE="$bundle" REQUESTS_CA_BUNDLE="$bundle" NODE_EXTRA_CA_CERTS="$bundle"
+else
+  echo "davy: could not add the CA certificates to the system store; only Node will trust them" >&2
+  cat /run/davy/ca-certs/*.crt >/tmp/davy-ca-certs.pem
+  export NODE_EXTRA_CA_CERTS=/tmp/davy-ca-certs.pem
+fi
+
+exec "$@""#;
+
+/// Mount point for the `index`th certificate, named after its file. The
+/// `.crt` extension is what `update-ca-certificates` looks for.
+pub fn target(index: usize, path: &Path) -> String {
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("ca")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    format!("{MOUNT_DIR}/{index}-{stem}.crt")
+}
+
+/// Check that `path` holds PEM certificates, which both distros' tools need.
+pub fn check(path: &Path) -> Result<()> {
+    let contents = fs::read(path)
+        .with_context(|| format!("failed to read CA certificate {}", path.display()))?;
+    if !String::from_utf8_lossy(&contents).contains("-----BEGIN CERTIFICATE-----") {
+        bail!(
+            "{} is not a PEM certificate; convert it with `openssl x509 -inform der -in {0} -out cert.pem`",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_mounted_certificates() {
+        assert_eq!(
+            target(0, Path::new("/etc/corp/Root CA.pem")),
+            "/run/davy/ca-certs/0-Root_CA.crt"
+        );
+        assert_eq!(
+            target(1, Path::new("proxy.crt")),
+            "/run/davy/ca-certs/1-proxy.crt"
+        );
+    }
+}
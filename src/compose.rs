@@ -0,0 +1,82 @@
+use std::env;
+use std::path::Path;
+
+use anyhow::{Result, bail};
+
+use crate::containers::engine_lines;
+use crate::engine::Engine;
+
+const COMPOSE_PROJECT_LABEL: &str = "com.docker.compose.project";
+
+/// Compose's default project name: `COMPOSE_PROJECT_NAME`, else the project
+/// directory name lowercased with unsupported characters dropped.
+pub fn default_project_name(project_dir: &Path) -> String {
+    if let Some(name) = env::var("COMPOSE_PROJECT_NAME")
+        .ok()
+        .filter(|name| !name.is_empty())
+    {
+        return name;
+    }
+    project_dir
+        .file_name()
+        .map(|name| {
+            name.to_string_lossy()
+                .to_ascii_lowercase()
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Find the network created by the compose stack `project`.
+pub fn find_network(engine: &dyn Engine, project: &str) -> Result<String> {
+    let networks = engine_lines(
+        engine,
+        &[
+            "network",
+            "ls",
+            "--filter",
+            &format!("label={COMPOSE_PROJECT_LABEL}={project}"),
+            "--format",
+            "{{.Name}}",
+        ],
+    )?;
+    match pick_network(project, &networks) {
+        Some(network) => Ok(network.to_owned()),
+        None => bail!(
+            "no networks found for compose project '{project}' (is the stack running? try `docker compose up`)"
+        ),
+    }
+}
+
+/// Prefer the stack's `<project>_default` network, else the first one.
+fn pick_network<'a>(project: &str, networks: &'a [String]) -> Option<&'a str> {
+    let default = format!("{project}_default");
+    networks
+        .iter()
+        .find(|network| **network == default)
+        .or_else(|| networks.first())
+        .map(String::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefers_default_network() {
+        let networks = ["app_backend".to_owned(), "app_default".to_owned()];
+        assert_eq!(pick_network("app", &networks), Some("app_default"));
+        assert_eq!(pick_network("app", &networks[..1]), Some("app_backend"));
+        assert_eq!(pick_network("app", &[]), None);
+    }
+
+    #[test]
+    fn default_project_name_follows_compose_rules() {
+        if env::var_os("COMPOSE_PROJECT_NAME").is_some() {
+            return;
+        }
+        assert_eq!(default_project_name(Path::new("/work/My.App_2")), "myapp_2");
+    }
+}
@@ -0,0 +1,647 @@
+use std::path::Path;
+use std::process::Stdio;
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Local, TimeDelta};
+use serde_json::{Value, json};
+
+use crate::engine::Engine;
+use crate::error::DavyError;
+
+pub const MANAGED_LABEL: &str = "davy.managed";
+pub const PROJECT_LABEL: &str = "davy.project";
+pub const STARTED_LABEL: &str = "davy.started";
+pub const SSH_PORT_LABEL: &str = "davy.ssh_port";
+pub const VOLUME_KIND_LABEL: &str = "davy.volume";
+pub const AUTH_PROFILE_LABEL: &str = "davy.auth_profile";
+
+/// A davy-managed container as reported by `docker ps`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContainerInfo {
+    pub name: String,
+    pub project: String,
+    pub image: String,
+    pub started: Option<DateTime<Local>>,
+    pub ssh_port: Option<u16>,
+    pub state: String,
+}
+
+impl ContainerInfo {
+    pub fn is_running(&self) -> bool {
+        self.state == "running"
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        matches!(self.state.as_str(), "exited" | "created" | "dead")
+    }
+
+    /// Human-readable uptime for running containers, else the container state.
+    pub fn uptime(&self, now: DateTime<Local>) -> String {
+        match self.started {
+            Some(started) if self.is_running() => format_duration(now - started),
+            _ => self.state.clone(),
+        }
+    }
+}
+
+/// Docker labels attached to every container started by `davy run`.
+pub fn run_labels(project: &str, started: DateTime<Local>, ssh_port: Option<u16>) -> Vec<String> {
+    let mut labels = vec![
+        format!("{MANAGED_LABEL}=true"),
+        format!("{PROJECT_LABEL}={project}"),
+        format!("{STARTED_LABEL}={}", started.to_rfc3339()),
+    ];
+    if let Some(port) = ssh_port {
+        labels.push(format!("{SSH_PORT_LABEL}={port}"));
+    }
+    labels
+}
+
+/// Docker labels attached to volumes created by davy.
+pub fn volume_labels(kind: &str) -> Vec<String> {
+    vec![
+        format!("{MANAGED_LABEL}=true"),
+        format!("{VOLUME_KIND_LABEL}={kind}"),
+    ]
+}
+
+/// Labels for an auth volume, which also record its auth profile.
+pub fn auth_volume_labels(kind: &str, profile: Option<&str>) -> Vec<String> {
+    let mut labels = volume_labels(kind);
+    labels.extend(profile.map(|profile| format!("{AUTH_PROFILE_LABEL}={profile}")));
+    labels
+}
+
+/// Volume kind for project copies used with remote daemons.
+pub const PROJECT_VOLUME_KIND: &str = "project";
+
+/// Volume kind for the shared shell history.
+pub const HISTORY_VOLUME_KIND: &str = "history";
+
+/// Volume kind for `--persist-home` home directories.
+pub const HOME_VOLUME_KIND: &str = "home";
+
+/// Volume kind for per-project agent session history.
+pub const STATE_VOLUME_KIND: &str = "state";
+
+/// Volume kind for shared tool caches.
+pub const CACHE_VOLUME_KIND: &str = "cache";
+
+/// Auth volumes hold logins and are only removed by `davy auth ... reset`.
+pub fn is_auth_volume_kind(kind: &str) -> bool {
+    kind.ends_with("-auth")
+}
+
+/// List davy containers, optionally including stopped ones.
+pub fn query(engine: &dyn Engine, all: bool) -> Result<Vec<ContainerInfo>> {
+    let format = [
+        "{{.Names}}".to_owned(),
+        engine.label_template(PROJECT_LABEL),
+        "{{.Image}}".to_owned(),
+        engine.label_template(STARTED_LABEL),
+        engine.label_template(SSH_PORT_LABEL),
+        "{{.State}}".to_owned(),
+    ]
+    .join("\t");
+    let filter = format!("label={MANAGED_LABEL}");
+    let mut args = vec!["ps", "--filter", &filter, "--format", &format];
+    if all {
+        args.push("--all");
+    }
+
+    Ok(engine_lines(engine, &args)?
+        .iter()
+        .filter_map(|line| parse_ps_line(line))
+        .collect())
+}
+
+fn parse_ps_line(line: &str) -> Option<ContainerInfo> {
+    let mut fields = line.split('\t');
+    let name = fields.next().filter(|name| !name.is_empty())?.to_owned();
+    let project = fields.next().unwrap_or_default().to_owned();
+    let image = fields.next().unwrap_or_default().to_owned();
+    let started = fields
+        .next()
+        .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+        .map(|value| value.with_timezone(&Local));
+    let ssh_port = fields.next().and_then(|value| value.parse().ok());
+    let state = fields.next().unwrap_or_default().to_owned();
+
+    Some(ContainerInfo {
+        name,
+        project,
+        image,
+        started,
+        ssh_port,
+        state,
+    })
+}
+
+/// Davy containers whose project label matches `project_dir`. Stopped
+/// containers are only included when `all` is set.
+pub fn for_project(
+    engine: &dyn Engine,
+    project_dir: &Path,
+    all: bool,
+) -> Result<Vec<ContainerInfo>> {
+    let project = project_dir.display().to_string();
+    Ok(query(engine, all)?
+        .into_iter()
+        .filter(|container| container.project == project)
+        .collect())
+}
+
+/// Newest davy container whose project label matches `project_dir`.
+pub fn find_for_project(
+    engine: &dyn Engine,
+    project_dir: &Path,
+    all: bool,
+) -> Result<Option<ContainerInfo>> {
+    Ok(for_project(engine, project_dir, all)?
+        .into_iter()
+        .max_by_key(|container| container.started))
+}
+
+/// IDs of dangling images produced by `davy build`.
+pub fn dangling_images(engine: &dyn Engine) -> Result<Vec<String>> {
+    engine_lines(
+        engine,
+        &[
+            "image",
+            "ls",
+            "--quiet",
+            "--filter",
+            "dangling=true",
+            "--filter",
+            &format!("label={MANAGED_LABEL}"),
+        ],
+    )
+}
+
+/// Davy volumes not referenced by any container, excluding auth and project
+/// volumes (which may hold work that was never copied back).
+pub fn unused_volumes(engine: &dyn Engine) -> Result<Vec<String>> {
+    let lines = engine_lines(
+        engine,
+        &[
+            "volume",
+            "ls",
+            "--filter",
+            "dangling=true",
+            "--filter",
+            &format!("label={MANAGED_LABEL}"),
+            "--format",
+            &format!(
+                "{{{{.Name}}}}\t{}",
+                engine.label_template(VOLUME_KIND_LABEL)
+            ),
+        ],
+    )?;
+    Ok(lines
+        .into_iter()
+        .filter_map(|line| {
+            let (name, kind) = line.split_once('\t').unwrap_or((line.as_str(), ""));
+            (!kept_by_clean(kind)).then(|| name.to_owned())
+        })
+        .collect())
+}
+
+/// An auth volume, for `davy auth list`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthVolumeInfo {
+    pub name: String,
+    /// Agent the volume holds auth for, from its kind.
+    pub agent: String,
+    /// Auth profile, `None` for the default one.
+    pub profile: Option<String>,
+}
+
+/// Volumes `davy clean --volumes` leaves alone because they hold logins,
+/// work, or history rather than things davy can recreate.
+fn kept_by_clean(kind: &str) -> bool {
+    is_auth_volume_kind(kind)
+        || matches!(
+            kind,
+            PROJECT_VOLUME_KIND | HISTORY_VOLUME_KIND | HOME_VOLUME_KIND | STATE_VOLUME_KIND
+        )
+}
+
+/// Davy auth volumes, sorted by profile then agent.
+pub fn auth_volumes(engine: &dyn Engine) -> Result<Vec<AuthVolumeInfo>> {
+    let lines = engine_lines(
+        engine,
+        &[
+            "volume",
+            "ls",
+            "--filter",
+            &format!("label={MANAGED_LABEL}"),
+            "--format",
+            &format!(
+                "{{{{.Name}}}}\t{}\t{}",
+                engine.label_template(VOLUME_KIND_LABEL),
+                engine.label_template(AUTH_PROFILE_LABEL)
+            ),
+        ],
+    )?;
+    let mut volumes = lines
+        .iter()
+        .filter_map(|line| parse_auth_volume_line(line))
+        .collect::<Vec<_>>();
+    volumes.sort_by(|a, b| (&a.profile, &a.agent).cmp(&(&b.profile, &b.agent)));
+    Ok(volumes)
+}
+
+fn parse_auth_volume_line(line: &str) -> Option<AuthVolumeInfo> {
+    let mut fields = line.split('\t');
+    let name = fields.next()?.to_owned();
+    let agent = fields.next()?.strip_suffix("-auth")?.to_owned();
+    let profile = fields.next().and_then(label_value).map(str::to_owned);
+    Some(AuthVolumeInfo {
+        name,
+        agent,
+        profile,
+    })
+}
+
+/// A davy volume, for `davy volumes`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VolumeInfo {
+    pub name: String,
+    /// Volume kind label, empty for volumes davy did not label.
+    pub kind: String,
+    /// Auth profile of an auth volume.
+    pub profile: Option<String>,
+    pub created: Option<DateTime<Local>>,
+}
+
+impl VolumeInfo {
+    /// The feature that owns the volume.
+    pub fn feature(&self) -> String {
+        let feature = if let Some(agent) = self.kind.strip_suffix("-auth") {
+            format!("{agent} auth")
+        } else {
+            match self.kind.as_str() {
+                PROJECT_VOLUME_KIND => "project copy".to_owned(),
+                "" => "-".to_owned(),
+                kind => kind.to_owned(),
+            }
+        };
+        match &self.profile {
+            Some(profile) => format!("{feature} ({profile})"),
+            None => feature,
+        }
+    }
+
+    pub fn is_auth(&self) -> bool {
+        is_auth_volume_kind(&self.kind)
+    }
+}
+
+/// Volumes davy labeled or named `davy-*`, sorted by name.
+pub fn volumes(engine: &dyn Engine) -> Result<Vec<VolumeInfo>> {
+    let lines = engine_lines(
+        engine,
+        &[
+            "volume",
+            "ls",
+            "--format",
+            &format!(
+                "{{{{.Name}}}}\t{}\t{}\t{}",
+                engine.label_template(MANAGED_LABEL),
+                engine.label_template(VOLUME_KIND_LABEL),
+                engine.label_template(AUTH_PROFILE_LABEL)
+            ),
+        ],
+    )?;
+    let mut volumes = lines
+        .iter()
+        .filter_map(|line| parse_volume_line(line))
+        .collect::<Vec<_>>();
+    volumes.sort_by(|a, b| a.name.cmp(&b.name));
+    if volumes.is_empty() {
+        return Ok(volumes);
+    }
+
+    let mut args = vec!["volume", "inspect", "--format", "{{.Name}}\t{{.CreatedAt}}"];
+    args.extend(volumes.iter().map(|volume| volume.name.as_str()));
+    for line in engine_lines(engine, &args)? {
+        let Some((name, created)) = line.split_once('\t') else {
+            continue;
+        };
+        if let Some(volume) = volumes.iter_mut().find(|volume| volume.name == name) {
+            volume.created = parse_created(created);
+        }
+    }
+    Ok(volumes)
+}
+
+fn parse_volume_line(line: &str) -> Option<VolumeInfo> {
+    let mut fields = line.split('\t').map(label_value);
+    let name = fields.next().flatten()?;
+    let managed = fields.next().flatten().is_some_and(|value| value == "true");
+    if !managed && !name.starts_with("davy-") {
+        return None;
+    }
+    Some(VolumeInfo {
+        name: name.to_owned(),
+        kind: fields.next().flatten().unwrap_or_default().to_owned(),
+        profile: fields.next().flatten().map(str::to_owned),
+        created: None,
+    })
+}
+
+/// A label value from a format template, `None` when unset.
+fn label_value(value: &str) -> Option<&str> {
+    let value = value.trim();
+    // Podman prints `<no value>` for a missing label.
+    (!value.is_empty() && value != "<no value>").then_some(value)
+}
+
+/// `CreatedAt` as Docker (RFC 3339) or Podman (Go's default format) print it.
+fn parse_created(value: &str) -> Option<DateTime<Local>> {
+    let value = value.trim();
+    DateTime::parse_from_rfc3339(value)
+        .or_else(|_| {
+            let value = value.rsplit_once(' ').map_or(value, |(time, _zone)| time);
+            DateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f %z")
+        })
+        .ok()
+        .map(|time| time.with_timezone(&Local))
+}
+
+/// Disk use of each volume in KiB, from one container that mounts them all.
+pub fn volume_sizes(engine: &dyn Engine, image: &str, names: &[&str]) -> Result<Vec<Option<u64>>> {
+    if names.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mounts = names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| format!("{name}:/v/{index}:ro"))
+        .collect::<Vec<_>>();
+    let mut args = vec!["run", "--rm", "--user", "0:0"];
+    for mount in &mounts {
+        args.extend(["-v", mount]);
+    }
+    args.extend([image, "sh", "-c", "du -sk /v/*"]);
+    let lines = engine_lines(engine, &args)?;
+    let mut sizes = vec![None; names.len()];
+    for line in lines {
+        let mut fields = line.split_whitespace();
+        let (Some(size), Some(path)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let index = path
+            .strip_prefix("/v/")
+            .and_then(|index| index.parse::<usize>().ok());
+        if let (Ok(size), Some(slot)) = (size.parse(), index.and_then(|index| sizes.get_mut(index)))
+        {
+            *slot = Some(size);
+        }
+    }
+    Ok(sizes)
+}
+
+/// A KiB count as a short size like `12K`, `3.4M`, or `1.2G`.
+pub fn format_size(kib: u64) -> String {
+    const UNITS: [&str; 4] = ["K", "M", "G", "T"];
+    let mut size = kib as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 || size >= 10.0 {
+        format!("{size:.0}{}", UNITS[unit])
+    } else {
+        format!("{size:.1}{}", UNITS[unit])
+    }
+}
+
+/// Run an engine subcommand and collect its non-empty stdout lines.
+pub fn engine_lines(engine: &dyn Engine, args: &[&str]) -> Result<Vec<String>> {
+    let name = format!(
+        "{} {}",
+        engine.name(),
+        args.iter()
+            .take_while(|arg| !arg.starts_with('-'))
+            .copied()
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+    tracing::trace!("{name}: {}", args.join(" "));
+    let output = engine
+        .command()
+        .args(args)
+        .stderr(Stdio::inherit())
+        .output()
+        .with_context(|| format!("failed to run {name}"))?;
+    if !output.status.success() {
+        bail!("{name} failed");
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_owned)
+        .collect())
+}
+
+pub fn is_davy_container(engine: &dyn Engine, name: &str) -> Result<bool> {
+    let output = engine
+        .command()
+        .arg("container")
+        .arg("inspect")
+        .arg("--format")
+        .arg(format!("{{{{index .Config.Labels \"{MANAGED_LABEL}\"}}}}"))
+        .arg(name)
+        .stderr(Stdio::null())
+        .output()
+        .with_context(|| format!("failed to run {} container inspect", engine.name()))?;
+    if !output.status.success() {
+        return Err(DavyError::ContainerNotFound {
+            name: name.to_owned(),
+        }
+        .into());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim() == "true")
+}
+
+/// Render containers as an aligned table for `davy ls`.
+pub fn render_table(containers: &[ContainerInfo], now: DateTime<Local>) -> String {
+    let rows = containers
+        .iter()
+        .map(|container| {
+            vec![
+                container.name.clone(),
+                container.project.clone(),
+                container.image.clone(),
+                container.uptime(now),
+                container
+                    .ssh_port
+                    .map(|port| port.to_string())
+                    .unwrap_or_else(|| "-".to_owned()),
+            ]
+        })
+        .collect::<Vec<_>>();
+    render_columns(&["NAME", "PROJECT", "IMAGE", "UPTIME", "SSH"], &rows)
+}
+
+/// Render rows as left-aligned columns under `header`.
+pub fn render_columns(header: &[&str], rows: &[Vec<String>]) -> String {
+    let header = header
+        .iter()
+        .map(|cell| (*cell).to_owned())
+        .collect::<Vec<_>>();
+    let mut widths = header.iter().map(String::len).collect::<Vec<_>>();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut out = String::new();
+    for row in std::iter::once(&header).chain(rows) {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// Containers as JSON for `davy ls --json`. Field names are stable;
+/// `started` is RFC 3339 and `uptime_seconds` is null unless running.
+pub fn to_json(containers: &[ContainerInfo], now: DateTime<Local>) -> Value {
+    containers
+        .iter()
+        .map(|container| {
+            json!({
+                "name": container.name,
+                "project": container.project,
+                "image": container.image,
+                "state": container.state,
+                "started": container.started.map(|started| started.to_rfc3339()),
+                "uptime_seconds": container
+                    .started
+                    .filter(|_| container.is_running())
+                    .map(|started| (now - started).num_seconds().max(0)),
+                "ssh_port": container.ssh_port,
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn format_duration(duration: TimeDelta) -> String {
+    let secs = duration.num_seconds().max(0);
+    let (days, hours, minutes) = (secs / 86_400, secs / 3_600 % 24, secs / 60 % 60);
+    if days > 0 {
+        format!("{days}d {hours}h")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else if minutes > 0 {
+        format!("{minutes}m")
+    } else {
+        format!("{secs}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ps_line_with_labels() {
+        let info = parse_ps_line(
+            "davy-app-1\t/work/app\tdavy-sandbox:latest\t2025-01-01T12:00:00+00:00\t2200\trunning",
+        )
+        .expect("line should parse");
+        assert_eq!(info.name, "davy-app-1");
+        assert_eq!(info.project, "/work/app");
+        assert_eq!(info.ssh_port, Some(2200));
+        assert!(info.started.is_some());
+        assert!(info.is_running());
+    }
+
+    #[test]
+    fn parses_ps_line_without_optional_labels() {
+        let info = parse_ps_line("davy-old\t\tdavy-sandbox:latest\t\t\texited")
+            .expect("line should parse");
+        assert_eq!(info.started, None);
+        assert_eq!(info.ssh_port, None);
+        assert_eq!(info.uptime(Local::now()), "exited");
+    }
+
+    #[test]
+    fn parses_auth_volume_lines() {
+        let volume = parse_auth_volume_line("davy-codex-auth-1000-work\tcodex-auth\twork")
+            .expect("line should parse");
+        assert_eq!(volume.agent, "codex");
+        assert_eq!(volume.profile.as_deref(), Some("work"));
+        let volume = parse_auth_volume_line("davy-claude-auth-1000-v1\tclaude-auth\t<no value>")
+            .expect("line should parse");
+        assert_eq!(volume.profile, None);
+        assert_eq!(parse_auth_volume_line("davy-cache\tcache\t"), None);
+    }
+
+    #[test]
+    fn parses_volume_lines() {
+        let volume = parse_volume_line("davy-claude-auth-1000-work\ttrue\tclaude-auth\twork")
+            .expect("line should parse");
+        assert_eq!(volume.feature(), "claude auth (work)");
+        assert!(volume.is_auth());
+        let volume = parse_volume_line("davy-old\t<no value>\t<no value>\t<no value>")
+            .expect("davy-* names are kept");
+        assert_eq!(volume.feature(), "-");
+        assert_eq!(parse_volume_line("pgdata\t\t\t"), None);
+
+        let docker = parse_created("2025-01-01T12:00:00Z").expect("RFC 3339");
+        let podman = parse_created("2025-01-01 12:00:00.123456 +0000 UTC").expect("Go format");
+        assert_eq!(docker.timestamp(), podman.timestamp());
+        assert_eq!(format_size(12), "12K");
+        assert_eq!(format_size(3_500), "3.4M");
+        assert_eq!(format_size(50 * 1024 * 1024), "50G");
+    }
+
+    #[test]
+    fn json_output_uses_stable_fields() {
+        let info = parse_ps_line(
+            "davy-app-1\t/work/app\tdavy-sandbox:latest\t2025-01-01T12:00:00+00:00\t\trunning",
+        )
+        .expect("line should parse");
+        let now = info.started.unwrap() + TimeDelta::minutes(2);
+        let value = to_json(&[info], now);
+        assert_eq!(value[0]["name"], "davy-app-1");
+        assert_eq!(value[0]["state"], "running");
+        assert_eq!(value[0]["uptime_seconds"], 120);
+        assert!(value[0]["ssh_port"].is_null());
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(TimeDelta::seconds(42)), "42s");
+        assert_eq!(format_duration(TimeDelta::minutes(5)), "5m");
+        assert_eq!(format_duration(TimeDelta::minutes(125)), "2h 5m");
+        assert_eq!(format_duration(TimeDelta::hours(50)), "2d 2h");
+    }
+
+    #[test]
+    fn auth_volumes_are_protected() {
+        assert!(is_auth_volume_kind("claude-auth"));
+        assert!(!is_auth_volume_kind("cache"));
+    }
+
+    #[test]
+    fn run_labels_include_ssh_port_when_exposed() {
+        let labels = run_labels("/work/app", Local::now(), Some(222));
+        assert_eq!(labels[0], "davy.managed=true");
+        assert_eq!(labels[1], "davy.project=/work/app");
+        assert_eq!(labels.last().map(String::as_str), Some("davy.ssh_port=222"));
+    }
+}
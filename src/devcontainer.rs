@@ -0,0 +1,335 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+
+use crate::note;
+
+/// Lookup order for a project's dev container definition.
+pub const DEVCONTAINER_FILES: [&str; 2] = [".devcontainer/devcontainer.json", ".devcontainer.json"];
+
+/// The subset of `devcontainer.json` davy understands.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct Spec {
+    image: Option<String>,
+    build: Option<BuildSpec>,
+    /// Pre-`build` spelling of `build.dockerfile`.
+    docker_file: Option<String>,
+    context: Option<String>,
+    container_env: BTreeMap<String, String>,
+    mounts: Vec<MountSpec>,
+    forward_ports: Vec<PortSpec>,
+    post_create_command: Option<CommandSpec>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct BuildSpec {
+    dockerfile: Option<String>,
+    context: Option<String>,
+    args: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum MountSpec {
+    /// Already in `--mount` syntax.
+    Raw(String),
+    Fields {
+        source: Option<String>,
+        target: String,
+        #[serde(rename = "type", default = "default_mount_type")]
+        kind: String,
+    },
+}
+
+fn default_mount_type() -> String {
+    "bind".to_owned()
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum PortSpec {
+    Port(u16),
+    /// `host:port`, forwarding from another service.
+    Service(String),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum CommandSpec {
+    Shell(String),
+    Exec(Vec<String>),
+    /// Named commands, run one after another.
+    Parallel(BTreeMap<String, CommandSpec>),
+}
+
+/// Where the sandbox image comes from.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ImageSource {
+    Image(String),
+    Dockerfile {
+        dockerfile: PathBuf,
+        context_dir: PathBuf,
+        build_args: Vec<(String, String)>,
+    },
+}
+
+/// A resolved dev container definition.
+#[derive(Debug)]
+pub struct DevContainer {
+    pub image: Option<ImageSource>,
+    pub env: Vec<String>,
+    /// `--mount` values.
+    pub mounts: Vec<String>,
+    pub forward_ports: Vec<u16>,
+    /// Shell script run before the sandbox command.
+    pub post_create: Option<String>,
+}
+
+/// Find `devcontainer.json`: `path` when given, else the standard locations.
+pub fn find(project_dir: &Path, path: Option<&Path>) -> Result<PathBuf> {
+    if let Some(path) = path {
+        let path = project_dir.join(path);
+        if !path.is_file() {
+            bail!("devcontainer file not found: {}", path.display());
+        }
+        return Ok(path);
+    }
+    DEVCONTAINER_FILES
+        .iter()
+        .map(|name| project_dir.join(name))
+        .find(|path| path.is_file())
+        .with_context(|| {
+            format!(
+                "no devcontainer.json found in {} (looked for {})",
+                project_dir.display(),
+                DEVCONTAINER_FILES.join(", ")
+            )
+        })
+}
+
+pub fn load(path: &Path, project_dir: &Path) -> Result<DevContainer> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let spec: Spec =
+        json5::from_str(&content).with_context(|| format!("failed to parse {}", path.display()))?;
+    let base_dir = path.parent().unwrap_or(project_dir);
+    Ok(resolve(spec, base_dir, project_dir))
+}
+
+fn resolve(spec: Spec, base_dir: &Path, project_dir: &Path) -> DevContainer {
+    let vars = |value: &str| substitute(value, project_dir);
+
+    let dockerfile = spec
+        .build
+        .as_ref()
+        .and_then(|build| build.dockerfile.clone())
+        .or(spec.docker_file);
+    let image = match (spec.image, dockerfile) {
+        (_, Some(dockerfile)) => {
+            let build = spec.build.unwrap_or_default();
+            let context = build
+                .context
+                .or(spec.context)
+                .unwrap_or_else(|| ".".to_owned());
+            Some(ImageSource::Dockerfile {
+                dockerfile: base_dir.join(vars(&dockerfile)),
+                context_dir: base_dir.join(vars(&context)),
+                build_args: build
+                    .args
+                    .iter()
+                    .map(|(key, value)| (key.clone(), vars(value)))
+                    .collect(),
+            })
+        }
+        (Some(image), None) => Some(ImageSource::Image(vars(&image))),
+        (None, None) => None,
+    };
+
+    let mounts = spec
+        .mounts
+        .iter()
+        .map(|mount| match mount {
+            MountSpec::Raw(raw) => vars(raw),
+            MountSpec::Fields {
+                source: Some(source),
+                target,
+                kind,
+            } => format!(
+                "type={kind},source={},target={}",
+                vars(source),
+                vars(target)
+            ),
+            MountSpec::Fields {
+                source: None,
+                target,
+                kind,
+            } => format!("type={kind},target={}", vars(target)),
+        })
+        .collect();
+
+    let mut forward_ports = Vec::new();
+    for port in spec.forward_ports {
+        match port {
+            PortSpec::Port(port) => forward_ports.push(port),
+            PortSpec::Service(service) => {
+                note!("ignoring devcontainer forwardPorts entry '{service}'.")
+            }
+        }
+    }
+
+    DevContainer {
+        image,
+        env: spec
+            .container_env
+            .iter()
+            .map(|(key, value)| format!("{key}={}", vars(value)))
+            .collect(),
+        mounts,
+        forward_ports,
+        post_create: spec
+            .post_create_command
+            .map(|command| command_script(&command)),
+    }
+}
+
+fn command_script(command: &CommandSpec) -> String {
+    match command {
+        CommandSpec::Shell(script) => script.clone(),
+        CommandSpec::Exec(args) => args
+            .iter()
+            .map(|arg| shell_quote(arg))
+            .collect::<Vec<_>>()
+            .join(" "),
+        CommandSpec::Parallel(commands) => commands
+            .values()
+            .map(command_script)
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// Expand the `${...}` variables dev containers commonly use.
+fn substitute(value: &str, project_dir: &Path) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            rest = &rest[start..];
+            break;
+        };
+        let name = &rest[start + 2..start + end];
+        let replacement = match name {
+            "localWorkspaceFolder" => Some(project_dir.display().to_string()),
+            "localWorkspaceFolderBasename" => project_dir
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned()),
+            "containerWorkspaceFolder" => Some("/project".to_owned()),
+            "containerWorkspaceFolderBasename" => Some("project".to_owned()),
+            _ => name.strip_prefix("localEnv:").map(|var| {
+                let (var, default) = var.split_once(':').unwrap_or((var, ""));
+                env::var(var).unwrap_or_else(|_| default.to_owned())
+            }),
+        };
+        match replacement {
+            Some(replacement) => out.push_str(&replacement),
+            None => out.push_str(&rest[start..=start + end]),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> DevContainer {
+        let spec: Spec = json5::from_str(content).expect("devcontainer should parse");
+        resolve(
+            spec,
+            Path::new("/work/app/.devcontainer"),
+            Path::new("/work/app"),
+        )
+    }
+
+    #[test]
+    fn reads_dockerfile_build_with_comments() {
+        let dc = parse(
+            r#"{
+                // JSON with comments
+                "build": { "dockerfile": "Dockerfile", "context": "..", "args": { "VARIANT": "3.12" } },
+                "containerEnv": { "APP_ROOT": "${containerWorkspaceFolder}" },
+                "forwardPorts": [3000, "db:5432"],
+                "postCreateCommand": "npm ci",
+            }"#,
+        );
+        assert_eq!(
+            dc.image,
+            Some(ImageSource::Dockerfile {
+                dockerfile: PathBuf::from("/work/app/.devcontainer/Dockerfile"),
+                context_dir: PathBuf::from("/work/app/.devcontainer/.."),
+                build_args: vec![("VARIANT".to_owned(), "3.12".to_owned())],
+            })
+        );
+        assert_eq!(dc.env, vec!["APP_ROOT=/project".to_owned()]);
+        assert_eq!(dc.forward_ports, vec![3000]);
+        assert_eq!(dc.post_create.as_deref(), Some("npm ci"));
+    }
+
+    #[test]
+    fn reads_image_mounts_and_exec_commands() {
+        let dc = parse(
+            r#"{
+                "image": "mcr.microsoft.com/devcontainers/rust:1",
+                "mounts": [
+                    "source=${localWorkspaceFolder}/.cache,target=/cache,type=bind",
+                    { "source": "cargo-cache", "target": "/usr/local/cargo", "type": "volume" }
+                ],
+                "postCreateCommand": ["cargo", "fetch", "it's"]
+            }"#,
+        );
+        assert_eq!(
+            dc.image,
+            Some(ImageSource::Image(
+                "mcr.microsoft.com/devcontainers/rust:1".to_owned()
+            ))
+        );
+        assert_eq!(
+            dc.mounts,
+            vec![
+                "source=/work/app/.cache,target=/cache,type=bind".to_owned(),
+                "type=volume,source=cargo-cache,target=/usr/local/cargo".to_owned(),
+            ]
+        );
+        assert_eq!(
+            dc.post_create.as_deref(),
+            Some(r"'cargo' 'fetch' 'it'\''s'")
+        );
+    }
+
+    #[test]
+    fn substitutes_known_variables_only() {
+        let project = Path::new("/work/app");
+        assert_eq!(
+            substitute("${localWorkspaceFolderBasename}-${unknown}", project),
+            "app-${unknown}"
+        );
+        assert_eq!(
+            substitute("${localEnv:DAVY_UNSET_VAR:fallback}", project),
+            "fallback"
+        );
+        assert_eq!(substitute("a-${unterminated", project), "a-${unterminated");
+    }
+}
@@ -0,0 +1,172 @@
+use std::fmt;
+use std::fs;
+#[cfg(unix)]
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::Path;
+use std::process::Stdio;
+use std::str::FromStr;
+
+use anyhow::{Result, bail};
+
+use crate::engine::{Engine, find_on_path};
+use crate::error::DavyError;
+
+/// Host binaries that come with the NVIDIA Container Toolkit.
+const NVIDIA_TOOLKIT_BINARIES: [&str; 3] = [
+    "nvidia-ctk",
+    "nvidia-container-runtime-hook",
+    "nvidia-container-toolkit",
+];
+
+/// GPUs to pass through with `--gpus`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gpus {
+    All,
+    /// The first N GPUs.
+    Count(u32),
+}
+
+impl Gpus {
+    /// Value for `NVIDIA_VISIBLE_DEVICES`.
+    pub fn visible_devices(self) -> String {
+        match self {
+            Gpus::All => "all".to_owned(),
+            Gpus::Count(count) => (0..count)
+                .map(|index| index.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+        }
+    }
+}
+
+impl FromStr for Gpus {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Gpus, String> {
+        if value == "all" {
+            return Ok(Gpus::All);
+        }
+        match value.parse::<u32>() {
+            Ok(count) if count > 0 => Ok(Gpus::Count(count)),
+            _ => Err(format!(
+                "invalid GPU count '{value}' (expected 'all' or a number greater than 0)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Gpus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Gpus::All => f.write_str("all"),
+            Gpus::Count(count) => write!(f, "{count}"),
+        }
+    }
+}
+
+/// Fail with install instructions unless the NVIDIA Container Toolkit is
+/// available: either its binaries are on this machine's `PATH` (local
+/// daemons only) or the daemon reports an `nvidia` runtime.
+pub fn check_nvidia_toolkit(engine: &dyn Engine, local_daemon: bool) -> Result<()> {
+    if local_daemon
+        && let Some(binary) = NVIDIA_TOOLKIT_BINARIES
+            .iter()
+            .find(|binary| find_on_path(binary))
+    {
+        tracing::debug!("found NVIDIA Container Toolkit binary {binary}");
+        return Ok(());
+    }
+    if has_nvidia_runtime(engine) {
+        tracing::debug!("{} reports an nvidia runtime", engine.name());
+        return Ok(());
+    }
+    bail!(
+        "--gpus needs the NVIDIA Container Toolkit, which was not found (looked for {} on PATH and an nvidia runtime in `{} info`); install it (https://docs.nvidia.com/datacenter/cloud-native/container-toolkit/latest/install-guide.html), then run `sudo nvidia-ctk runtime configure --runtime={}` and restart the daemon",
+        NVIDIA_TOOLKIT_BINARIES.join(", "),
+        engine.binary(),
+        engine.binary()
+    );
+}
+
+fn has_nvidia_runtime(engine: &dyn Engine) -> bool {
+    engine
+        .command()
+        .arg("info")
+        .arg("--format")
+        .arg("{{json .Runtimes}}")
+        .stderr(Stdio::null())
+        .output()
+        .is_ok_and(|output| {
+            output.status.success() && String::from_utf8_lossy(&output.stdout).contains("nvidia")
+        })
+}
+
+/// Host side of a `--device HOST[:CONTAINER[:PERMISSIONS]]` spec.
+pub fn device_host_path(spec: &str) -> &Path {
+    Path::new(spec.split(':').next().unwrap_or(spec))
+}
+
+/// Check that the host side of a `--device` spec is a device node and return
+/// the group that owns it, for `--group-add`.
+pub fn device_gid(spec: &str) -> Result<u32> {
+    let path = device_host_path(spec);
+    let Ok(metadata) = fs::metadata(path) else {
+        return Err(DavyError::DeviceMissing {
+            path: path.to_path_buf(),
+        }
+        .into());
+    };
+
+    #[cfg(unix)]
+    {
+        let file_type = metadata.file_type();
+        if !file_type.is_char_device() && !file_type.is_block_device() {
+            return Err(DavyError::NotADevice {
+                path: path.to_path_buf(),
+            }
+            .into());
+        }
+        Ok(metadata.gid())
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        bail!("--device is only supported on unix hosts");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_gpu_requests() {
+        assert_eq!("all".parse(), Ok(Gpus::All));
+        assert_eq!("2".parse(), Ok(Gpus::Count(2)));
+        assert!("0".parse::<Gpus>().is_err());
+        assert!("many".parse::<Gpus>().is_err());
+        assert_eq!(Gpus::Count(3).visible_devices(), "0,1,2");
+        assert_eq!(Gpus::All.visible_devices(), "all");
+        assert_eq!(Gpus::Count(2).to_string(), "2");
+    }
+
+    #[test]
+    fn devices_must_be_device_nodes() {
+        assert_eq!(
+            device_host_path("/dev/ttyUSB0:/dev/ttyACM0:rw"),
+            Path::new("/dev/ttyUSB0")
+        );
+        assert!(device_gid("/dev/null").is_ok());
+        let err = device_gid("/dev/davy-missing").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<DavyError>(),
+            Some(DavyError::DeviceMissing { .. })
+        ));
+        let err = device_gid(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml")).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<DavyError>(),
+            Some(DavyError::NotADevice { .. })
+        ));
+    }
+}
@@ -0,0 +1,169 @@
+//! GUI passthrough: the host's X11 display, with its xauth cookie, and its
+//! Wayland socket.
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+
+use crate::write_private_file;
+
+/// Directory X servers put their sockets in.
+const X11_SOCKET_DIR: &str = "/tmp/.X11-unix";
+/// Where the sandbox finds the forwarded cookie.
+const XAUTHORITY_TARGET: &str = "/tmp/.davy-xauthority";
+/// xauth family that matches any host, since the sandbox has its own
+/// hostname.
+const FAMILY_WILD: [u8; 2] = [0xff, 0xff];
+
+/// Volumes and environment that expose a host display to the sandbox.
+#[derive(Debug, Default, PartialEq)]
+pub struct Passthrough {
+    /// `-v` values.
+    pub volumes: Vec<String>,
+    /// `-e` values.
+    pub env: Vec<String>,
+}
+
+/// Forward the X display in `$DISPLAY`: mount the socket directory and, if
+/// `xauth` has a cookie for it, a copy usable under any hostname.
+pub fn x11() -> Result<Passthrough> {
+    let host_display = env::var("DISPLAY").unwrap_or_default();
+    if host_display.is_empty() {
+        bail!("--x11 needs DISPLAY to be set");
+    }
+    let number = local_display_number(&host_display).with_context(|| {
+        format!("--x11 only forwards local X sockets, but DISPLAY is '{host_display}'")
+    })?;
+    let socket = Path::new(X11_SOCKET_DIR).join(format!("X{number}"));
+    if !socket.exists() {
+        bail!("X socket {} does not exist", socket.display());
+    }
+
+    let mut passthrough = Passthrough {
+        volumes: vec![format!("{X11_SOCKET_DIR}:{X11_SOCKET_DIR}:ro")],
+        env: vec![format!("DISPLAY=:{number}")],
+    };
+    match xauth_cookie(&host_display) {
+        Some(cookie) => {
+            let path = write_private_file(&format!("xauthority-{number}"), &cookie)?;
+            passthrough
+                .volumes
+                .push(format!("{}:{XAUTHORITY_TARGET}:ro", path.display()));
+            passthrough
+                .env
+                .push(format!("XAUTHORITY={XAUTHORITY_TARGET}"));
+        }
+        None => tracing::debug!("no xauth cookie for {host_display}; relying on xhost access"),
+    }
+    Ok(passthrough)
+}
+
+/// Forward the Wayland compositor socket (`$WAYLAND_DISPLAY`, `wayland-0`
+/// by default) into a runtime dir for `uid`.
+pub fn wayland(uid: u32) -> Result<Passthrough> {
+    let name = env::var("WAYLAND_DISPLAY")
+        .ok()
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "wayland-0".to_owned());
+    let socket = match Path::new(&name) {
+        path if path.is_absolute() => path.to_path_buf(),
+        path => env::var_os("XDG_RUNTIME_DIR")
+            .filter(|dir| !dir.is_empty())
+            .map(|dir| PathBuf::from(dir).join(path))
+            .context("--wayland needs XDG_RUNTIME_DIR to find the Wayland socket")?,
+    };
+    if !socket.exists() {
+        bail!("Wayland socket {} does not exist", socket.display());
+    }
+    let runtime_dir = format!("/run/user/{uid}");
+    Ok(Passthrough {
+        volumes: vec![format!("{}:{runtime_dir}/wayland-0", socket.display())],
+        env: vec![
+            format!("XDG_RUNTIME_DIR={runtime_dir}"),
+            "WAYLAND_DISPLAY=wayland-0".to_owned(),
+        ],
+    })
+}
+
+/// Display number of a display on a local socket: `:0`, `:1.0`, `unix:0`.
+fn local_display_number(display: &str) -> Option<&str> {
+    let (host, rest) = display.rsplit_once(':')?;
+    if !host.is_empty() && host != "unix" {
+        return None;
+    }
+    let number = rest.split('.').next()?;
+    (!number.is_empty() && number.chars().all(|c| c.is_ascii_digit())).then_some(number)
+}
+
+/// The display's xauth entries as an Xauthority file, with every entry's
+/// family made wild.
+fn xauth_cookie(display: &str) -> Option<Vec<u8>> {
+    let output = Command::new("xauth")
+        .arg("nlist")
+        .arg(display)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let cookie = wild_xauthority(&String::from_utf8_lossy(&output.stdout));
+    (!cookie.is_empty()).then_some(cookie)
+}
+
+/// `xauth nlist` prints each entry as the hex of its binary form, with
+/// spaces between fields, so decoding a line gives the file format.
+fn wild_xauthority(nlist: &str) -> Vec<u8> {
+    let mut file = Vec::new();
+    for line in nlist.lines() {
+        let hex: String = line.split_whitespace().collect();
+        let Some(mut entry) = decode_hex(&hex) else {
+            continue;
+        };
+        if entry.len() < 2 {
+            continue;
+        }
+        entry[..2].copy_from_slice(&FAMILY_WILD);
+        file.extend(entry);
+    }
+    file
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_local_display_numbers() {
+        assert_eq!(local_display_number(":0"), Some("0"));
+        assert_eq!(local_display_number(":1.0"), Some("1"));
+        assert_eq!(local_display_number("unix:2"), Some("2"));
+        assert_eq!(local_display_number("localhost:10.0"), None);
+        assert_eq!(local_display_number("/private/tmp/xquartz:0"), None);
+        assert_eq!(local_display_number(":"), None);
+    }
+
+    #[test]
+    fn xauth_entries_become_wild() {
+        let nlist =
+            "0100 0004 6c617074 0001 30 0012 4d49542d4d414749432d434f4f4b49452d31 0002 abcd\n";
+        let file = wild_xauthority(nlist);
+        assert_eq!(&file[..2], &FAMILY_WILD);
+        assert_eq!(&file[2..8], b"\x00\x04lapt");
+        assert_eq!(&file[file.len() - 4..], &[0x00, 0x02, 0xab, 0xcd]);
+        assert!(wild_xauthority("zz\n").is_empty());
+    }
+}
@@ -0,0 +1,355 @@
+//! The reference Dockerfiles, built into the binary so a fresh install can
+//! write them to `~/.config/davy`.
+
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+
+use crate::{dry_run, home_dir, note};
+
+/// Which reference Dockerfile.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Flavor {
+    /// Rocky Linux 9, with Docker CLI, Spack, and the agent CLIs
+    #[default]
+    Rocky,
+    /// Debian bookworm-slim, with the agent CLIs
+    Debian,
+}
+
+impl Flavor {
+    /// File name davy looks for in the config dir.
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Flavor::Rocky => "rocky.Dockerfile",
+            Flavor::Debian => "debian.Dockerfile",
+        }
+    }
+
+    /// The Dockerfile this binary was built with.
+    pub fn contents(self) -> &'static str {
+        match self {
+            Flavor::Rocky => include_str!("../rocky.Dockerfile"),
+            Flavor::Debian => include_str!("../debian.Dockerfile"),
+        }
+    }
+}
+
+/// An agent CLI a generated Dockerfile can include.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum Agent {
+    Claude,
+    Codex,
+    Gemini,
+    Pi,
+    Opencode,
+    Copilot,
+    Aider,
+    Goose,
+    Cursor,
+}
+
+impl Agent {
+    /// The npm package, for agents installed with npm.
+    fn npm_package(self) -> Option<&'static str> {
+        match self {
+            Agent::Codex => Some("@openai/codex"),
+            Agent::Gemini => Some("@google/gemini-cli"),
+            Agent::Pi => Some("@mariozechner/pi-coding-agent"),
+            Agent::Opencode => Some("opencode-ai"),
+            Agent::Copilot => Some("@github/copilot"),
+            Agent::Claude | Agent::Aider | Agent::Goose | Agent::Cursor => None,
+        }
+    }
+
+    /// The installer run as the user, for the others.
+    fn user_install(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Agent::Claude => Some((
+                "Claude Code, into ~/.local/bin",
+                "curl -fsSL https://claude.ai/install.sh | bash",
+            )),
+            Agent::Aider => Some((
+                "aider, as a uv tool in ~/.local/bin",
+                "curl -LsSf https://aider.chat/install.sh | sh",
+            )),
+            Agent::Goose => Some((
+                "Goose, into ~/.local/bin",
+                "curl -fsSL https://github.com/block/goose/releases/download/stable/download_cli.sh | CONFIGURE=false bash",
+            )),
+            Agent::Cursor => Some((
+                "cursor-agent, into ~/.local/bin",
+                "curl -fsS https://cursor.com/install | bash",
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// A language toolchain a generated Dockerfile can include.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum Lang {
+    Node,
+    Python,
+    Rust,
+    Go,
+}
+
+const NODE_SECTION: &str = r#"# Install Node.js (official tarball + checksum verification)
+RUN set -eux; \
+    case "${TARGETARCH:-$(uname -m)}" in \
+      x86_64|amd64) distro=linux-x64 ;; \
+      aarch64|arm64) distro=linux-arm64 ;; \
+      *) echo "unsupported architecture" >&2; exit 1 ;; \
+    esac; \
+    cd /tmp; \
+    curl -fsSLO "https://nodejs.org/dist/v${NODE_VERSION}/node-v${NODE_VERSION}-${distro}.tar.xz"; \
+    curl -fsSLO "https://nodejs.org/dist/v${NODE_VERSION}/SHASUMS256.txt"; \
+    grep " node-v${NODE_VERSION}-${distro}.tar.xz\$" SHASUMS256.txt | sha256sum -c -; \
+    tar -xJf "node-v${NODE_VERSION}-${distro}.tar.xz" -C /usr/local --strip-components=1; \
+    rm -rf /tmp/*; \
+    node --version; npm --version; \
+    corepack enable || true
+"#;
+
+const GO_SECTION: &str = r#"# Install Go (official tarball + checksum verification)
+RUN set -eux; \
+    case "${TARGETARCH:-$(uname -m)}" in \
+      x86_64|amd64) arch=amd64 ;; \
+      aarch64|arm64) arch=arm64 ;; \
+      *) echo "unsupported architecture" >&2; exit 1 ;; \
+    esac; \
+    cd /tmp; \
+    curl -fsSLO "https://dl.google.com/go/go${GO_VERSION}.linux-${arch}.tar.gz"; \
+    echo "$(curl -fsSL "https://dl.google.com/go/go${GO_VERSION}.linux-${arch}.tar.gz.sha256")  go${GO_VERSION}.linux-${arch}.tar.gz" | sha256sum -c -; \
+    tar -xzf "go${GO_VERSION}.linux-${arch}.tar.gz" -C /usr/local; \
+    rm -rf /tmp/*; \
+    /usr/local/go/bin/go version
+"#;
+
+const USER_SECTION: &str = r#"# Non-root user (matches host UID/GID for mounted volumes)
+RUN set -eux; \
+    if ! getent group "${USER_GID}" >/dev/null; then \
+      groupadd --gid "${USER_GID}" "${USERNAME}"; \
+    fi; \
+    useradd --uid "${USER_UID}" --gid "${USER_GID}" -m -s /bin/bash "${USERNAME}"; \
+    echo "${USERNAME} ALL=(ALL) NOPASSWD:ALL" > "/etc/sudoers.d/${USERNAME}"; \
+    chmod 0440 "/etc/sudoers.d/${USERNAME}"
+"#;
+
+/// Render a Debian-based Dockerfile with only `agents` and `langs`, plus
+/// Node when an agent is installed with npm. The base tooling is that of
+/// the reference Debian Dockerfile, less the Docker CLI.
+pub fn generate(agents: &[Agent], langs: &[Lang]) -> String {
+    let mut agents = agents.to_vec();
+    agents.sort();
+    agents.dedup();
+    let mut langs = langs.to_vec();
+    if agents.iter().any(|agent| agent.npm_package().is_some()) {
+        langs.push(Lang::Node);
+    }
+    langs.sort();
+    langs.dedup();
+    let has = |lang| langs.contains(&lang);
+
+    let mut out = String::from("# syntax=docker/dockerfile:1.6\n");
+    out.push_str(&format!(
+        "# Generated by `davy dockerfile generate`: agents {}; toolchains {}.\n\n",
+        list(&agents),
+        list(&langs)
+    ));
+    out.push_str(
+        "FROM debian:bookworm-slim\n\n\
+         ARG USERNAME=dev\n\
+         ARG USER_UID=1000\n\
+         ARG USER_GID=${USER_UID}\n",
+    );
+    if has(Lang::Node) {
+        out.push_str("ARG NODE_VERSION=25.6.1\n");
+    }
+    if has(Lang::Go) {
+        out.push_str("ARG GO_VERSION=1.24.1\n");
+    }
+    out.push_str(
+        "# Docker/buildx sets TARGETARCH to values like amd64/arm64.\n\
+         ARG TARGETARCH\n\n\
+         ENV DEBIAN_FRONTEND=noninteractive \\\n    PROJECT_DIR=/project\n\n",
+    );
+
+    let mut packages = vec![
+        "bash ca-certificates curl git openssh-client",
+        "iptables iproute2",
+        "jq ripgrep fd-find less unzip zip xz-utils",
+        "sudo zsh tmux ttyd",
+        "gnupg",
+    ];
+    if has(Lang::Node) || has(Lang::Python) || has(Lang::Rust) {
+        packages.insert(2, "build-essential pkg-config");
+    }
+    if has(Lang::Python) {
+        packages.insert(2, "python3 python3-venv python3-pip");
+    }
+    out.push_str(
+        "# Base tooling\nRUN apt-get update && apt-get install -y --no-install-recommends \\\n",
+    );
+    for line in packages {
+        out.push_str(&format!("      {line} \\\n"));
+    }
+    out.push_str(
+        "    && ln -sf /usr/bin/fdfind /usr/local/bin/fd \\\n    && rm -rf /var/lib/apt/lists/*\n\n",
+    );
+
+    if has(Lang::Node) {
+        out.push_str(NODE_SECTION);
+        out.push('\n');
+    }
+    if has(Lang::Go) {
+        out.push_str(GO_SECTION);
+        out.push('\n');
+    }
+    out.push_str(USER_SECTION);
+    out.push('\n');
+
+    let packages = agents
+        .iter()
+        .filter_map(|agent| agent.npm_package())
+        .collect::<Vec<_>>();
+    if !packages.is_empty() {
+        out.push_str("# Agent CLIs\nRUN npm install -g --no-fund --no-audit \\\n");
+        for package in packages {
+            out.push_str(&format!("      {package} \\\n"));
+        }
+        out.push_str(" && npm cache clean --force\n\n");
+    }
+
+    out.push_str(
+        "# Workspace\n\
+         RUN mkdir -p \"${PROJECT_DIR}\" \\\n \
+         && chown -R \"${USER_UID}:${USER_GID}\" \"${PROJECT_DIR}\"\n\n\
+         USER \"${USERNAME}\"\n\
+         WORKDIR \"${PROJECT_DIR}\"\n\n\
+         # Owned by the user, so mounts below them (auth, pip config) do not make\n\
+         # the engine create them as root\n\
+         RUN mkdir -p ~/.config ~/.cache ~/.local/share ~/.local/state\n\n",
+    );
+    let mut path = vec!["/home/${USERNAME}/.local/bin"];
+    if has(Lang::Rust) {
+        path.push("/home/${USERNAME}/.cargo/bin");
+    }
+    if has(Lang::Go) {
+        path.extend(["/usr/local/go/bin", "/home/${USERNAME}/go/bin"]);
+    }
+    out.push_str(&format!("ENV PATH=\"{}:${{PATH}}\"\n", path.join(":")));
+
+    if has(Lang::Python) {
+        out.push_str(
+            "\n# uv, into ~/.local/bin\nRUN curl -LsSf https://astral.sh/uv/install.sh | sh\n",
+        );
+    }
+    if has(Lang::Rust) {
+        out.push_str(
+            "\n# Rust, with rustup into ~/.cargo\n\
+             RUN curl https://sh.rustup.rs -sSf | sh -s -- -y --profile minimal --component clippy,rustfmt\n",
+        );
+    }
+    for (comment, command) in agents.iter().filter_map(|agent| agent.user_install()) {
+        out.push_str(&format!("\n# {comment}\nRUN {command}\n"));
+    }
+    out.push_str("\nCMD [\"bash\"]\n");
+    out
+}
+
+/// `a, b` from value names, or `none`.
+fn list<T: ValueEnum>(values: &[T]) -> String {
+    if values.is_empty() {
+        return "none".to_owned();
+    }
+    values
+        .iter()
+        .filter_map(|value| value.to_possible_value())
+        .map(|value| value.get_name().to_owned())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Where davy looks for Dockerfiles by default.
+pub fn config_dir() -> Result<PathBuf> {
+    Ok(home_dir()?.join(".config/davy"))
+}
+
+/// Write `flavor`'s Dockerfile into `dir`. An existing file is only
+/// replaced with `force`.
+pub fn install(flavor: Flavor, dir: &Path, force: bool) -> Result<PathBuf> {
+    let path = dir.join(flavor.file_name());
+    if path.exists() && !force {
+        bail!(
+            "{} already exists; pass --force to replace it",
+            path.display()
+        );
+    }
+    fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    fs::write(&path, flavor.contents())
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    note!("wrote {}", path.display());
+    Ok(path)
+}
+
+/// When no Dockerfile was found in `dir`, offer on the terminal to install
+/// the Rocky one there. Returns its path when the user agrees.
+pub fn offer_install(dir: &Path) -> Result<Option<PathBuf>> {
+    if dry_run() || !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        return Ok(None);
+    }
+    eprint!(
+        "davy: no Dockerfile in {}. Install the bundled {} there? [Y/n] ",
+        dir.display(),
+        Flavor::Rocky.file_name()
+    );
+    io::stderr().flush().ok();
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .context("failed to read answer")?;
+    if !matches!(answer.trim(), "" | "y" | "Y" | "yes") {
+        return Ok(None);
+    }
+    install(Flavor::Rocky, dir, false).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn installs_embedded_dockerfiles() {
+        let dir = std::env::temp_dir().join(format!("davy-dockerfiles-{}", std::process::id()));
+        let path = install(Flavor::Debian, &dir, false).unwrap();
+        assert_eq!(path, dir.join("debian.Dockerfile"));
+        assert!(fs::read_to_string(&path).unwrap().contains("FROM debian:"));
+        assert!(install(Flavor::Debian, &dir, false).is_err());
+        assert!(install(Flavor::Debian, &dir, true).is_ok());
+        assert!(Flavor::Rocky.contents().contains("FROM rockylinux:"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn generates_only_the_requested_tools() {
+        let dockerfile = generate(&[Agent::Codex, Agent::Claude], &[Lang::Rust]);
+        assert!(dockerfile.contains("agents claude, codex; toolchains node, rust."));
+        assert!(dockerfile.contains("      @openai/codex \\\n"));
+        assert!(dockerfile.contains("https://claude.ai/install.sh"));
+        assert!(dockerfile.contains("https://sh.rustup.rs"));
+        assert!(dockerfile.contains("ARG NODE_VERSION="));
+        assert!(!dockerfile.contains("gemini"));
+        assert!(!dockerfile.contains("python3"));
+        assert!(!dockerfile.contains("GO_VERSION"));
+
+        let dockerfile = generate(&[Agent::Claude], &[Lang::Go]);
+        assert!(!dockerfile.contains("NODE_VERSION"));
+        assert!(!dockerfile.contains("build-essential"));
+        assert!(dockerfile.contains("/usr/local/go/bin"));
+    }
+}
@@ -0,0 +1,123 @@
+//! Applying the user's dotfiles (a git repo or a host directory) in the
+//! sandbox at startup.
+
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{home_dir, run_checked};
+
+/// Where the host's dotfiles are mounted read-only.
+pub const MOUNT_DIR: &str = "/home/dev/.davy-dotfiles";
+
+/// Copies the dotfiles to `~/.dotfiles` and applies them the way GitHub
+/// Codespaces does: the first install script found, else `chezmoi apply` for
+/// a chezmoi source, else links for the top-level dotfiles. A failure is
+/// reported but does not stop the sandbox.
+pub const INSTALL_SCRIPT: &str = r#"set -e
+if [ ! -e /home/dev/.dotfiles ]; then
+  cp -a /home/dev/.davy-dotfiles /home/dev/.dotfiles
+fi
+(
+  cd /home/dev/.dotfiles
+  for script in install.sh install bootstrap.sh bootstrap script/bootstrap setup.sh setup script/setup; do
+    if [ -f "$script" ]; then
+      chmod +x "$script"
+      exec "./$script"
+    fi
+  done
+  if [ -f .chezmoiroot ] || ls -d dot_* >/dev/null 2>&1; then
+    if command -v chezmoi >/dev/null 2>&1; then
+      exec chezmoi apply --source /home/dev/.dotfiles
+    fi
+    echo "davy: dotfiles look like a chezmoi source, but chezmoi is not installed" >&2
+    exit 1
+  fi
+  for file in .[!.]*; do
+    case "$file" in .git|.github|.gitignore|.gitmodules) continue ;; esac
+    [ -e "$file" ] && ln -sfn "/home/dev/.dotfiles/$file" "/home/dev/$file"
+  done
+) >/tmp/davy-dotfiles.log 2>&1 || echo "davy: applying dotfiles failed; see /tmp/davy-dotfiles.log" >&2
+
+exec "$@""#;
+
+/// Whether `source` names a git repository rather than a host directory.
+pub fn is_repo_url(source: &str) -> bool {
+    source.contains("://") || (source.starts_with("git@") && source.contains(':'))
+}
+
+/// Host directory holding the dotfiles from `source`: the directory itself,
+/// or a clone of the repository kept under the cache dir and fast-forwarded
+/// on each run.
+pub fn host_dir(source: &str) -> Result<PathBuf> {
+    if !is_repo_url(source) {
+        let dir = PathBuf::from(source);
+        if !dir.is_dir() {
+            bail!("dotfiles directory {} not found", dir.display());
+        }
+        return Ok(dir);
+    }
+
+    let checkout = cache_dir()?.join(checkout_name(source));
+    if checkout.join(".git").is_dir() {
+        let mut pull = Command::new("git");
+        pull.arg("-C")
+            .arg(&checkout)
+            .args(["pull", "--ff-only", "--quiet"])
+            .stdout(Stdio::null());
+        if let Err(err) = run_checked(&mut pull, "git pull (dotfiles)") {
+            eprintln!("davy: warning: using the dotfiles as last fetched: {err:#}");
+        }
+    } else {
+        let mut clone = Command::new("git");
+        clone
+            .args(["clone", "--depth", "1", "--quiet", source])
+            .arg(&checkout);
+        run_checked(&mut clone, "git clone (dotfiles)")?;
+    }
+    Ok(checkout)
+}
+
+fn cache_dir() -> Result<PathBuf> {
+    let dir = match env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir).join("davy/dotfiles"),
+        None => home_dir()?.join(".cache/davy/dotfiles"),
+    };
+    std::fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    Ok(dir)
+}
+
+/// Readable and unique directory name for a repository URL.
+fn checkout_name(url: &str) -> String {
+    let repo = Path::new(url.trim_end_matches('/').trim_end_matches(".git"))
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.rsplit(':').next().unwrap_or(name))
+        .unwrap_or("dotfiles");
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+    format!("{repo}-{:08x}", hasher.finish() as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tells_repos_from_directories() {
+        assert!(is_repo_url("https://github.com/me/dotfiles"));
+        assert!(is_repo_url("git@github.com:me/dotfiles.git"));
+        assert!(!is_repo_url("/home/me/dotfiles"));
+        assert!(!is_repo_url("dotfiles"));
+        assert!(checkout_name("https://github.com/me/dotfiles.git").starts_with("dotfiles-"));
+        assert!(checkout_name("git@host:cfg").starts_with("cfg-"));
+        assert_ne!(
+            checkout_name("https://a.example/dotfiles"),
+            checkout_name("https://b.example/dotfiles")
+        );
+    }
+}
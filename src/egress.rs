@@ -0,0 +1,345 @@
+//! Egress filtering: a tinyproxy sidecar that is the sandbox's only way out,
+//! allows a list of domains, and logs every request.
+
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+
+use crate::engine::Engine;
+use crate::sidecars::{self, SIDECAR_OF_LABEL};
+use crate::{home_dir, note, run_checked, signals};
+
+/// Name the sandbox reaches the proxy by.
+pub const PROXY_ALIAS: &str = "davy-proxy";
+const PROXY_PORT: u16 = 3128;
+/// Alpine-based image; tinyproxy is installed at start unless it has it.
+pub const DEFAULT_PROXY_IMAGE: &str = "alpine:3";
+const PROXY_START_TIMEOUT: Duration = Duration::from_secs(60);
+
+const PROXY_SCRIPT: &str = r#"set -e
+command -v tinyproxy >/dev/null 2>&1 || apk add --no-cache tinyproxy >/dev/null
+printf '%s' "$DAVY_PROXY_FILTER" >/tmp/davy-filter
+printf '%s\n' "$DAVY_PROXY_CONF" >/tmp/davy-tinyproxy.conf
+exec tinyproxy -d -c /tmp/davy-tinyproxy.conf"#;
+
+/// Egress filtering settings for one sandbox.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Egress {
+    /// Allowed domains; `example.com` also allows its subdomains.
+    pub allow: Vec<String>,
+    pub image: String,
+}
+
+impl Egress {
+    /// Normalize and check the allowlist (`*.example.com` is the same as
+    /// `example.com`).
+    pub fn new(allow: Vec<String>, image: Option<String>) -> Result<Egress> {
+        let mut domains = Vec::new();
+        for domain in allow {
+            let domain = domain.trim_start_matches("*.").to_ascii_lowercase();
+            if domain.is_empty()
+                || !domain
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+            {
+                bail!("invalid domain '{domain}' in egress allowlist");
+            }
+            if !domains.contains(&domain) {
+                domains.push(domain);
+            }
+        }
+        Ok(Egress {
+            allow: domains,
+            image: image.unwrap_or_else(|| DEFAULT_PROXY_IMAGE.to_owned()),
+        })
+    }
+}
+
+pub fn proxy_container_name(sandbox: &str) -> String {
+    sidecars::container_name(sandbox, "egress-proxy")
+}
+
+/// Proxy variables for the sandbox. Sidecars are reached directly.
+pub fn sandbox_env<'a>(sidecar_names: impl IntoIterator<Item = &'a String>) -> Vec<String> {
+    let url = format!("http://{PROXY_ALIAS}:{PROXY_PORT}");
+    let no_proxy = ["localhost", "127.0.0.1", "::1"]
+        .into_iter()
+        .chain(sidecar_names.into_iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(",");
+    let mut env = Vec::new();
+    for key in ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"] {
+        env.push(format!("{key}={url}"));
+    }
+    env.push(format!("NO_PROXY={no_proxy}"));
+    env.push(format!("no_proxy={no_proxy}"));
+    env
+}
+
+/// Start the proxy on the engine's default network, so it can reach the
+/// internet, and connect it to the sandbox's internal `network`.
+pub fn start_proxy(
+    engine: &dyn Engine,
+    sandbox: &str,
+    network: &str,
+    egress: &Egress,
+) -> Result<String> {
+    let container = proxy_container_name(sandbox);
+    let mut run = engine.command();
+    run.arg("run")
+        .arg("--detach")
+        .arg("--rm")
+        .arg("--name")
+        .arg(&container)
+        .arg("--label")
+        .arg(format!("{SIDECAR_OF_LABEL}={sandbox}"))
+        .arg("--network")
+        .arg(engine.default_network())
+        .arg("-e")
+        .arg(format!("DAVY_PROXY_CONF={}", tinyproxy_config()))
+        .arg("-e")
+        .arg(format!("DAVY_PROXY_FILTER={}", filter_file(&egress.allow)))
+        .arg(&egress.image)
+        .arg("sh")
+        .arg("-c")
+        .arg(PROXY_SCRIPT)
+        .stdout(Stdio::null());
+    run_checked(&mut run, &format!("{} run (egress proxy)", engine.name()))?;
+
+    let ready = (|| {
+        let mut connect = engine.command();
+        connect
+            .arg("network")
+            .arg("connect")
+            .arg("--alias")
+            .arg(PROXY_ALIAS)
+            .arg(network)
+            .arg(&container)
+            .stdout(Stdio::null());
+        run_checked(&mut connect, &format!("{} network connect", engine.name()))?;
+        if crate::dry_run() {
+            return Ok(());
+        }
+        wait_for_proxy(engine, &container)
+    })();
+    if let Err(err) = ready {
+        signals::remove_container(engine.binary(), &container);
+        return Err(err);
+    }
+    note!(
+        "egress limited to {} through proxy '{container}'.",
+        if egress.allow.is_empty() {
+            "nothing".to_owned()
+        } else {
+            egress.allow.join(", ")
+        }
+    );
+    Ok(container)
+}
+
+fn wait_for_proxy(engine: &dyn Engine, container: &str) -> Result<()> {
+    let deadline = Instant::now() + PROXY_START_TIMEOUT;
+    loop {
+        let running = engine
+            .command()
+            .arg("exec")
+            .arg(container)
+            .arg("pidof")
+            .arg("tinyproxy")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        if running {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            bail!(
+                "egress proxy did not start within {}s (see `{} logs {container}`)",
+                PROXY_START_TIMEOUT.as_secs(),
+                engine.binary()
+            );
+        }
+        thread::sleep(Duration::from_millis(500));
+    }
+}
+
+fn tinyproxy_config() -> String {
+    [
+        format!("Port {PROXY_PORT}"),
+        "Listen 0.0.0.0".to_owned(),
+        "Timeout 600".to_owned(),
+        "LogLevel Connect".to_owned(),
+        "Filter \"/tmp/davy-filter\"".to_owned(),
+        "FilterDefaultDeny Yes".to_owned(),
+        "ConnectPort 443".to_owned(),
+        "ConnectPort 80".to_owned(),
+    ]
+    .join("\n")
+}
+
+/// One regex per line, matching a domain and its subdomains. Written without
+/// blank lines, since an empty pattern would allow everything.
+fn filter_file(allow: &[String]) -> String {
+    allow
+        .iter()
+        .flat_map(|domain| {
+            let escaped = domain.replace('.', "\\.");
+            [format!("^{escaped}$\n"), format!("\\.{escaped}$\n")]
+        })
+        .collect()
+}
+
+/// Default audit log location: `$XDG_STATE_HOME/davy/egress/<sandbox>.log`.
+pub fn audit_log_path(sandbox: &str) -> Result<PathBuf> {
+    let dir = match env::var_os("XDG_STATE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => home_dir()?.join(".local/state"),
+    };
+    Ok(dir.join("davy/egress").join(format!("{sandbox}.log")))
+}
+
+/// Append every request the proxy saw to `path`, as
+/// `TIMESTAMP allow|deny HOST` lines. Returns the number of requests.
+pub fn write_audit_log(engine: &dyn Engine, container: &str, path: &Path) -> Result<usize> {
+    let output = engine
+        .command()
+        .arg("logs")
+        .arg("--timestamps")
+        .arg(container)
+        .stdin(Stdio::null())
+        .output()
+        .with_context(|| format!("failed to read logs of {container}"))?;
+    // tinyproxy logs to stderr; merge both streams in case an image differs.
+    let logs = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let entries = parse_log(&logs);
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    for entry in &entries {
+        writeln!(
+            file,
+            "{} {} {}",
+            entry.time,
+            if entry.allowed { "allow" } else { "deny" },
+            entry.host
+        )
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    Ok(entries.len())
+}
+
+#[derive(Debug, PartialEq)]
+struct Entry {
+    time: String,
+    host: String,
+    allowed: bool,
+}
+
+/// Pull requests out of `logs --timestamps` output of tinyproxy.
+fn parse_log(logs: &str) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = Vec::new();
+    for line in logs.lines() {
+        let time = line.split_whitespace().next().unwrap_or_default();
+        if let Some((_, request)) = line.split_once("Request (file descriptor ")
+            && let Some((_, request)) = request.split_once("): ")
+        {
+            let mut parts = request.split_whitespace();
+            let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+                continue;
+            };
+            let host = if method == "CONNECT" {
+                target
+            } else {
+                let target = target.split_once("://").map_or(target, |(_, rest)| rest);
+                target.split('/').next().unwrap_or(target)
+            };
+            entries.push(Entry {
+                time: time.to_owned(),
+                host: host.to_owned(),
+                allowed: true,
+            });
+        } else if let Some((_, domain)) = line.split_once("refused on filtered domain \"") {
+            let domain = domain.trim_end_matches('"');
+            if let Some(entry) = entries
+                .iter_mut()
+                .rev()
+                .find(|entry| entry.host == domain || entry.host.split(':').next() == Some(domain))
+            {
+                entry.allowed = false;
+            }
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allowlist_becomes_anchored_filters() {
+        let egress = Egress::new(
+            vec!["*.GitHub.com".to_owned(), "github.com".to_owned()],
+            None,
+        )
+        .unwrap();
+        assert_eq!(egress.allow, vec!["github.com".to_owned()]);
+        assert_eq!(egress.image, DEFAULT_PROXY_IMAGE);
+        assert_eq!(
+            filter_file(&egress.allow),
+            "^github\\.com$\n\\.github\\.com$\n"
+        );
+        assert_eq!(filter_file(&[]), "");
+        assert!(Egress::new(vec!["evil.com/path".to_owned()], None).is_err());
+    }
+
+    #[test]
+    fn sandbox_env_bypasses_proxy_for_sidecars() {
+        let names = ["postgres".to_owned()];
+        let env = sandbox_env(&names);
+        assert!(env.contains(&"HTTPS_PROXY=http://davy-proxy:3128".to_owned()));
+        assert!(env.contains(&"NO_PROXY=localhost,127.0.0.1,::1,postgres".to_owned()));
+    }
+
+    #[test]
+    fn parses_requests_and_denials() {
+        let logs = "\
+2026-10-16T11:00:00.1Z CONNECT   Oct 16 11:00:00.100 [1]: Connect (file descriptor 6): 172.20.0.3
+2026-10-16T11:00:00.2Z CONNECT   Oct 16 11:00:00.200 [1]: Request (file descriptor 6): CONNECT api.github.com:443 HTTP/1.1
+2026-10-16T11:00:01.0Z CONNECT   Oct 16 11:00:01.000 [1]: Request (file descriptor 7): GET http://evil.example/x HTTP/1.1
+2026-10-16T11:00:01.1Z NOTICE    Oct 16 11:00:01.100 [1]: Proxying refused on filtered domain \"evil.example\"
+";
+        assert_eq!(
+            parse_log(logs),
+            vec![
+                Entry {
+                    time: "2026-10-16T11:00:00.2Z".to_owned(),
+                    host: "api.github.com:443".to_owned(),
+                    allowed: true,
+                },
+                Entry {
+                    time: "2026-10-16T11:00:01.0Z".to_owned(),
+                    host: "evil.example".to_owned(),
+                    allowed: false,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,316 @@
+use std::env;
+use std::ffi::OsString;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+
+use crate::error::DavyError;
+use crate::{run_captured, run_checked, run_status};
+
+#[cfg(feature = "docker-api")]
+mod docker_api;
+
+/// Names accepted by `--engine`, `DAVY_ENGINE`, and the `engine` config key,
+/// in the order they are looked for on PATH.
+pub const ENGINE_NAMES: [&str; 3] = ["docker", "podman", "nerdctl"];
+
+/// Everything needed to build the sandbox image, independent of the engine.
+pub struct BuildRequest<'a> {
+    pub dockerfile: &'a Path,
+    /// Build context. Without one, the Dockerfile's directory.
+    pub context_dir: Option<&'a Path>,
+    pub image: &'a str,
+    pub build_args: Vec<(String, String)>,
+    /// Labels in `KEY=VALUE` form.
+    pub labels: Vec<String>,
+    pub pull: bool,
+    pub no_cache: bool,
+}
+
+impl BuildRequest<'_> {
+    /// Directory sent as the build context, the same for every engine.
+    pub fn context(&self) -> &Path {
+        self.context_dir
+            .or_else(|| self.dockerfile.parent())
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."))
+    }
+}
+
+/// A docker-compatible container CLI. Call sites build commands through the
+/// engine so new backends only need to describe where they differ.
+pub trait Engine {
+    /// Name used in messages and for selection.
+    fn name(&self) -> &'static str;
+
+    /// Executable to invoke.
+    fn binary(&self) -> &'static str;
+
+    fn command(&self) -> Command {
+        Command::new(self.binary())
+    }
+
+    /// Extra `run` arguments needed for bind-mounted files to keep the host
+    /// user's ownership.
+    fn run_args(&self) -> Vec<OsString> {
+        Vec::new()
+    }
+
+    /// Go template that reads a label in `ps` and `volume ls` output.
+    fn label_template(&self, key: &str) -> String {
+        format!("{{{{.Label \"{key}\"}}}}")
+    }
+
+    /// Network containers join when none is given.
+    fn default_network(&self) -> &'static str {
+        "bridge"
+    }
+
+    /// Whether containers already resolve `host.docker.internal` to the host.
+    fn maps_host_alias(&self) -> bool {
+        false
+    }
+
+    fn image_exists(&self, image: &str) -> Result<bool> {
+        let status = self
+            .command()
+            .arg("image")
+            .arg("inspect")
+            .arg(image)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .with_context(|| format!("failed to run {} image inspect", self.name()))?;
+
+        Ok(status.success())
+    }
+
+    fn build_image(&self, request: &BuildRequest) -> Result<()> {
+        let mut cmd = self.build_command(request);
+        let name = format!("{} build", self.name());
+        let status = if crate::quiet() {
+            run_captured(&mut cmd, &name)?
+        } else {
+            run_status(&mut cmd, &name)?
+        };
+        if !status.success() {
+            return Err(DavyError::BuildFailed {
+                code: status.code(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// CLI equivalent of [`build_image`](Engine::build_image).
+    fn build_command(&self, request: &BuildRequest) -> Command {
+        let mut cmd = self.command();
+        cmd.arg("build");
+        if request.pull {
+            cmd.arg("--pull");
+        }
+        if request.no_cache {
+            cmd.arg("--no-cache");
+        }
+        for (key, value) in &request.build_args {
+            cmd.arg("--build-arg").arg(format!("{key}={value}"));
+        }
+        for label in &request.labels {
+            cmd.arg("--label").arg(label);
+        }
+        cmd.arg("-f")
+            .arg(request.dockerfile)
+            .arg("-t")
+            .arg(request.image)
+            .arg(request.context());
+        cmd
+    }
+
+    fn volume_exists(&self, name: &str) -> Result<bool> {
+        let status = self
+            .command()
+            .arg("volume")
+            .arg("inspect")
+            .arg(name)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .with_context(|| format!("failed to run {} volume inspect", self.name()))?;
+
+        Ok(status.success())
+    }
+
+    /// Create (or reuse) a named volume with `KEY=VALUE` labels.
+    fn create_volume(&self, name: &str, labels: &[String]) -> Result<()> {
+        run_checked(
+            &mut self.create_volume_command(name, labels),
+            &format!("{} volume create", self.name()),
+        )
+    }
+
+    fn create_volume_command(&self, name: &str, labels: &[String]) -> Command {
+        let mut cmd = self.command();
+        cmd.arg("volume").arg("create");
+        for label in labels {
+            cmd.arg("--label").arg(label);
+        }
+        cmd.arg(name).stdout(Stdio::null());
+        cmd
+    }
+
+    fn remove_volume(&self, name: &str) -> Result<()> {
+        run_checked(
+            &mut self.remove_volume_command(name),
+            &format!("{} volume rm", self.name()),
+        )
+    }
+
+    fn remove_volume_command(&self, name: &str) -> Command {
+        let mut cmd = self.command();
+        cmd.arg("volume").arg("rm").arg("-f").arg(name);
+        cmd
+    }
+}
+
+pub struct Docker;
+
+impl Engine for Docker {
+    fn name(&self) -> &'static str {
+        "docker"
+    }
+
+    fn binary(&self) -> &'static str {
+        "docker"
+    }
+}
+
+pub struct Podman;
+
+impl Engine for Podman {
+    fn name(&self) -> &'static str {
+        "podman"
+    }
+
+    fn binary(&self) -> &'static str {
+        "podman"
+    }
+
+    fn run_args(&self) -> Vec<OsString> {
+        // Rootless podman maps the host user to root by default.
+        vec![OsString::from("--userns=keep-id")]
+    }
+
+    fn label_template(&self, key: &str) -> String {
+        format!("{{{{index .Labels \"{key}\"}}}}")
+    }
+
+    fn default_network(&self) -> &'static str {
+        "podman"
+    }
+
+    fn maps_host_alias(&self) -> bool {
+        true
+    }
+}
+
+pub struct Nerdctl;
+
+impl Engine for Nerdctl {
+    fn name(&self) -> &'static str {
+        "nerdctl"
+    }
+
+    fn binary(&self) -> &'static str {
+        "nerdctl"
+    }
+}
+
+pub fn from_name(name: &str) -> Result<Box<dyn Engine>> {
+    match name {
+        "docker" => Ok(Box::new(Docker)),
+        "podman" => Ok(Box::new(Podman)),
+        "nerdctl" => Ok(Box::new(Nerdctl)),
+        _ => Err(DavyError::UnknownEngine {
+            name: name.to_owned(),
+        }
+        .into()),
+    }
+}
+
+/// Use the requested engine, or the first engine found on PATH.
+pub fn select(requested: Option<&str>) -> Result<Box<dyn Engine>> {
+    if let Some(name) = requested {
+        tracing::debug!("using requested engine {name}");
+        return from_name(name);
+    }
+
+    for name in ENGINE_NAMES {
+        if find_on_path(name) {
+            tracing::debug!("using engine {name} found on PATH");
+            return from_name(name);
+        }
+    }
+
+    Err(DavyError::DockerNotFound.into())
+}
+
+/// Have the docker engine build images and manage volumes through the Docker
+/// Engine API. Everything else still goes through the docker CLI.
+pub fn with_docker_api(engine: Box<dyn Engine>) -> Result<Box<dyn Engine>> {
+    if engine.name() != "docker" {
+        bail!(
+            "--docker-api only works with the docker engine, not {}",
+            engine.name()
+        );
+    }
+    #[cfg(feature = "docker-api")]
+    return Ok(Box::new(docker_api::DockerApi::connect()?));
+    #[cfg(not(feature = "docker-api"))]
+    bail!("davy was built without the 'docker-api' feature");
+}
+
+pub(crate) fn find_on_path(binary: &str) -> bool {
+    let Some(paths) = env::var_os("PATH") else {
+        return false;
+    };
+    env::split_paths(&paths).any(|dir| is_executable(&dir.join(binary)))
+}
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+    }
+
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_name_rejects_unknown_engines() {
+        assert_eq!(from_name("podman").unwrap().name(), "podman");
+        let err = from_name("lxc").err().expect("lxc should be rejected");
+        assert!(err.to_string().contains("docker, podman, nerdctl)"));
+    }
+
+    #[test]
+    fn label_templates_match_engine_syntax() {
+        assert_eq!(Docker.label_template("a.b"), "{{.Label \"a.b\"}}");
+        assert_eq!(Podman.label_template("a.b"), "{{index .Labels \"a.b\"}}");
+    }
+
+    #[test]
+    fn podman_keeps_host_user_ids() {
+        assert_eq!(Podman.run_args(), vec![OsString::from("--userns=keep-id")]);
+        assert!(Docker.run_args().is_empty());
+    }
+}
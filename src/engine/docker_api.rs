@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::fs;
+
+use anyhow::{Context, Result, anyhow};
+use bollard::errors::Error as ApiError;
+use bollard::image::BuildImageOptions;
+use bollard::volume::{CreateVolumeOptions, RemoveVolumeOptions};
+use futures_util::StreamExt;
+use tokio::runtime::Runtime;
+
+use super::{BuildRequest, Engine};
+use crate::error::DavyError;
+use crate::run_checked;
+
+/// The docker engine, with image and volume operations going through the
+/// Docker Engine API. Interactive `run`/`exec` sessions, listing, and `cp`
+/// still go through the docker CLI.
+pub struct DockerApi {
+    client: bollard::Docker,
+    runtime: Runtime,
+}
+
+impl DockerApi {
+    pub fn connect() -> Result<DockerApi> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .context("failed to start async runtime for the Docker API")?;
+        let client = bollard::Docker::connect_with_local_defaults()
+            .context("failed to connect to the Docker Engine API")?;
+        Ok(DockerApi { client, runtime })
+    }
+}
+
+impl Engine for DockerApi {
+    fn name(&self) -> &'static str {
+        "docker"
+    }
+
+    fn binary(&self) -> &'static str {
+        "docker"
+    }
+
+    fn image_exists(&self, image: &str) -> Result<bool> {
+        match self.runtime.block_on(self.client.inspect_image(image)) {
+            Ok(_) => Ok(true),
+            Err(err) if is_not_found(&err) => Ok(false),
+            Err(err) => Err(err).with_context(|| format!("failed to inspect image '{image}'")),
+        }
+    }
+
+    fn build_image(&self, request: &BuildRequest) -> Result<()> {
+        if crate::dry_run() {
+            return run_checked(&mut self.build_command(request), "docker build");
+        }
+        let (dockerfile, context) = build_context(request)?;
+
+        let options = BuildImageOptions {
+            dockerfile,
+            t: request.image.to_owned(),
+            pull: request.pull,
+            nocache: request.no_cache,
+            rm: true,
+            buildargs: request.build_args.iter().cloned().collect(),
+            labels: split_labels(&request.labels),
+            ..Default::default()
+        };
+
+        self.runtime.block_on(async {
+            let mut progress = self.client.build_image(options, None, Some(context.into()));
+            while let Some(event) = progress.next().await {
+                let event = event.context("Docker API build failed")?;
+                if let Some(message) = event
+                    .error_detail
+                    .and_then(|detail| detail.message)
+                    .or(event.error)
+                {
+                    return Err(anyhow!(message.trim_end().to_owned())
+                        .context(DavyError::BuildFailed { code: None }));
+                }
+                if crate::quiet() {
+                    continue;
+                }
+                if let Some(line) = event.stream {
+                    eprint!("{line}");
+                } else if let Some(status) = event.status {
+                    eprintln!("{status}");
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn volume_exists(&self, name: &str) -> Result<bool> {
+        match self.runtime.block_on(self.client.inspect_volume(name)) {
+            Ok(_) => Ok(true),
+            Err(err) if is_not_found(&err) => Ok(false),
+            Err(err) => Err(err).with_context(|| format!("failed to inspect volume '{name}'")),
+        }
+    }
+
+    fn create_volume(&self, name: &str, labels: &[String]) -> Result<()> {
+        if crate::dry_run() {
+            return run_checked(
+                &mut self.create_volume_command(name, labels),
+                "docker volume create",
+            );
+        }
+        let options = CreateVolumeOptions {
+            name: name.to_owned(),
+            labels: split_labels(labels),
+            ..Default::default()
+        };
+        self.runtime
+            .block_on(self.client.create_volume(options))
+            .with_context(|| format!("failed to create volume '{name}'"))?;
+        Ok(())
+    }
+
+    fn remove_volume(&self, name: &str) -> Result<()> {
+        if crate::dry_run() {
+            return run_checked(&mut self.remove_volume_command(name), "docker volume rm");
+        }
+        let options = RemoveVolumeOptions { force: true };
+        self.runtime
+            .block_on(self.client.remove_volume(name, Some(options)))
+            .with_context(|| format!("failed to remove volume '{name}'"))
+    }
+}
+
+/// Archive the build context for the API, the same directory the CLI
+/// engines send, with symlinks kept as links so they cannot pull in files
+/// from elsewhere on the host. Returns the Dockerfile's path inside the
+/// archive and the archive.
+fn build_context(request: &BuildRequest) -> Result<(String, Vec<u8>)> {
+    let context_dir = request.context();
+    let mut archive = tar::Builder::new(Vec::new());
+    archive.follow_symlinks(false);
+    archive
+        .append_dir_all(".", context_dir)
+        .with_context(|| format!("failed to archive build context {}", context_dir.display()))?;
+    let dockerfile = match request.dockerfile.strip_prefix(context_dir) {
+        Ok(path) => path.to_string_lossy().into_owned(),
+        Err(_) => {
+            let name = ".davy.Dockerfile";
+            let mut file = fs::File::open(request.dockerfile).with_context(|| {
+                format!("failed to read Dockerfile {}", request.dockerfile.display())
+            })?;
+            archive
+                .append_file(name, &mut file)
+                .context("failed to archive the Dockerfile")?;
+            name.to_owned()
+        }
+    };
+    let context = archive
+        .into_inner()
+        .context("failed to finish build context archive")?;
+    Ok((dockerfile, context))
+}
+
+fn is_not_found(err: &ApiError) -> bool {
+    matches!(
+        err,
+        ApiError::DockerResponseServerError {
+            status_code: 404,
+            ..
+        }
+    )
+}
+
+fn split_labels(labels: &[String]) -> HashMap<String, String> {
+    labels
+        .iter()
+        .map(|label| match label.split_once('=') {
+            Some((key, value)) => (key.to_owned(), value.to_owned()),
+            None => (label.clone(), String::new()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive_entries(archive: &[u8]) -> Vec<(String, tar::EntryType)> {
+        tar::Archive::new(archive)
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                let path = entry.path().unwrap().to_string_lossy().into_owned();
+                (path, entry.header().entry_type())
+            })
+            .collect()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn build_context_matches_the_cli_engines() {
+        let dir = std::env::temp_dir().join(format!("davy-api-context-{}", std::process::id()));
+        let context = dir.join("context");
+        fs::create_dir_all(&context).unwrap();
+        fs::write(dir.join("Dockerfile"), "FROM scratch\nCOPY app.txt /\n").unwrap();
+        fs::write(dir.join("app.txt"), "app").unwrap();
+        fs::write(context.join("app.txt"), "app").unwrap();
+        std::os::unix::fs::symlink(dir.join("Dockerfile"), context.join("link")).unwrap();
+
+        let mut request = BuildRequest {
+            dockerfile: &dir.join("Dockerfile"),
+            context_dir: None,
+            image: "davy-test",
+            build_args: Vec::new(),
+            labels: Vec::new(),
+            pull: false,
+            no_cache: false,
+        };
+        let cli_context = crate::engine::Docker
+            .build_command(&request)
+            .get_args()
+            .last()
+            .map(|arg| arg.to_owned());
+        let (default_name, default) = build_context(&request).unwrap();
+        request.context_dir = Some(&context);
+        let (explicit_name, explicit) = build_context(&request).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(cli_context, Some(dir.clone().into_os_string()));
+        assert_eq!(default_name, "Dockerfile");
+        let entries = archive_entries(&default);
+        assert!(entries.contains(&("Dockerfile".to_owned(), tar::EntryType::Regular)));
+        assert!(entries.contains(&("app.txt".to_owned(), tar::EntryType::Regular)));
+        assert!(entries.iter().any(|(path, _)| path.starts_with("context")));
+        assert_eq!(explicit_name, ".davy.Dockerfile");
+        let entries = archive_entries(&explicit);
+        assert!(entries.contains(&("link".to_owned(), tar::EntryType::Symlink)));
+        assert!(entries.contains(&("app.txt".to_owned(), tar::EntryType::Regular)));
+        assert!(entries.contains(&(".davy.Dockerfile".to_owned(), tar::EntryType::Regular)));
+    }
+
+    #[test]
+    fn split_labels_handles_missing_values() {
+        let labels = split_labels(&["davy.managed=true".to_owned(), "flag".to_owned()]);
+        assert_eq!(labels.get("davy.managed").map(String::as_str), Some("true"));
+        assert_eq!(labels.get("flag").map(String::as_str), Some(""));
+    }
+}
@@ -0,0 +1,283 @@
+//! `--env-file` files, read the way `docker run --env-file` reads them, and
+//! a project's `.env` files, read the way dotenv libraries read them.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+use anyhow::{Context, Result, bail};
+
+use crate::{DavyError, dry_run, note};
+
+/// Read `KEY=VALUE` lines from `path`.
+pub fn read(path: &Path) -> Result<Vec<String>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("failed to read env file {}", path.display()))?;
+    parse(&contents, |key| env::var(key).ok())
+        .with_context(|| format!("invalid env file {}", path.display()))
+}
+
+/// Parse env file `contents` as docker does: blank lines and lines starting
+/// with `#` are skipped, values are taken literally (quotes included), and a
+/// bare `KEY` takes its value from `lookup`, or is dropped when that has none.
+pub fn parse(contents: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<Vec<String>> {
+    let mut vars = Vec::new();
+    let contents = contents.strip_prefix('\u{feff}').unwrap_or(contents);
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim_start();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key, Some(value)),
+            None => (line, None),
+        };
+        if key.is_empty() {
+            bail!("no variable name on line {}: '{line}'", number + 1);
+        }
+        if key.contains(char::is_whitespace) {
+            bail!(
+                "variable '{key}' on line {} contains whitespace",
+                number + 1
+            );
+        }
+        match value.map(str::to_owned).or_else(|| lookup(key)) {
+            Some(value) => vars.push(format!("{key}={value}")),
+            None => tracing::debug!("env file variable {key} is not set on the host"),
+        }
+    }
+    Ok(vars)
+}
+
+/// Project files loaded with `dotenv = true`, later ones winning.
+pub const DOTENV_FILES: &[&str] = &[".env", ".env.davy"];
+
+/// Parse `.env` `contents` as dotenv libraries do: `export ` prefixes are
+/// allowed, single-quoted values are literal, double-quoted values expand
+/// `\n`, `\t`, `\"`, and `\\`, and unquoted values end at ` #`.
+/// Variables are not expanded, and lines without `=` are skipped.
+pub fn parse_dotenv(contents: &str) -> Result<Vec<String>> {
+    let mut vars = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            bail!("invalid variable name on line {}: '{key}'", number + 1);
+        }
+        let value = value.trim_start();
+        let value = if let Some(rest) = value.strip_prefix('\'') {
+            let Some((quoted, _)) = rest.split_once('\'') else {
+                bail!("unterminated quote on line {}", number + 1);
+            };
+            quoted.to_owned()
+        } else if let Some(rest) = value.strip_prefix('"') {
+            unescape_double_quoted(rest)
+                .with_context(|| format!("unterminated quote on line {}", number + 1))?
+        } else {
+            match value.find(" #") {
+                Some(end) => value[..end].trim_end().to_owned(),
+                None => value.trim_end().to_owned(),
+            }
+        };
+        vars.push(format!("{key}={value}"));
+    }
+    Ok(vars)
+}
+
+fn unescape_double_quoted(rest: &str) -> Option<String> {
+    let mut value = String::new();
+    let mut chars = rest.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'r' => value.push('\r'),
+                other @ ('"' | '\\') => value.push(other),
+                other => {
+                    value.push('\\');
+                    value.push(other);
+                }
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+/// Variables from the project's `.env` files, with a note naming the keys
+/// (never the values) loaded from each.
+pub fn load_dotenv(project_dir: &Path) -> Result<Vec<String>> {
+    let mut vars = Vec::new();
+    for name in DOTENV_FILES {
+        let path = project_dir.join(name);
+        if !path.is_file() {
+            continue;
+        }
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let loaded =
+            parse_dotenv(&contents).with_context(|| format!("invalid {}", path.display()))?;
+        let keys = loaded
+            .iter()
+            .filter_map(|kv| kv.split_once('=').map(|(key, _)| key))
+            .collect::<Vec<_>>();
+        note!("loaded {name}: {} (values hidden)", keys.join(", "));
+        vars.extend(loaded);
+    }
+    Ok(vars)
+}
+
+/// A `--env-from-cmd KEY=COMMAND` variable, set from a host command's output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvCommand {
+    pub key: String,
+    pub command: String,
+}
+
+impl FromStr for EnvCommand {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<EnvCommand, String> {
+        match value.split_once('=') {
+            Some((key, command))
+                if !key.is_empty()
+                    && !key.contains(char::is_whitespace)
+                    && !command.trim().is_empty() =>
+            {
+                Ok(EnvCommand {
+                    key: key.to_owned(),
+                    command: command.to_owned(),
+                })
+            }
+            _ => Err(format!(
+                "invalid env command '{value}' (expected KEY=COMMAND)"
+            )),
+        }
+    }
+}
+
+impl EnvCommand {
+    /// Run the command with `sh -c` on the host and return its output, less
+    /// the trailing newline. Its stdin and stderr stay on the terminal so
+    /// password managers can prompt. Dry runs skip it and return "".
+    pub fn value(&self) -> Result<String> {
+        self.value_with_stdin(Stdio::inherit())
+    }
+
+    fn value_with_stdin(&self, stdin: Stdio) -> Result<String> {
+        let name = format!("--env-from-cmd {}", self.key);
+        if dry_run() {
+            note!("would run `{}` for {}", self.command, self.key);
+            return Ok(String::new());
+        }
+        tracing::debug!("{name}: {}", self.command);
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .stdin(stdin)
+            .stderr(Stdio::inherit())
+            .output()
+            .with_context(|| format!("failed to run {name}"))?;
+        if !output.status.success() {
+            return Err(DavyError::CommandFailed {
+                command: name,
+                code: output.status.code(),
+            }
+            .into());
+        }
+        let mut value = String::from_utf8(output.stdout)
+            .with_context(|| format!("{name} printed non-UTF-8"))?;
+        if value.ends_with('\n') {
+            value.pop();
+            if value.ends_with('\r') {
+                value.pop();
+            }
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_like_docker() {
+        let contents =
+            "\u{feff}# secrets\n\n  API_KEY=abc=123\nQUOTED=\"kept\" \nFROM_HOST\nUNSET\nEMPTY=\n";
+        let lookup = |key: &str| (key == "FROM_HOST").then(|| "host".to_owned());
+        assert_eq!(
+            parse(contents, lookup).unwrap(),
+            [
+                "API_KEY=abc=123",
+                "QUOTED=\"kept\" ",
+                "FROM_HOST=host",
+                "EMPTY="
+            ]
+        );
+        assert!(parse("=value\n", |_| None).is_err());
+        assert!(parse("MY KEY=value\n", |_| None).is_err());
+    }
+
+    #[test]
+    fn runs_env_commands() {
+        let cmd = "TOKEN=printf 'a=b\\n\\n'".parse::<EnvCommand>().unwrap();
+        assert_eq!(cmd.key, "TOKEN");
+        assert_eq!(cmd.value().unwrap(), "a=b\n");
+        assert!("TOKEN=".parse::<EnvCommand>().is_err());
+        assert!("=echo".parse::<EnvCommand>().is_err());
+        assert!(
+            "FAIL=exit 3"
+                .parse::<EnvCommand>()
+                .unwrap()
+                .value()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn env_commands_read_stdin() {
+        let path = env::temp_dir().join(format!("davy-env-stdin-{}", std::process::id()));
+        fs::write(&path, "typed-secret\n").unwrap();
+        let stdin = Stdio::from(fs::File::open(&path).unwrap());
+        let cmd = "TOKEN=read -r line && echo \"$line\""
+            .parse::<EnvCommand>()
+            .unwrap();
+        let value = cmd.value_with_stdin(stdin);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(value.unwrap(), "typed-secret");
+    }
+
+    #[test]
+    fn parses_dotenv() {
+        let contents = r#"
+# local settings
+export DATABASE_URL=postgres://localhost/app  # dev db
+SINGLE='a "b" $c'
+DOUBLE="line\nnext \"q\""
+HASH=abc#def
+NOT_A_VAR
+"#;
+        assert_eq!(
+            parse_dotenv(contents).unwrap(),
+            [
+                "DATABASE_URL=postgres://localhost/app",
+                "SINGLE=a \"b\" $c",
+                "DOUBLE=line\nnext \"q\"",
+                "HASH=abc#def",
+            ]
+        );
+        assert!(parse_dotenv("OPEN=\"unterminated\n").is_err());
+    }
+}
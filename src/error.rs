@@ -0,0 +1,179 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::engine::ENGINE_NAMES;
+
+/// Failures callers may want to branch on. Library functions return
+/// `anyhow::Error`; use `err.downcast_ref::<DavyError>()` to get at these,
+/// even when context was added on top.
+#[derive(Debug, thiserror::Error)]
+pub enum DavyError {
+    #[error("no container engine found on PATH (looked for {})", ENGINE_NAMES.join(", "))]
+    DockerNotFound,
+
+    #[error("unknown container engine '{name}' (expected one of: {})", ENGINE_NAMES.join(", "))]
+    UnknownEngine { name: String },
+
+    #[error("{}", profile_not_found(name, available))]
+    ProfileNotFound {
+        name: String,
+        available: Vec<String>,
+    },
+
+    #[error(
+        "project config {} sets {}, which only the global config or the command line may set",
+        path.display(),
+        keys.join(", ")
+    )]
+    GlobalOnlyConfig { path: PathBuf, keys: Vec<String> },
+
+    #[error("project dir not found: {}", path.display())]
+    ProjectDirMissing { path: PathBuf },
+
+    #[error("Dockerfile not found at: {}", path.display())]
+    DockerfileMissing { path: PathBuf },
+
+    #[error(
+        "no Dockerfile found (looked for {}); run `davy dockerfile install`, or use --dockerfile, --local-dockerfile, or DAVY_DOCKERFILE",
+        searched.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(" and ")
+    )]
+    NoDockerfile { searched: Vec<PathBuf> },
+
+    #[error("image '{image}' not found (and --no-build was set)")]
+    ImageMissing { image: String },
+
+    #[error("{label} mount source not found: {}", path.display())]
+    MountSourceMissing { label: String, path: PathBuf },
+
+    #[error("{label} mount source is not a {expected}: {}", path.display())]
+    MountSourceInvalid {
+        label: String,
+        path: PathBuf,
+        expected: &'static str,
+    },
+
+    #[error("device not found: {}", path.display())]
+    DeviceMissing { path: PathBuf },
+
+    #[error("not a device: {} (expected a character or block device)", path.display())]
+    NotADevice { path: PathBuf },
+
+    #[error("image build failed{}", exit_suffix(*code))]
+    BuildFailed { code: Option<i32> },
+
+    #[error("{command} {}", exit_description(*code))]
+    CommandFailed { command: String, code: Option<i32> },
+
+    #[error("container '{name}' not found")]
+    ContainerNotFound { name: String },
+
+    #[error("'{name}' is not a davy container")]
+    NotDavyContainer { name: String },
+
+    #[error(
+        "no {}davy container for project {}",
+        if *running { "running " } else { "" },
+        project.display()
+    )]
+    NoProjectContainer { project: PathBuf, running: bool },
+
+    #[error("sandbox timed out after {}", humantime::format_duration(*after))]
+    TimedOut { after: Duration },
+}
+
+impl DavyError {
+    /// Exit code the CLI uses for this failure (1 for anything untyped).
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            DavyError::UnknownEngine { .. }
+            | DavyError::ProfileNotFound { .. }
+            | DavyError::GlobalOnlyConfig { .. } => 2,
+            DavyError::DockerNotFound => 3,
+            DavyError::ImageMissing { .. } => 4,
+            DavyError::DockerfileMissing { .. } | DavyError::NoDockerfile { .. } => 5,
+            DavyError::ProjectDirMissing { .. }
+            | DavyError::MountSourceMissing { .. }
+            | DavyError::MountSourceInvalid { .. }
+            | DavyError::DeviceMissing { .. }
+            | DavyError::NotADevice { .. } => 6,
+            DavyError::BuildFailed { .. } => 7,
+            DavyError::ContainerNotFound { .. }
+            | DavyError::NotDavyContainer { .. }
+            | DavyError::NoProjectContainer { .. } => 8,
+            DavyError::CommandFailed { .. } => 1,
+            // Same as timeout(1).
+            DavyError::TimedOut { .. } => 124,
+        }
+    }
+}
+
+fn profile_not_found(name: &str, available: &[String]) -> String {
+    if available.is_empty() {
+        format!("profile '{name}' not found (no profiles are defined)")
+    } else {
+        format!(
+            "profile '{name}' not found (available: {})",
+            available.join(", ")
+        )
+    }
+}
+
+fn exit_suffix(code: Option<i32>) -> String {
+    code.map(|code| format!(" (exit code {code})"))
+        .unwrap_or_default()
+}
+
+fn exit_description(code: Option<i32>) -> String {
+    match code {
+        Some(code) => format!("exited with status code {code}"),
+        None => "terminated by signal".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Context;
+
+    use super::*;
+
+    #[test]
+    fn typed_errors_survive_context() {
+        let err = Err::<(), _>(DavyError::ImageMissing {
+            image: "davy-sandbox:latest".to_owned(),
+        })
+        .context("failed to start sandbox")
+        .unwrap_err();
+        let typed = err.downcast_ref::<DavyError>().expect("typed error");
+        assert_eq!(typed.exit_code(), 4);
+        assert_eq!(
+            format!("{err:#}"),
+            "failed to start sandbox: image 'davy-sandbox:latest' not found (and --no-build was set)"
+        );
+    }
+
+    #[test]
+    fn messages_match_previous_wording() {
+        let err = DavyError::CommandFailed {
+            command: "docker run".to_owned(),
+            code: None,
+        };
+        assert_eq!(err.to_string(), "docker run terminated by signal");
+        let err = DavyError::NoProjectContainer {
+            project: PathBuf::from("/work/app"),
+            running: true,
+        };
+        assert_eq!(
+            err.to_string(),
+            "no running davy container for project /work/app"
+        );
+    }
+
+    #[test]
+    fn timeouts_exit_like_timeout_1() {
+        let err = DavyError::TimedOut {
+            after: Duration::from_secs(30 * 60),
+        };
+        assert_eq!(err.exit_code(), 124);
+        assert_eq!(err.to_string(), "sandbox timed out after 30m");
+    }
+}
@@ -0,0 +1,67 @@
+//! Git setup for the sandbox: the host user's identity, and trust in
+//! /project, whose owner may not match the container user.
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Environment that makes commits in the sandbox use the host identity and
+/// marks /project as a safe directory.
+pub fn sandbox_env(project_dir: &Path) -> Vec<String> {
+    env_for(
+        host_config(project_dir, "user.name"),
+        host_config(project_dir, "user.email"),
+    )
+}
+
+fn env_for(name: Option<String>, email: Option<String>) -> Vec<String> {
+    let mut env = vec![
+        "GIT_CONFIG_COUNT=1".to_owned(),
+        "GIT_CONFIG_KEY_0=safe.directory".to_owned(),
+        "GIT_CONFIG_VALUE_0=/project".to_owned(),
+    ];
+    if let Some(name) = name {
+        env.push(format!("GIT_AUTHOR_NAME={name}"));
+        env.push(format!("GIT_COMMITTER_NAME={name}"));
+    }
+    if let Some(email) = email {
+        env.push(format!("GIT_AUTHOR_EMAIL={email}"));
+        env.push(format!("GIT_COMMITTER_EMAIL={email}"));
+    }
+    env
+}
+
+/// `git config --get KEY` as seen from the project, so repository settings
+/// win over global ones.
+fn host_config(project_dir: &Path, key: &str) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(project_dir)
+        .arg("config")
+        .arg("--get")
+        .arg(key)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    (!value.is_empty()).then_some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_sets_identity_and_safe_directory() {
+        let env = env_for(Some("Ada".to_owned()), None);
+        assert!(env.contains(&"GIT_CONFIG_VALUE_0=/project".to_owned()));
+        assert!(env.contains(&"GIT_COMMITTER_NAME=Ada".to_owned()));
+        assert!(
+            !env.iter()
+                .any(|value| value.starts_with("GIT_AUTHOR_EMAIL="))
+        );
+    }
+}
@@ -0,0 +1,51 @@
+//! A persistent volume over `/home/dev`, so tool installs, caches, and agent
+//! state outlive the container.
+
+use std::path::Path;
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::remote;
+
+/// Where the home volume is mounted.
+pub const MOUNT_DIR: &str = "/home/dev";
+
+/// Which sandboxes share a home volume.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HomeScope {
+    /// One home for all of the user's sandboxes
+    #[default]
+    User,
+    /// One home per project directory
+    Project,
+}
+
+/// Name of the home volume for `host_uid` and `scope`.
+pub fn volume_name(host_uid: u32, scope: HomeScope, project_dir: &Path) -> String {
+    match scope {
+        HomeScope::User => format!("davy-home-{host_uid}"),
+        HomeScope::Project => format!("davy-home-{host_uid}-{}", remote::project_hash(project_dir)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_home_volumes_by_scope() {
+        let (app, lib) = (Path::new("/work/app"), Path::new("/work/lib"));
+        assert_eq!(volume_name(1000, HomeScope::User, app), "davy-home-1000");
+        assert_eq!(
+            volume_name(1000, HomeScope::User, app),
+            volume_name(1000, HomeScope::User, lib)
+        );
+        assert!(volume_name(1000, HomeScope::Project, app).starts_with("davy-home-1000-"));
+        assert_ne!(
+            volume_name(1000, HomeScope::Project, app),
+            volume_name(1000, HomeScope::Project, lib)
+        );
+    }
+}
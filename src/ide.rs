@@ -0,0 +1,237 @@
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::net::{IpAddr, TcpStream, ToSocketAddrs};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+
+use crate::note;
+
+/// Port ttyd listens on inside the container.
+pub const WEB_TERMINAL_PORT: u16 = 7681;
+
+/// How long to wait for sshd in the sandbox to answer.
+const SSH_READY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Where an IDE reaches the sandbox's sshd.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SshTarget {
+    pub host: String,
+    pub port: u16,
+}
+
+impl SshTarget {
+    /// Remote-SSH authority for an explicit user and port: hex-encoded JSON,
+    /// so no `~/.ssh/config` entry is needed.
+    pub fn vscode_authority(&self) -> String {
+        let json = serde_json::json!({
+            "hostName": self.host,
+            "user": "dev",
+            "port": self.port,
+        })
+        .to_string();
+        let hex: String = json.bytes().map(|byte| format!("{byte:02x}")).collect();
+        format!("ssh-remote+{hex}")
+    }
+
+    /// `code` command line that opens /project.
+    pub fn code_command(&self) -> String {
+        format!("code --remote {} /project", self.vscode_authority())
+    }
+
+    /// `vscode://` URI that opens /project.
+    pub fn vscode_uri(&self) -> String {
+        format!("vscode://vscode-remote/{}/project", self.vscode_authority())
+    }
+
+    /// Wait until sshd sends its banner (the engine's port proxy accepts
+    /// connections before sshd is up).
+    pub fn wait_ready(&self) -> bool {
+        let deadline = Instant::now() + SSH_READY_TIMEOUT;
+        while Instant::now() < deadline {
+            if self.answers() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(500));
+        }
+        false
+    }
+
+    fn answers(&self) -> bool {
+        let Ok(addrs) = (self.host.as_str(), self.port).to_socket_addrs() else {
+            return false;
+        };
+        addrs.into_iter().any(|addr| {
+            let Ok(mut stream) = TcpStream::connect_timeout(&addr, Duration::from_secs(2)) else {
+                return false;
+            };
+            let _ = stream.set_read_timeout(Some(Duration::from_secs(2)));
+            let mut banner = [0; 4];
+            stream.read_exact(&mut banner).is_ok() && &banner == b"SSH-"
+        })
+    }
+
+    /// Wait for sshd, then print how to connect VS Code and, with `open`,
+    /// launch it.
+    pub fn announce(&self, open: bool) {
+        if !self.wait_ready() {
+            eprintln!(
+                "davy: warning: sshd did not answer on {}:{} within {}s.",
+                self.host,
+                self.port,
+                SSH_READY_TIMEOUT.as_secs()
+            );
+            return;
+        }
+        note!("SSH ready: ssh -p {} dev@{}", self.port, self.host);
+        note!("VS Code: {}", self.code_command());
+        note!("VS Code URI: {}", self.vscode_uri());
+        if open {
+            let launched = Command::new("code")
+                .arg("--remote")
+                .arg(self.vscode_authority())
+                .arg("/project")
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn();
+            if let Err(err) = launched {
+                eprintln!("davy: warning: could not launch `code`: {err}");
+            }
+        }
+    }
+}
+
+/// Where `--web-terminal [[IP:]PORT]` publishes ttyd; 127.0.0.1 unless an
+/// IP is given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WebTerminal {
+    pub bind: IpAddr,
+    pub port: u16,
+}
+
+impl WebTerminal {
+    /// Terminals reachable from other machines get a password.
+    pub fn needs_credential(&self) -> bool {
+        !self.bind.is_loopback()
+    }
+
+    /// `-p` value for the engine.
+    pub fn publish_arg(&self) -> String {
+        match self.bind {
+            IpAddr::V4(ip) => format!("{ip}:{}:{WEB_TERMINAL_PORT}", self.port),
+            IpAddr::V6(ip) => format!("[{ip}]:{}:{WEB_TERMINAL_PORT}", self.port),
+        }
+    }
+
+    /// URL to open; `host` replaces an unspecified bind address.
+    pub fn url(&self, host: &str) -> String {
+        let host = match self.bind {
+            ip if ip.is_unspecified() => host.to_owned(),
+            IpAddr::V4(ip) => ip.to_string(),
+            IpAddr::V6(ip) => format!("[{ip}]"),
+        };
+        format!("http://{host}:{}/", self.port)
+    }
+}
+
+impl FromStr for WebTerminal {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<WebTerminal, String> {
+        let invalid = || format!("invalid web terminal address '{value}' (expected [IP:]PORT)");
+        let (bind, port) = match value.rsplit_once(':') {
+            Some((ip, port)) => (
+                ip.trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .parse()
+                    .map_err(|_| invalid())?,
+                port,
+            ),
+            None => (IpAddr::from([127, 0, 0, 1]), value),
+        };
+        let port = port
+            .parse::<u16>()
+            .ok()
+            .filter(|port| *port > 0)
+            .ok_or_else(invalid)?;
+        Ok(WebTerminal { bind, port })
+    }
+}
+
+impl fmt::Display for WebTerminal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.bind {
+            IpAddr::V6(ip) => write!(f, "[{ip}]:{}", self.port),
+            ip => write!(f, "{ip}:{}", self.port),
+        }
+    }
+}
+
+/// Random password for a shared web terminal.
+pub fn random_credential() -> Result<String> {
+    let mut bytes = [0; 12];
+    File::open("/dev/urandom")
+        .and_then(|mut random| random.read_exact(&mut bytes))
+        .context("failed to read /dev/urandom")?;
+    Ok(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vscode_targets_encode_user_and_port() {
+        let target = SshTarget {
+            host: "localhost".to_owned(),
+            port: 2222,
+        };
+        let authority = target.vscode_authority();
+        let hex = authority.strip_prefix("ssh-remote+").unwrap();
+        let json: Vec<u8> = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json["hostName"], "localhost");
+        assert_eq!(json["user"], "dev");
+        assert_eq!(json["port"], 2222);
+        assert_eq!(
+            target.vscode_uri(),
+            format!("vscode://vscode-remote/{authority}/project")
+        );
+    }
+
+    #[test]
+    fn parses_web_terminal_addresses() {
+        let local: WebTerminal = "7681".parse().unwrap();
+        assert!(!local.needs_credential());
+        assert_eq!(local.publish_arg(), "127.0.0.1:7681:7681");
+        assert_eq!(local.url("box"), "http://127.0.0.1:7681/");
+        let shared: WebTerminal = "0.0.0.0:8080".parse().unwrap();
+        assert!(shared.needs_credential());
+        assert_eq!(shared.url("box"), "http://box:8080/");
+        assert_eq!(shared.to_string(), "0.0.0.0:8080");
+        for invalid in ["", "0", "lan:8080", "99999"] {
+            assert!(invalid.parse::<WebTerminal>().is_err(), "{invalid}");
+        }
+        assert_eq!(random_credential().unwrap().len(), 24);
+    }
+
+    #[test]
+    fn closed_ports_are_not_ready() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let target = SshTarget {
+            host: "127.0.0.1".to_owned(),
+            port,
+        };
+        assert!(!target.answers());
+    }
+}
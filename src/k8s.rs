@@ -0,0 +1,431 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+
+use anyhow::{Context, Result, bail};
+use chrono::Local;
+use clap::ValueEnum;
+use serde_json::{Value, json};
+
+use crate::containers::{MANAGED_LABEL, PROJECT_LABEL, STARTED_LABEL};
+use crate::run_checked;
+
+/// Where `davy run` starts the sandbox.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Backend {
+    /// A local (or remote) container engine
+    #[default]
+    Container,
+    /// A pod in the current kubeconfig context (experimental)
+    K8s,
+}
+
+/// Everything needed to describe the sandbox pod.
+pub struct PodSpec<'a> {
+    pub name: &'a str,
+    pub image: &'a str,
+    pub project_dir: &'a Path,
+    pub claim: &'a str,
+    /// Names of the variables set from the [`env_secret_manifest`] Secret.
+    pub env_keys: &'a [&'a str],
+}
+
+/// `kubectl` scoped to a namespace (the context's default when `None`).
+pub struct Kubectl {
+    namespace: Option<String>,
+}
+
+impl Kubectl {
+    pub fn new(namespace: Option<String>) -> Kubectl {
+        Kubectl { namespace }
+    }
+
+    fn command(&self) -> Command {
+        let mut cmd = Command::new("kubectl");
+        if let Some(namespace) = &self.namespace {
+            cmd.arg("--namespace").arg(namespace);
+        }
+        cmd
+    }
+
+    /// The `kubectl` invocation as a user would type it, for hints.
+    pub fn display(&self) -> String {
+        match &self.namespace {
+            Some(namespace) => format!("kubectl --namespace {namespace}"),
+            None => "kubectl".to_owned(),
+        }
+    }
+
+    pub fn exists(&self, kind: &str, name: &str) -> Result<bool> {
+        let status = self
+            .command()
+            .arg("get")
+            .arg(kind)
+            .arg(name)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .context("failed to run kubectl get")?;
+        Ok(status.success())
+    }
+
+    pub fn apply(&self, manifest: &Value) -> Result<()> {
+        let mut child = self
+            .command()
+            .arg("apply")
+            .arg("--filename")
+            .arg("-")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .context("failed to run kubectl apply")?;
+        child
+            .stdin
+            .take()
+            .context("failed to open kubectl stdin")?
+            .write_all(manifest.to_string().as_bytes())
+            .context("failed to write manifest to kubectl")?;
+        let status = child.wait().context("failed to wait for kubectl apply")?;
+        if !status.success() {
+            bail!("kubectl apply failed");
+        }
+        Ok(())
+    }
+
+    /// UID of an existing object, for owner references.
+    pub fn uid(&self, kind: &str, name: &str) -> Result<String> {
+        let output = self
+            .command()
+            .arg("get")
+            .arg(kind)
+            .arg(name)
+            .arg("--output")
+            .arg("jsonpath={.metadata.uid}")
+            .stdin(Stdio::null())
+            .output()
+            .context("failed to run kubectl get")?;
+        let uid = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+        if !output.status.success() || uid.is_empty() {
+            bail!("kubectl get {kind} {name} failed");
+        }
+        Ok(uid)
+    }
+
+    pub fn wait_ready(&self, pod: &str, timeout: &str) -> Result<()> {
+        let mut cmd = self.command();
+        cmd.arg("wait")
+            .arg("--for=condition=Ready")
+            .arg(format!("--timeout={timeout}"))
+            .arg(format!("pod/{pod}"))
+            .stdout(Stdio::null());
+        run_checked(&mut cmd, "kubectl wait")
+    }
+
+    /// Copy the contents of `project_dir` into `/project` of the pod.
+    pub fn copy_in(&self, pod: &str, project_dir: &Path) -> Result<()> {
+        let mut cmd = self.command();
+        cmd.arg("cp")
+            .arg(project_dir)
+            .arg(format!("{pod}:/project"));
+        run_checked(&mut cmd, "kubectl cp (project to pod)")
+    }
+
+    /// Copy `/project` of the pod back over `project_dir`.
+    pub fn copy_out(&self, pod: &str, project_dir: &Path) -> Result<()> {
+        let mut cmd = self.command();
+        cmd.arg("cp")
+            .arg(format!("{pod}:/project"))
+            .arg(project_dir);
+        run_checked(&mut cmd, "kubectl cp (pod to project)")
+    }
+
+    /// Run `command` (bash by default) in `/project` of the pod.
+    pub fn exec(&self, pod: &str, command: &[OsString]) -> Result<ExitStatus> {
+        let mut cmd = self.command();
+        cmd.arg("exec").arg("--stdin");
+        if std::io::stdin().is_terminal() && std::io::stdout().is_terminal() {
+            cmd.arg("--tty");
+        }
+        cmd.arg(pod)
+            .arg("--")
+            .arg("sh")
+            .arg("-c")
+            .arg("cd /project && exec \"$@\"")
+            .arg("sh");
+        if command.is_empty() {
+            cmd.arg("bash");
+        } else {
+            cmd.args(command);
+        }
+        cmd.status().context("failed to run kubectl exec")
+    }
+
+    pub fn delete(&self, kind: &str, name: &str) -> Result<()> {
+        let mut cmd = self.command();
+        cmd.arg("delete")
+            .arg(kind)
+            .arg(name)
+            .arg("--ignore-not-found")
+            .arg("--wait=false")
+            .stdout(Stdio::null());
+        run_checked(&mut cmd, &format!("kubectl delete {kind}"))
+    }
+}
+
+/// Turn a container name into a valid pod name (RFC 1123 label).
+pub fn pod_name(name: &str) -> String {
+    let sanitized = name
+        .to_ascii_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .take(63)
+        .collect::<String>();
+    let trimmed = sanitized.trim_matches('-');
+    if trimmed.is_empty() {
+        "davy".to_owned()
+    } else {
+        trimmed.to_owned()
+    }
+}
+
+/// Name of the Secret holding a pod's environment.
+pub fn env_secret_name(pod: &str) -> String {
+    format!("{pod}-env")
+}
+
+/// Split `KEY=VALUE` entries into the variable names, in order and without
+/// repeats, and the values keyed by name (the last one wins, as with `-e`).
+pub fn split_env(env: &[String]) -> (Vec<&str>, BTreeMap<&str, &str>) {
+    let mut keys = Vec::new();
+    let mut values = BTreeMap::new();
+    for kv in env {
+        let (name, value) = kv.split_once('=').unwrap_or((kv.as_str(), ""));
+        if values.insert(name, value).is_none() {
+            keys.push(name);
+        }
+    }
+    (keys, values)
+}
+
+/// Secret holding the sandbox's environment, so values stay out of the Pod
+/// spec. Owned by the pod once it exists, so it is deleted with it.
+pub fn env_secret_manifest(
+    name: &str,
+    values: &BTreeMap<&str, &str>,
+    owner: Option<(&str, &str)>,
+) -> Value {
+    let mut manifest = json!({
+        "apiVersion": "v1",
+        "kind": "Secret",
+        "type": "Opaque",
+        "metadata": {
+            "name": name,
+            "labels": { MANAGED_LABEL: "true" },
+        },
+        "stringData": values,
+    });
+    if let Some((pod, uid)) = owner {
+        manifest["metadata"]["ownerReferences"] = json!([{
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "name": pod,
+            "uid": uid,
+        }]);
+    }
+    manifest
+}
+
+/// Pod that idles so davy can copy the project in and exec into it.
+pub fn pod_manifest(spec: &PodSpec) -> Value {
+    let secret = env_secret_name(spec.name);
+    let env = spec
+        .env_keys
+        .iter()
+        .map(|name| {
+            json!({
+                "name": name,
+                "valueFrom": { "secretKeyRef": { "name": secret, "key": name } },
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": {
+            "name": spec.name,
+            "labels": { MANAGED_LABEL: "true" },
+            // Label values cannot hold paths, so project details are annotations.
+            "annotations": {
+                PROJECT_LABEL: spec.project_dir.display().to_string(),
+                STARTED_LABEL: Local::now().to_rfc3339(),
+            },
+        },
+        "spec": {
+            "restartPolicy": "Never",
+            "containers": [{
+                "name": "sandbox",
+                "image": spec.image,
+                "command": ["sleep", "infinity"],
+                "workingDir": "/project",
+                "env": env,
+                "volumeMounts": [{ "name": "project", "mountPath": "/project" }],
+            }],
+            "volumes": [{
+                "name": "project",
+                "persistentVolumeClaim": { "claimName": spec.claim },
+            }],
+        },
+    })
+}
+
+/// Claim holding the project copy.
+pub fn pvc_manifest(name: &str, storage: &str, storage_class: Option<&str>) -> Value {
+    let mut manifest = json!({
+        "apiVersion": "v1",
+        "kind": "PersistentVolumeClaim",
+        "metadata": {
+            "name": name,
+            "labels": { MANAGED_LABEL: "true" },
+        },
+        "spec": {
+            "accessModes": ["ReadWriteOnce"],
+            "resources": { "requests": { "storage": storage } },
+        },
+    });
+    if let Some(class) = storage_class {
+        manifest["spec"]["storageClassName"] = json!(class);
+    }
+    manifest
+}
+
+/// The host kubeconfig with certificates and keys inlined, cut down to
+/// `context` (and its cluster and user) when given.
+pub fn sandbox_kubeconfig(context: Option<&str>) -> Result<Value> {
+    let mut cmd = Command::new("kubectl");
+    cmd.arg("config")
+        .arg("view")
+        .arg("--flatten")
+        .arg("--output")
+        .arg("json");
+    if let Some(context) = context {
+        cmd.arg("--minify").arg("--context").arg(context);
+    }
+    let output = cmd
+        .stdin(Stdio::null())
+        .output()
+        .context("failed to run kubectl config view (is kubectl installed?)")?;
+    if !output.status.success() {
+        bail!(
+            "kubectl config view failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    serde_json::from_slice(&output.stdout).context("kubectl config view did not print JSON")
+}
+
+/// Cluster servers on loopback, which are not reachable from a container.
+pub fn loopback_servers(kubeconfig: &Value) -> Vec<String> {
+    kubeconfig["clusters"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|cluster| cluster["cluster"]["server"].as_str())
+        .filter(|server| {
+            let rest = server.split_once("://").map_or(*server, |(_, rest)| rest);
+            let host = match rest.strip_prefix('[') {
+                Some(rest) => rest.split(']').next().unwrap_or(rest),
+                None => rest.split([':', '/']).next().unwrap_or(rest),
+            };
+            host == "localhost"
+                || host
+                    .parse::<std::net::IpAddr>()
+                    .is_ok_and(|ip| ip.is_loopback())
+        })
+        .map(str::to_owned)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pod_names_are_valid_labels() {
+        assert_eq!(pod_name("davy-My_App-20250101"), "davy-my-app-20250101");
+        assert_eq!(pod_name("__"), "davy");
+        assert_eq!(pod_name(&"a".repeat(80)).len(), 63);
+    }
+
+    #[test]
+    fn pod_manifest_mounts_claim_and_reads_env_from_secret() {
+        let manifest = pod_manifest(&PodSpec {
+            name: "davy-app",
+            image: "registry.example/davy:latest",
+            project_dir: Path::new("/work/app"),
+            claim: "davy-project-1",
+            env_keys: &["FOO"],
+        });
+        let container = &manifest["spec"]["containers"][0];
+        assert_eq!(container["image"], "registry.example/davy:latest");
+        assert_eq!(container["env"][0]["name"], "FOO");
+        assert!(container["env"][0]["value"].is_null());
+        assert_eq!(
+            container["env"][0]["valueFrom"]["secretKeyRef"],
+            json!({ "name": "davy-app-env", "key": "FOO" })
+        );
+        assert_eq!(
+            manifest["spec"]["volumes"][0]["persistentVolumeClaim"]["claimName"],
+            "davy-project-1"
+        );
+        assert_eq!(
+            manifest["metadata"]["annotations"]["davy.project"],
+            "/work/app"
+        );
+    }
+
+    #[test]
+    fn env_secret_holds_values_and_belongs_to_the_pod() {
+        let env = [
+            "TOKEN=abc".to_owned(),
+            "CI=1".to_owned(),
+            "TOKEN=def".to_owned(),
+        ];
+        let (keys, values) = split_env(&env);
+        assert_eq!(keys, ["TOKEN", "CI"]);
+        let secret = env_secret_manifest("davy-app-env", &values, Some(("davy-app", "uid-1")));
+        assert_eq!(secret["stringData"], json!({ "TOKEN": "def", "CI": "1" }));
+        assert_eq!(secret["metadata"]["ownerReferences"][0]["uid"], "uid-1");
+        assert!(
+            env_secret_manifest("davy-app-env", &values, None)["metadata"]["ownerReferences"]
+                .is_null()
+        );
+    }
+
+    #[test]
+    fn finds_loopback_servers() {
+        let kubeconfig = json!({
+            "clusters": [
+                { "name": "kind", "cluster": { "server": "https://127.0.0.1:6443" } },
+                { "name": "v6", "cluster": { "server": "https://[::1]:6443" } },
+                { "name": "prod", "cluster": { "server": "https://k8s.example.com" } },
+            ],
+        });
+        assert_eq!(
+            loopback_servers(&kubeconfig),
+            ["https://127.0.0.1:6443", "https://[::1]:6443"]
+        );
+        assert!(loopback_servers(&json!({})).is_empty());
+    }
+
+    #[test]
+    fn pvc_manifest_sets_storage_class_when_given() {
+        assert!(pvc_manifest("c", "5Gi", None)["spec"]["storageClassName"].is_null());
+        assert_eq!(
+            pvc_manifest("c", "5Gi", Some("fast"))["spec"]["storageClassName"],
+            "fast"
+        );
+    }
+}
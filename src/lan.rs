@@ -0,0 +1,160 @@
+//! The `--no-lan` firewall. Its rules are added by a short-lived helper
+//! container that joins the sandbox's network namespace with
+//! `CAP_NET_ADMIN`. The sandbox never gets that capability, so neither
+//! `sudo` in the sandbox nor `davy exec` and SSH sessions can undo the rules.
+
+use std::process::{Command, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+
+use crate::run_checked;
+
+/// Written by root in the sandbox once the helper is done: `ok` or `failed`.
+const STATUS_FILE: &str = "/run/davy-no-lan";
+
+/// How long the sandbox may take to start, and its command to wait for the
+/// firewall.
+const START_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Runs in the sandbox ahead of its command and holds it until the helper
+/// reports. Only a root-owned status file counts.
+pub const WAIT_SCRIPT: &str = r#"for _ in $(seq 600); do
+  if [ -f /run/davy-no-lan ] && [ "$(stat -c %u /run/davy-no-lan)" = 0 ]; then
+    if [ "$(cat /run/davy-no-lan)" = ok ]; then
+      exec "$@"
+    fi
+    break
+  fi
+  sleep 0.1
+done
+echo "davy: --no-lan could not set up its firewall; not starting." >&2
+exit 1"#;
+
+/// Blocks private ranges and the host gateway for the namespace it runs in.
+/// The sandbox's own networks (sidecars, compose) and its DNS servers stay
+/// reachable. Any rule that cannot be added, IPv6 included, fails the setup.
+const RULES_SCRIPT: &str = r#"set -euo pipefail
+for tool in iptables ip; do
+  if ! command -v "$tool" >/dev/null 2>&1; then
+    echo "davy: --no-lan needs '$tool' in the image. Rebuild with the latest rocky.Dockerfile." >&2
+    exit 1
+  fi
+done
+
+ipt() { iptables -w "$@"; }
+ipt -A OUTPUT -o lo -j ACCEPT
+ipt -A OUTPUT -m conntrack --ctstate ESTABLISHED,RELATED -j ACCEPT
+while read -r key server _; do
+  if [ "$key" = nameserver ] && [ "${server#*:}" = "$server" ]; then
+    ipt -A OUTPUT -d "$server" -p udp --dport 53 -j ACCEPT
+    ipt -A OUTPUT -d "$server" -p tcp --dport 53 -j ACCEPT
+  fi
+done </etc/resolv.conf
+read -r _ _ gateway _ < <(ip -4 route show default) || true
+if [ -n "$gateway" ]; then
+  ipt -A OUTPUT -d "$gateway" -j REJECT
+fi
+while read -r subnet _; do
+  ipt -A OUTPUT -d "$subnet" -j ACCEPT
+done < <(ip -4 route show scope link)
+for range in 10.0.0.0/8 172.16.0.0/12 192.168.0.0/16 169.254.0.0/16 100.64.0.0/10; do
+  ipt -A OUTPUT -d "$range" -j REJECT
+done
+if [ -e /proc/net/if_inet6 ]; then
+  if ! command -v ip6tables >/dev/null 2>&1; then
+    echo "davy: --no-lan needs 'ip6tables' in the image when the sandbox has IPv6." >&2
+    exit 1
+  fi
+  ip6tables -w -A OUTPUT -d fc00::/7 -j REJECT
+  ip6tables -w -A OUTPUT -d fe80::/10 -p tcp -j REJECT
+  ip6tables -w -A OUTPUT -d fe80::/10 -p udp -j REJECT
+fi"#;
+
+/// Set up the firewall for `container` from a helper running `image`, in the
+/// background: the sandbox may still be starting. The sandbox's
+/// [`WAIT_SCRIPT`] learns the outcome either way.
+pub fn spawn_firewall(
+    binary: &'static str,
+    container: &str,
+    image: &str,
+) -> JoinHandle<Result<()>> {
+    let container = container.to_owned();
+    let image = image.to_owned();
+    thread::spawn(move || {
+        let result = apply(binary, &container, &image);
+        if let Err(err) = &result {
+            eprintln!("davy: --no-lan: {err:#}");
+        }
+        result
+    })
+}
+
+fn apply(binary: &'static str, container: &str, image: &str) -> Result<()> {
+    wait_running(binary, container)?;
+    let mut helper = Command::new(binary);
+    helper
+        .arg("run")
+        .arg("--rm")
+        .arg("--network")
+        .arg(format!("container:{container}"))
+        .arg("--cap-add")
+        .arg("NET_ADMIN")
+        .arg("--user")
+        .arg("0")
+        .arg("--entrypoint")
+        .arg("bash")
+        .arg(image)
+        .arg("-c")
+        .arg(RULES_SCRIPT);
+    // Its output would land in the middle of the sandbox's terminal.
+    let result = helper
+        .stdin(Stdio::null())
+        .output()
+        .with_context(|| format!("failed to run {binary} run for the firewall helper"))
+        .and_then(|output| {
+            if !output.status.success() {
+                bail!(
+                    "the firewall helper failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+            Ok(())
+        });
+    let outcome = if result.is_ok() { "ok" } else { "failed" };
+    let mut report = Command::new(binary);
+    report
+        .arg("exec")
+        .arg("--user")
+        .arg("0")
+        .arg(container)
+        .arg("sh")
+        .arg("-c")
+        .arg(format!("echo {outcome} > {STATUS_FILE}"))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null());
+    run_checked(&mut report, &format!("{binary} exec"))?;
+    result
+}
+
+fn wait_running(binary: &str, container: &str) -> Result<()> {
+    let deadline = Instant::now() + START_TIMEOUT;
+    loop {
+        let output = Command::new(binary)
+            .arg("inspect")
+            .arg("--format")
+            .arg("{{.State.Running}}")
+            .arg(container)
+            .stderr(Stdio::null())
+            .output()
+            .with_context(|| format!("failed to run {binary} inspect"))?;
+        if String::from_utf8_lossy(&output.stdout).trim() == "true" {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            bail!("container '{container}' did not start in time");
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
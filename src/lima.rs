@@ -0,0 +1,164 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+/// A docker daemon running inside a Colima or Lima VM (common on macOS).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimaVm {
+    pub kind: LimaKind,
+    pub instance: String,
+    /// Directory holding the instance's config and sockets.
+    pub dir: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimaKind {
+    Colima,
+    Lima,
+}
+
+impl LimaKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            LimaKind::Colima => "Colima",
+            LimaKind::Lima => "Lima",
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct VmConfig {
+    #[serde(default)]
+    mounts: Vec<VmMount>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VmMount {
+    location: String,
+}
+
+impl LimaVm {
+    /// Detect a Colima or Lima VM from the docker endpoint's socket path
+    /// (`~/.colima/<profile>/docker.sock`, `~/.lima/<instance>/sock/docker.sock`).
+    pub fn from_endpoint(endpoint: &str, home: &Path) -> Option<LimaVm> {
+        let socket = Path::new(endpoint.strip_prefix("unix://")?);
+
+        let colima_home = env::var_os("COLIMA_HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.join(".colima"));
+        if let Ok(rest) = socket.strip_prefix(&colima_home)
+            && let Some(instance) = first_component(rest)
+        {
+            return Some(LimaVm {
+                kind: LimaKind::Colima,
+                dir: colima_home.join(&instance),
+                instance,
+            });
+        }
+
+        let lima_home = env::var_os("LIMA_HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.join(".lima"));
+        if let Ok(rest) = socket.strip_prefix(&lima_home)
+            && let Some(instance) = first_component(rest)
+        {
+            return Some(LimaVm {
+                kind: LimaKind::Lima,
+                dir: lima_home.join(&instance),
+                instance,
+            });
+        }
+
+        None
+    }
+
+    /// Host directories shared with the VM, from the instance's YAML config
+    /// or the tool's defaults when none are listed.
+    pub fn shared_paths(&self, home: &Path) -> Vec<PathBuf> {
+        let file = match self.kind {
+            LimaKind::Colima => "colima.yaml",
+            LimaKind::Lima => "lima.yaml",
+        };
+        let config = fs::read_to_string(self.dir.join(file))
+            .ok()
+            .and_then(|content| serde_yaml::from_str::<VmConfig>(&content).ok())
+            .unwrap_or_default();
+        parse_shared_paths(config, self.kind, home)
+    }
+}
+
+fn parse_shared_paths(config: VmConfig, kind: LimaKind, home: &Path) -> Vec<PathBuf> {
+    if config.mounts.is_empty() {
+        let tmp = match kind {
+            LimaKind::Colima => "/tmp/colima",
+            LimaKind::Lima => "/tmp/lima",
+        };
+        return vec![home.to_path_buf(), PathBuf::from(tmp)];
+    }
+    config
+        .mounts
+        .into_iter()
+        .map(|mount| match mount.location.strip_prefix('~') {
+            Some(rest) => home.join(rest.trim_start_matches('/')),
+            None => PathBuf::from(mount.location),
+        })
+        .collect()
+}
+
+/// Whether `path` lies inside one of the VM's shared directories.
+pub fn is_shared(path: &Path, shared: &[PathBuf]) -> bool {
+    shared.iter().any(|dir| path.starts_with(dir))
+}
+
+fn first_component(path: &Path) -> Option<String> {
+    path.components()
+        .next()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_colima_and_lima_sockets() {
+        let home = Path::new("/Users/me");
+        let colima =
+            LimaVm::from_endpoint("unix:///Users/me/.colima/work/docker.sock", home).unwrap();
+        assert_eq!(colima.kind, LimaKind::Colima);
+        assert_eq!(colima.instance, "work");
+        assert_eq!(colima.dir, PathBuf::from("/Users/me/.colima/work"));
+
+        let lima =
+            LimaVm::from_endpoint("unix:///Users/me/.lima/docker/sock/docker.sock", home).unwrap();
+        assert_eq!(lima.kind, LimaKind::Lima);
+        assert_eq!(lima.instance, "docker");
+
+        assert!(LimaVm::from_endpoint("unix:///var/run/docker.sock", home).is_none());
+        assert!(LimaVm::from_endpoint("tcp://127.0.0.1:2375", home).is_none());
+    }
+
+    #[test]
+    fn shared_paths_come_from_config_or_defaults() {
+        let home = Path::new("/Users/me");
+        let config: VmConfig = serde_yaml::from_str(
+            "cpu: 4\nmounts:\n  - location: ~/src\n    writable: true\n  - location: /Volumes/data\n",
+        )
+        .unwrap();
+        let shared = parse_shared_paths(config, LimaKind::Colima, home);
+        assert_eq!(
+            shared,
+            vec![
+                PathBuf::from("/Users/me/src"),
+                PathBuf::from("/Volumes/data")
+            ]
+        );
+        assert!(is_shared(Path::new("/Users/me/src/app"), &shared));
+        assert!(!is_shared(Path::new("/Users/me/other"), &shared));
+
+        let defaults = parse_shared_paths(VmConfig::default(), LimaKind::Colima, home);
+        assert!(is_shared(Path::new("/Users/me/anything"), &defaults));
+    }
+}
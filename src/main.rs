@@ -1,88 +1,31 @@
-use std::collections::HashSet;
 use std::env;
 use std::ffi::OsString;
-use std::fs;
-use std::path::{Path, PathBuf};
-use std::process::{Command, ExitStatus, Stdio};
+use std::io::IsTerminal;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::process::{ExitStatus, Stdio};
+use std::time::Duration;
 
 use anyhow::{Context, Result, bail};
-use base64::Engine;
-use base64::engine::general_purpose::STANDARD;
 use chrono::Local;
 use clap::{ArgAction, Args, Parser, Subcommand};
-#[cfg(unix)]
-use std::os::unix::fs::{FileTypeExt, MetadataExt};
-#[cfg(unix)]
-use users::os::unix::UserExt;
-use users::{get_current_gid, get_current_uid, get_user_by_uid};
-
-const DEFAULT_IMAGE: &str = "davy-sandbox:latest";
-const CLAUDE_LINK_SCRIPT: &str = r#"set -e
-mkdir -p /home/dev/.claude-auth/.claude
-touch /home/dev/.claude-auth/.claude.json
-
-if [ -e /home/dev/.claude ] && [ ! -L /home/dev/.claude ]; then
-  rm -rf /home/dev/.claude
-fi
-if [ -e /home/dev/.claude.json ] && [ ! -L /home/dev/.claude.json ]; then
-  rm -f /home/dev/.claude.json
-fi
-
-ln -sfn /home/dev/.claude-auth/.claude /home/dev/.claude
-ln -sfn /home/dev/.claude-auth/.claude.json /home/dev/.claude.json
-export CLAUDE_CONFIG_DIR=/home/dev/.claude
-
-exec "$@""#;
-
-const SSH_BOOTSTRAP_SCRIPT: &str = r#"set -e
-if ! command -v sshd >/dev/null 2>&1; then
-  echo "davy: sshd is not installed in image. Rebuild with the latest rocky.Dockerfile." >&2
-  exit 1
-fi
-
-if ! command -v ps >/dev/null 2>&1; then
-  echo "davy: 'ps' is required for remote IDE SSH helpers (VS Code and derivatives)." >&2
-  echo "davy: rebuild with the latest rocky.Dockerfile." >&2
-  exit 1
-fi
-
-if ! command -v flock >/dev/null 2>&1; then
-  echo "davy: 'flock' is required for remote IDE SSH helpers (VS Code and derivatives)." >&2
-  echo "davy: rebuild with the latest rocky.Dockerfile." >&2
-  exit 1
-fi
-
-if [ -z "${DAVY_SSH_AUTH_KEYS_B64:-}" ]; then
-  echo "davy: DAVY_SSH_AUTH_KEYS_B64 is missing." >&2
-  exit 1
-fi
-
-mkdir -p /home/dev/.ssh
-chmod 700 /home/dev/.ssh
-if ! printf "%s" "$DAVY_SSH_AUTH_KEYS_B64" | base64 -d >/home/dev/.ssh/authorized_keys 2>/dev/null; then
-  printf "%s" "$DAVY_SSH_AUTH_KEYS_B64" | base64 --decode >/home/dev/.ssh/authorized_keys
-fi
-if [ ! -s /home/dev/.ssh/authorized_keys ]; then
-  echo "davy: decoded authorized_keys is empty." >&2
-  exit 1
-fi
-chmod 600 /home/dev/.ssh/authorized_keys
-
-sudo mkdir -p /run/sshd
-if ! ls /etc/ssh/ssh_host_*_key >/dev/null 2>&1; then
-  sudo ssh-keygen -A >/dev/null
-fi
-
-sudo /usr/sbin/sshd \
-  -o PermitRootLogin=no \
-  -o PasswordAuthentication=no \
-  -o KbdInteractiveAuthentication=no \
-  -o ChallengeResponseAuthentication=no \
-  -o PubkeyAuthentication=yes \
-  -o AuthorizedKeysFile=.ssh/authorized_keys \
-  -o PidFile=/tmp/davy-sshd.pid
-
-exec "$@""#;
+use users::get_current_uid;
+
+use davy::auth::{self, AuthStore};
+use davy::caches::{self, CacheMount};
+use davy::devices::Gpus;
+use davy::dockerfiles::{self, Agent, Flavor, Lang};
+use davy::envfile::EnvCommand;
+use davy::home::{self, HomeScope};
+use davy::ide::WebTerminal;
+use davy::k8s::Backend;
+use davy::network::{self, HostEntry, PortMapping};
+use davy::remote::ProjectMode;
+use davy::resources::Ulimit;
+use davy::sandbox::{self, AuthMount, SandboxBuilder};
+use davy::sidecars;
+use davy::skills::{self, SkillsMode};
+use davy::{DavyError, Engine, config, containers, engine, note, run_checked};
 
 #[derive(Debug, Parser)]
 #[command(
@@ -92,6 +35,27 @@ exec "$@""#;
     args_conflicts_with_subcommands = true
 )]
 struct Cli {
+    /// Container engine to use: docker, podman, or nerdctl (default: auto-detect)
+    #[arg(
+        long = "engine",
+        env = "DAVY_ENGINE",
+        global = true,
+        value_name = "ENGINE"
+    )]
+    engine: Option<String>,
+
+    /// Build images and manage volumes through the Docker Engine API (docker engine; needs the docker-api feature)
+    #[arg(long = "docker-api", global = true, action = ArgAction::SetTrue)]
+    docker_api: bool,
+
+    /// Log resolution steps to stderr (-v for debug, -vv for trace)
+    #[arg(short = 'v', long = "verbose", global = true, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Hide image build output and informational messages
+    #[arg(short = 'q', long = "quiet", global = true, action = ArgAction::SetTrue)]
+    quiet: bool,
+
     #[command(subcommand)]
     command: Option<Commands>,
 
@@ -101,8 +65,56 @@ struct Cli {
 
 #[derive(Debug, Subcommand)]
 enum Commands {
+    /// Run a sandbox container (default when no subcommand is given)
+    Run(Box<RunArgs>),
+    /// Build the sandbox image
+    Build(BuildArgs),
+    /// List davy containers
+    Ls(LsArgs),
+    /// Remove davy containers
+    Rm(RmArgs),
+    /// Run a command in a running davy container
+    Exec(ExecArgs),
+    /// Attach to a running (e.g. detached) davy container
+    Attach(AttachArgs),
+    /// Stop running davy containers
+    Stop(StopArgs),
+    /// Kill running davy containers
+    Kill(KillArgs),
+    /// Show logs of a davy container
+    Logs(LogsArgs),
+    /// Remove stopped davy containers, orphaned sidecars and credentials, dangling davy images, and (optionally) unused volumes
+    Clean(CleanArgs),
+    /// List davy volumes with their size, age, and owning feature
+    Volumes(VolumesArgs),
+    /// Review skills an agent changed in a sandbox and copy them to the host
+    Skills {
+        #[command(subcommand)]
+        command: SkillsCommands,
+    },
+    /// List and remove shared cache volumes
+    Cache(CacheArgs),
+    /// Manage `--persist-home` home volumes
+    Home {
+        #[command(subcommand)]
+        command: HomeCommands,
+    },
+    /// Manage the reference Dockerfiles built into davy
+    Dockerfile {
+        #[command(subcommand)]
+        command: DockerfileCommands,
+    },
     /// Manage persistent auth state
     Auth {
+        /// Auth profile whose volumes to manage (default: the default profile)
+        #[arg(
+            long = "auth-profile",
+            env = "DAVY_AUTH_PROFILE",
+            value_name = "NAME",
+            global = true,
+            value_parser = auth::parse_profile
+        )]
+        auth_profile: Option<String>,
         #[command(subcommand)]
         command: AuthCommands,
     },
@@ -110,17 +122,389 @@ enum Commands {
 
 #[derive(Debug, Subcommand)]
 enum AuthCommands {
+    /// List auth volumes by auth profile
+    List,
+    /// Show host auth, auth volumes, and login state for each agent
+    Status {
+        /// Image for the container that reads the volumes (default: davy-sandbox:latest)
+        #[arg(long = "image", env = "DAVY_IMAGE")]
+        image: Option<String>,
+    },
     /// Claude auth volume management
     Claude {
         #[command(subcommand)]
-        command: ClaudeCommands,
+        command: AuthVolumeCommands,
+    },
+    /// OpenCode auth volume management (`--auth-opencode volume`)
+    Opencode {
+        #[command(subcommand)]
+        command: AuthVolumeCommands,
+    },
+    /// Codex auth volume management (`--auth-codex volume`)
+    Codex {
+        #[command(subcommand)]
+        command: AuthVolumeCommands,
+    },
+    /// Gemini auth volume management (`--auth-gemini volume`)
+    Gemini {
+        #[command(subcommand)]
+        command: AuthVolumeCommands,
+    },
+    /// Pi auth volume management (`--auth-pi volume`)
+    Pi {
+        #[command(subcommand)]
+        command: AuthVolumeCommands,
+    },
+    /// cursor-agent auth volume management
+    Cursor {
+        #[command(subcommand)]
+        command: AuthVolumeCommands,
     },
 }
 
 #[derive(Debug, Subcommand)]
-enum ClaudeCommands {
-    /// Delete the Claude auth volume
+enum AuthVolumeCommands {
+    /// Delete the auth volume
     Reset,
+    /// Save the auth volume to a .tar.gz file
+    Export {
+        /// File to write
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+        /// Image for the temporary container (default: davy-sandbox:latest)
+        #[arg(long = "image", env = "DAVY_IMAGE")]
+        image: Option<String>,
+        /// Encrypt with age, prompting for a passphrase unless --recipient is given
+        #[arg(long = "encrypt", action = ArgAction::SetTrue)]
+        encrypt: bool,
+        /// Encrypt with age to this public key (age or SSH); repeatable, implies --encrypt
+        #[arg(long = "recipient", value_name = "KEY")]
+        recipients: Vec<String>,
+    },
+    /// Restore the auth volume from a .tar.gz file made by export
+    Import {
+        /// File to read
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+        /// Image for the temporary container (default: davy-sandbox:latest)
+        #[arg(long = "image", env = "DAVY_IMAGE")]
+        image: Option<String>,
+        /// age identity file for a backup encrypted to a key
+        #[arg(long = "identity", value_name = "FILE")]
+        identity: Option<PathBuf>,
+        /// Replace an existing auth volume
+        #[arg(long = "force", action = ArgAction::SetTrue)]
+        force: bool,
+    },
+}
+
+#[derive(Debug, Args)]
+struct BuildArgs {
+    /// Project directory used to locate .davy.toml
+    #[arg(short = 'p', long = "project", value_name = "DIR")]
+    project_dir: Option<PathBuf>,
+
+    /// Apply a named profile from the config files
+    #[arg(long = "profile", env = "DAVY_PROFILE", value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Docker image tag (default: davy-sandbox:latest)
+    #[arg(long = "image", env = "DAVY_IMAGE")]
+    image: Option<String>,
+
+    /// Dockerfile to build (defaults to ~/.config/davy/rocky.Dockerfile, then ~/.config/davy/debian.Dockerfile)
+    #[arg(long = "dockerfile", env = "DAVY_DOCKERFILE", value_name = "PATH")]
+    dockerfile: Option<PathBuf>,
+
+    /// Use Dockerfile from current directory instead of ~/.config/davy
+    #[arg(long = "local-dockerfile", action = ArgAction::SetTrue)]
+    local_dockerfile: bool,
+
+    /// Pull the base image and build without cache
+    #[arg(long = "rebuild", action = ArgAction::SetTrue)]
+    rebuild: bool,
+
+    /// Pass the host's HTTP_PROXY, HTTPS_PROXY, and NO_PROXY (both cases) as build args
+    #[arg(long = "proxy", action = ArgAction::SetTrue)]
+    proxy: bool,
+
+    /// Print the docker build command instead of running it
+    #[arg(long = "dry-run", action = ArgAction::SetTrue)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Args)]
+struct LsArgs {
+    /// Include stopped containers
+    #[arg(short = 'a', long = "all", action = ArgAction::SetTrue)]
+    all: bool,
+
+    /// Print a JSON array instead of a table
+    #[arg(long = "json", action = ArgAction::SetTrue)]
+    json: bool,
+}
+
+#[derive(Debug, Args)]
+struct RmArgs {
+    /// Container names
+    #[arg(value_name = "NAME", required = true)]
+    names: Vec<String>,
+
+    /// Stop running containers before removing them
+    #[arg(short = 'f', long = "force", action = ArgAction::SetTrue)]
+    force: bool,
+}
+
+#[derive(Debug, Args)]
+struct ExecArgs {
+    /// Container name (defaults to the newest running container for the project)
+    #[arg(value_name = "NAME", conflicts_with = "project_dir")]
+    name: Option<String>,
+
+    /// Resolve the container by project directory (defaults to the current directory)
+    #[arg(short = 'p', long = "project", value_name = "DIR")]
+    project_dir: Option<PathBuf>,
+
+    #[command(flatten)]
+    tty: TtyArgs,
+
+    /// Command to run inside the container (pass after --; defaults to bash)
+    #[arg(last = true, value_name = "COMMAND")]
+    cmd: Vec<OsString>,
+}
+
+/// Selects running davy containers by name, project, or all of them.
+#[derive(Debug, Args)]
+struct TargetArgs {
+    /// Container names (defaults to the containers for the project)
+    #[arg(value_name = "NAME", conflicts_with_all = ["all", "project_dir"])]
+    names: Vec<String>,
+
+    /// Select every running davy container
+    #[arg(long = "all", action = ArgAction::SetTrue, conflicts_with = "project_dir")]
+    all: bool,
+
+    /// Select containers by project directory (defaults to the current directory)
+    #[arg(short = 'p', long = "project", value_name = "DIR")]
+    project_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct StopArgs {
+    #[command(flatten)]
+    target: TargetArgs,
+
+    /// Seconds to wait before killing the container
+    #[arg(short = 't', long = "time", value_name = "SECONDS")]
+    time: Option<u32>,
+}
+
+#[derive(Debug, Args)]
+struct KillArgs {
+    #[command(flatten)]
+    target: TargetArgs,
+
+    /// Signal to send to the container
+    #[arg(short = 's', long = "signal", value_name = "SIGNAL")]
+    signal: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct AttachArgs {
+    /// Container name (defaults to the newest running container for the project)
+    #[arg(value_name = "NAME", conflicts_with = "project_dir")]
+    name: Option<String>,
+
+    /// Resolve the container by project directory (defaults to the current directory)
+    #[arg(short = 'p', long = "project", value_name = "DIR")]
+    project_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct LogsArgs {
+    /// Container name (defaults to the newest container for the project)
+    #[arg(value_name = "NAME", conflicts_with = "project_dir")]
+    name: Option<String>,
+
+    /// Resolve the container by project directory (defaults to the current directory)
+    #[arg(short = 'p', long = "project", value_name = "DIR")]
+    project_dir: Option<PathBuf>,
+
+    /// Follow log output
+    #[arg(short = 'f', long = "follow", action = ArgAction::SetTrue)]
+    follow: bool,
+
+    /// Number of lines to show from the end of the logs
+    #[arg(long = "tail", value_name = "N")]
+    tail: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct CleanArgs {
+    /// Also remove unused davy volumes (auth volumes are kept; use `davy auth ... reset`)
+    #[arg(long = "volumes", action = ArgAction::SetTrue)]
+    volumes: bool,
+
+    /// Print what would be removed without removing anything
+    #[arg(long = "dry-run", action = ArgAction::SetTrue)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Args)]
+struct VolumesArgs {
+    /// Image for the container that measures volume sizes (default: davy-sandbox:latest)
+    #[arg(long = "image", env = "DAVY_IMAGE", global = true)]
+    image: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<VolumesCommands>,
+}
+
+#[derive(Debug, Subcommand)]
+enum VolumesCommands {
+    /// Remove davy volumes after confirmation
+    Rm {
+        /// Volume names
+        #[arg(value_name = "NAME", required = true)]
+        names: Vec<String>,
+        /// Don't ask for confirmation
+        #[arg(short = 'y', long = "yes", action = ArgAction::SetTrue)]
+        yes: bool,
+    },
+}
+
+#[derive(Debug, Args)]
+struct CacheArgs {
+    /// Image for the container that measures volume sizes (default: davy-sandbox:latest)
+    #[arg(long = "image", env = "DAVY_IMAGE", global = true)]
+    image: Option<String>,
+
+    #[command(subcommand)]
+    command: CacheCommands,
+}
+
+#[derive(Debug, Subcommand)]
+enum CacheCommands {
+    /// List cache volumes with their size and age
+    Ls,
+    /// Remove cache volumes; they are recreated empty on next use
+    Rm {
+        /// Cache names, as given to `--cache` (`cargo` removes every `cargo-*` volume)
+        #[arg(
+            value_name = "NAME",
+            required_unless_present = "all",
+            conflicts_with = "all"
+        )]
+        names: Vec<String>,
+        /// Remove every cache volume
+        #[arg(short = 'a', long = "all", action = ArgAction::SetTrue)]
+        all: bool,
+        /// Don't ask for confirmation
+        #[arg(short = 'y', long = "yes", action = ArgAction::SetTrue)]
+        yes: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum SkillsCommands {
+    /// Copy new or changed skills from a `--skills copy` sandbox to ~/.agents/skills, asking for each
+    Sync(SkillsSyncArgs),
+}
+
+#[derive(Debug, Args)]
+struct SkillsSyncArgs {
+    /// Container name (defaults to the newest container for the project, even if stopped)
+    #[arg(value_name = "NAME", conflicts_with = "project_dir")]
+    name: Option<String>,
+
+    /// Resolve the container by project directory (defaults to the current directory)
+    #[arg(short = 'p', long = "project", value_name = "DIR")]
+    project_dir: Option<PathBuf>,
+
+    /// Copy every changed skill without asking (diffs are still shown)
+    #[arg(short = 'y', long = "yes", action = ArgAction::SetTrue)]
+    yes: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum HomeCommands {
+    /// Delete a persistent home volume so the next run starts from the image's /home/dev
+    Reset {
+        /// Which home to reset, as given to `--persist-home`
+        #[arg(value_name = "user|project", default_value = "user")]
+        scope: HomeScope,
+
+        /// Project whose home to reset (defaults to the current directory)
+        #[arg(short = 'p', long = "project", value_name = "DIR")]
+        project_dir: Option<PathBuf>,
+
+        /// Do not ask for confirmation
+        #[arg(short = 'y', long = "yes", action = ArgAction::SetTrue)]
+        yes: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum DockerfileCommands {
+    /// Write a reference Dockerfile into ~/.config/davy, where davy looks by default
+    Install {
+        /// Which Dockerfile to write
+        #[arg(long = "flavor", value_enum, default_value_t)]
+        flavor: Flavor,
+
+        /// Replace an existing file
+        #[arg(short = 'f', long = "force", action = ArgAction::SetTrue)]
+        force: bool,
+    },
+    /// Print a Dockerfile with only the chosen agent CLIs and toolchains
+    Generate {
+        /// Agent CLIs to install, comma-separated
+        #[arg(
+            long = "agents",
+            value_enum,
+            value_delimiter = ',',
+            value_name = "AGENT,..."
+        )]
+        agents: Vec<Agent>,
+
+        /// Language toolchains to install, comma-separated (node is added for npm agents)
+        #[arg(
+            long = "langs",
+            value_enum,
+            value_delimiter = ',',
+            value_name = "LANG,..."
+        )]
+        langs: Vec<Lang>,
+
+        /// Write to FILE instead of stdout
+        #[arg(short = 'o', long = "output", value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Replace an existing output file
+        #[arg(short = 'f', long = "force", action = ArgAction::SetTrue)]
+        force: bool,
+    },
+}
+
+#[derive(Debug, Args)]
+struct TtyArgs {
+    /// Always allocate a TTY (default: only when stdin and stdout are terminals)
+    #[arg(long = "tty", action = ArgAction::SetTrue, overrides_with = "no_tty")]
+    tty: bool,
+
+    /// Never allocate a TTY
+    #[arg(long = "no-tty", action = ArgAction::SetTrue, overrides_with = "tty")]
+    no_tty: bool,
+}
+
+impl TtyArgs {
+    fn resolve(&self) -> Option<bool> {
+        match (self.tty, self.no_tty) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Args)]
@@ -153,6 +537,141 @@ struct RunArgs {
     #[arg(long = "keep", action = ArgAction::SetTrue)]
     keep: bool,
 
+    #[command(flatten)]
+    tty: TtyArgs,
+
+    /// Start the sandbox in the background and print its name (see `davy attach`)
+    #[arg(short = 'd', long = "detach", action = ArgAction::SetTrue)]
+    detach: bool,
+
+    /// Stop and remove the container if it is still running after DURATION (e.g. 90s, 30m, 2h)
+    #[arg(long = "timeout", value_name = "DURATION", value_parser = humantime::parse_duration)]
+    timeout: Option<Duration>,
+
+    /// Stop the container once it has had no exec/SSH sessions or CPU activity for DURATION, even after davy exits
+    #[arg(long = "idle-timeout", value_name = "DURATION", value_parser = humantime::parse_duration)]
+    idle_timeout: Option<Duration>,
+
+    /// Memory limit (e.g. 512m, 8g)
+    #[arg(long = "memory", value_name = "SIZE")]
+    memory: Option<String>,
+
+    /// CPU limit (e.g. 2, 0.5)
+    #[arg(long = "cpus", value_name = "N")]
+    cpus: Option<f64>,
+
+    /// Maximum number of processes and threads
+    #[arg(long = "pids-limit", value_name = "N")]
+    pids_limit: Option<u32>,
+
+    /// Pass GPUs through: all (the default) or the first N; needs the NVIDIA Container Toolkit
+    #[arg(long = "gpus", value_name = "all|N", num_args = 0..=1, default_missing_value = "all")]
+    gpus: Option<Gpus>,
+
+    /// Pass a host device through (HOST[:CONTAINER[:PERMISSIONS]]), adding its group; repeatable
+    #[arg(long = "device", value_name = "DEVICE")]
+    devices: Vec<String>,
+
+    /// Size of /dev/shm (e.g. 2g; Chromium wants more than the 64m default)
+    #[arg(long = "shm-size", value_name = "SIZE")]
+    shm_size: Option<String>,
+
+    /// Set a ulimit, e.g. nofile=65536 or nofile=1024:65536; repeatable
+    #[arg(long = "ulimit", value_name = "NAME=SOFT[:HARD]")]
+    ulimits: Vec<Ulimit>,
+
+    /// Least privilege: drop all capabilities, forbid privilege escalation (no sudo), and limit pids
+    #[arg(long = "hardened", action = ArgAction::SetTrue)]
+    hardened: bool,
+
+    /// Run fully offline (--network none); incompatible with --expose-ssh, sidecars, and compose networks
+    #[arg(long = "no-network", action = ArgAction::SetTrue)]
+    no_network: bool,
+
+    /// Add an /etc/hosts entry, NAME:IP or NAME:host-gateway (repeatable; host.docker.internal is mapped to the host on Linux)
+    #[arg(long = "add-host", value_name = "NAME:IP")]
+    add_hosts: Vec<HostEntry>,
+
+    /// Publish a container port, [IP:]HOST:CONTAINER[/tcp|udp|sctp] (repeatable; binds 127.0.0.1 unless IP is given)
+    #[arg(long = "publish", value_name = "[IP:]HOST:CONTAINER")]
+    publish: Vec<PortMapping>,
+
+    /// Container hostname (default: davy-<project>)
+    #[arg(long = "hostname", value_name = "NAME", value_parser = network::parse_hostname)]
+    hostname: Option<String>,
+
+    /// DNS server for the sandbox (repeatable)
+    #[arg(long = "dns", value_name = "IP")]
+    dns: Vec<IpAddr>,
+
+    /// DNS search domain (repeatable)
+    #[arg(long = "dns-search", value_name = "DOMAIN", value_parser = network::parse_search_domain)]
+    dns_search: Vec<String>,
+
+    /// Keep internet access but block private networks (10/8, 172.16/12, 192.168/16, ...) and the host gateway
+    #[arg(long = "no-lan", action = ArgAction::SetTrue)]
+    no_lan: bool,
+
+    /// Route all traffic through a filtering proxy sidecar and log every domain contacted (~/.local/state/davy/egress/)
+    #[arg(long = "egress-proxy", action = ArgAction::SetTrue)]
+    egress_proxy: bool,
+
+    /// Let the egress proxy through to DOMAIN and its subdomains (repeatable; implies --egress-proxy)
+    #[arg(long = "allow-domain", value_name = "DOMAIN")]
+    allowed_domains: Vec<String>,
+
+    /// Seccomp profile: a JSON file, davy-restricted (bundled; blocks ptrace, mount, unshare, ...), or unconfined
+    #[arg(long = "seccomp", value_name = "PROFILE")]
+    seccomp: Option<PathBuf>,
+
+    /// AppArmor profile to confine the container with (must be loaded on the docker host)
+    #[arg(long = "apparmor", value_name = "PROFILE")]
+    apparmor: Option<String>,
+
+    /// Apply default resource limits (8g memory, 2 CPUs, 4096 pids) where none are set
+    #[arg(long = "constrained", action = ArgAction::SetTrue)]
+    constrained: bool,
+
+    /// Print the docker build/volume/run commands instead of running them
+    #[arg(long = "dry-run", action = ArgAction::SetTrue)]
+    dry_run: bool,
+
+    /// Exec into a running davy container for the same project instead of starting a new one
+    #[arg(long = "reuse", action = ArgAction::SetTrue)]
+    reuse: bool,
+
+    /// Where to start the sandbox: container (default) or k8s (experimental pod in the current kubeconfig context)
+    #[arg(
+        long = "backend",
+        env = "DAVY_BACKEND",
+        value_enum,
+        default_value_t = Backend::Container,
+        value_name = "BACKEND"
+    )]
+    backend: Backend,
+
+    /// How the project reaches the container: bind mount, or a copy in a volume for remote daemons (default: auto)
+    #[arg(long = "project-mode", value_enum, value_name = "MODE")]
+    project_mode: Option<ProjectMode>,
+
+    /// Use the project's devcontainer.json (default: .devcontainer/devcontainer.json, then .devcontainer.json)
+    #[arg(
+        long = "devcontainer",
+        num_args = 0..=1,
+        default_missing_value = "",
+        value_name = "PATH"
+    )]
+    devcontainer: Option<PathBuf>,
+
+    /// Join the network of a running docker compose stack (default: this project's compose name)
+    #[arg(
+        long = "network-from-compose",
+        num_args = 0..=1,
+        default_missing_value = "",
+        value_name = "PROJECT"
+    )]
+    network_from_compose: Option<String>,
+
     /// Publish host PORT to container port 22 (default: 222)
     #[arg(
         short = 's',
@@ -164,37 +683,254 @@ struct RunArgs {
     )]
     expose_ssh: Option<u16>,
 
+    /// Serve the sandbox's terminal in the browser with ttyd (default: 127.0.0.1:7681; other IPs get a password)
+    #[arg(
+        long = "web-terminal",
+        num_args = 0..=1,
+        default_missing_value = "7681",
+        value_name = "[IP:]PORT"
+    )]
+    web_terminal: Option<WebTerminal>,
+
+    /// Run code-server (installed on first use) and publish it on 127.0.0.1:PORT (default: 8080)
+    #[arg(
+        long = "code-server",
+        num_args = 0..=1,
+        default_missing_value = "8080",
+        value_name = "PORT",
+        value_parser = clap::value_parser!(u16).range(1..)
+    )]
+    code_server: Option<u16>,
+
+    /// Commit as the host's git user.name/user.email and trust /project as a safe.directory (default)
+    #[arg(long = "git-config", action = ArgAction::SetTrue, overrides_with = "no_git_config")]
+    git_config: bool,
+
+    /// Do not pass the host's git identity in
+    #[arg(long = "no-git-config", action = ArgAction::SetTrue, overrides_with = "git_config")]
+    no_git_config: bool,
+
+    /// Forward the host's X11 display (socket and xauth cookie) for GUI tools
+    #[arg(long = "x11", action = ArgAction::SetTrue)]
+    x11: bool,
+
+    /// Forward the host's Wayland socket for native Wayland GUI apps
+    #[arg(long = "wayland", action = ArgAction::SetTrue)]
+    wayland: bool,
+
+    /// Point Ollama clients at the host's Ollama server via host.docker.internal
+    #[arg(long = "ollama", action = ArgAction::SetTrue)]
+    ollama: bool,
+
+    /// Keep bash/zsh history in a per-user volume so it survives across runs
+    #[arg(long = "history", action = ArgAction::SetTrue)]
+    history: bool,
+
+    /// Keep /home/dev in a volume shared by all sandboxes (user) or per project
+    #[arg(
+        long = "persist-home",
+        value_name = "user|project",
+        num_args = 0..=1,
+        default_missing_value = "user"
+    )]
+    persist_home: Option<HomeScope>,
+
+    /// Keep Claude and Codex session history and todos in a volume for this project
+    #[arg(long = "project-state", action = ArgAction::SetTrue)]
+    project_state: bool,
+
+    /// Share Cargo's registry and git checkouts between sandboxes in volumes
+    #[arg(long = "cache-cargo", action = ArgAction::SetTrue)]
+    cache_cargo: bool,
+
+    /// Share the npm cache, pnpm store, and Yarn cache between sandboxes in volumes
+    #[arg(long = "cache-node", action = ArgAction::SetTrue)]
+    cache_node: bool,
+
+    /// Share the pip and uv caches between sandboxes in volumes
+    #[arg(long = "cache-python", action = ArgAction::SetTrue)]
+    cache_python: bool,
+
+    /// Share Go's module and build caches (GOMODCACHE, GOCACHE) between sandboxes in volumes
+    #[arg(long = "cache-go", action = ArgAction::SetTrue)]
+    cache_go: bool,
+
+    /// Share ccache/sccache caches between sandboxes in a volume (sets CCACHE_DIR, SCCACHE_DIR, RUSTC_WRAPPER)
+    #[arg(long = "cache-cc", action = ArgAction::SetTrue)]
+    cache_cc: bool,
+
+    /// Keep a tool's cache in the shared volume NAME, mounted at PATH; repeatable
+    #[arg(long = "cache", value_name = "NAME:PATH")]
+    caches: Vec<CacheMount>,
+
+    /// Open VS Code on /project over SSH once sshd is ready (implies --expose-ssh)
+    #[arg(long = "vscode", action = ArgAction::SetTrue)]
+    vscode: bool,
+
     /// Additional environment variable in KEY=VALUE format (repeatable)
     #[arg(short = 'e', long = "env", value_name = "KEY=VALUE", action = ArgAction::Append)]
     extra_env: Vec<String>,
 
-    /// Forward host environment variable by key name (repeatable)
+    /// Forward host environment variables by name or glob such as 'AWS_*' (repeatable)
     #[arg(long = "pass-env", value_name = "KEY", action = ArgAction::Append)]
     pass_env: Vec<String>,
 
-    /// Mount host Pi auth
-    #[arg(long = "auth-pi", alias = "pi-auth", action = ArgAction::SetTrue)]
-    with_pi_auth: bool,
+    /// Read KEY=VALUE lines from a file, as docker's --env-file does (repeatable)
+    #[arg(long = "env-file", value_name = "PATH", action = ArgAction::Append)]
+    env_files: Vec<PathBuf>,
+
+    /// Set KEY from a host command's stdout at startup, e.g. KEY='op read op://...' (repeatable)
+    #[arg(long = "env-from-cmd", value_name = "KEY=COMMAND", action = ArgAction::Append)]
+    env_commands: Vec<EnvCommand>,
+
+    /// Mount host Pi auth, or keep it in a davy volume with `volume`
+    #[arg(
+        long = "auth-pi",
+        alias = "pi-auth",
+        value_enum,
+        value_name = "STORE",
+        num_args = 0..=1
+    )]
+    pi_auth: Option<Option<AuthStore>>,
 
-    /// Mount host Codex auth
-    #[arg(long = "auth-codex", alias = "codex-auth", action = ArgAction::SetTrue)]
-    with_codex_auth: bool,
+    /// Mount host Codex auth, or keep it in a davy volume with `volume`
+    #[arg(
+        long = "auth-codex",
+        alias = "codex-auth",
+        value_enum,
+        value_name = "STORE",
+        num_args = 0..=1
+    )]
+    codex_auth: Option<Option<AuthStore>>,
 
-    /// Mount host Gemini auth
-    #[arg(long = "auth-gemini", alias = "gemini-auth", action = ArgAction::SetTrue)]
-    with_gemini_auth: bool,
+    /// Mount host Gemini auth, or keep it in a davy volume with `volume`
+    #[arg(
+        long = "auth-gemini",
+        alias = "gemini-auth",
+        value_enum,
+        value_name = "STORE",
+        num_args = 0..=1
+    )]
+    gemini_auth: Option<Option<AuthStore>>,
 
     /// Mount persistent Claude auth volume
     #[arg(long = "auth-claude", alias = "claude-auth", action = ArgAction::SetTrue)]
     with_claude_auth: bool,
 
-    /// Enable all auth mounts (pi, codex, gemini, claude)
+    /// Write this settings.json (permissions, model, hooks) into the sandbox's Claude config at startup
+    #[arg(long = "claude-settings", value_name = "FILE")]
+    claude_settings: Option<PathBuf>,
+
+    /// Apply dotfiles at startup from a git URL or host directory (install script, chezmoi, or links)
+    #[arg(long = "dotfiles", value_name = "URL|DIR")]
+    dotfiles: Option<String>,
+
+    /// How host ~/.agents/skills is mounted: rw (default), ro, or copy (review changes with `davy skills sync`)
+    #[arg(long = "skills", value_enum, value_name = "MODE")]
+    skills: Option<SkillsMode>,
+
+    /// Mount host GitHub CLI auth read-only and forward GH_TOKEN
+    #[arg(long = "auth-gh", alias = "gh-auth", action = ArgAction::SetTrue)]
+    with_gh_auth: bool,
+
+    /// Mount host GitLab CLI auth and forward GITLAB_TOKEN
+    #[arg(long = "auth-glab", alias = "glab-auth", action = ArgAction::SetTrue)]
+    with_glab_auth: bool,
+
+    /// Mount host ~/.aws read-only and forward AWS_PROFILE (or PROFILE) and AWS_REGION
+    #[arg(long = "auth-aws", value_name = "PROFILE", num_args = 0..=1)]
+    aws_auth: Option<Option<String>>,
+
+    /// Mount host gcloud config and application-default credentials
+    #[arg(long = "auth-gcloud", alias = "gcloud-auth", action = ArgAction::SetTrue)]
+    with_gcloud_auth: bool,
+
+    /// Mount host Azure CLI auth (~/.azure)
+    #[arg(long = "auth-azure", alias = "azure-auth", action = ArgAction::SetTrue)]
+    with_azure_auth: bool,
+
+    /// Mount the registry auth lines of host ~/.npmrc for private packages
+    #[arg(long = "auth-npm", alias = "npm-auth", action = ArgAction::SetTrue)]
+    with_npm_auth: bool,
+
+    /// Mount host pip.conf and the ~/.netrc entries of its indexes, and forward PIP_/UV_ index variables
+    #[arg(long = "auth-pip", alias = "pip-auth", action = ArgAction::SetTrue)]
+    with_pip_auth: bool,
+
+    /// Mount host Cargo registry credentials and registry config read-only
+    #[arg(long = "auth-cargo", alias = "cargo-auth", action = ArgAction::SetTrue)]
+    with_cargo_auth: bool,
+
+    /// Mount host ~/.aider.conf.yml read-only and pass AIDER_* and OPENAI_API_KEY through
+    #[arg(long = "auth-aider", alias = "aider-auth", action = ArgAction::SetTrue)]
+    with_aider_auth: bool,
+
+    /// Mount host ~/.config/goose read-write and pass its provider keys through
+    #[arg(long = "auth-goose", alias = "goose-auth", action = ArgAction::SetTrue)]
+    with_goose_auth: bool,
+
+    /// Mount host OpenCode auth, or keep it in a davy volume with `volume`
+    #[arg(
+        long = "auth-opencode",
+        alias = "opencode-auth",
+        value_enum,
+        value_name = "STORE",
+        num_args = 0..=1
+    )]
+    opencode_auth: Option<Option<AuthStore>>,
+
+    /// Enable persistent cursor-agent auth volume mount
+    #[arg(long = "auth-cursor", alias = "cursor-auth", action = ArgAction::SetTrue)]
+    with_cursor_auth: bool,
+
+    /// Mount host ~/.copilot read-write and pass GITHUB_TOKEN and GH_TOKEN through
+    #[arg(long = "auth-copilot", alias = "copilot-auth", action = ArgAction::SetTrue)]
+    with_copilot_auth: bool,
+
+    /// Mount another tool's config like the auth flags do, as SRC:DST[:ro] (repeatable)
+    #[arg(long = "mount-auth", value_name = "SRC:DST[:ro]", action = ArgAction::Append)]
+    auth_mounts: Vec<AuthMount>,
+
+    /// Mount the host kubeconfig read-only, flattened, and limited to CONTEXT when given
+    #[arg(long = "kubeconfig", value_name = "CONTEXT", num_args = 0..=1)]
+    kubeconfig: Option<Option<String>>,
+
+    /// Mount the host sops age key read-only and set SOPS_AGE_KEY_FILE
+    #[arg(long = "sops", action = ArgAction::SetTrue)]
+    sops: bool,
+
+    /// Pass the host's HTTP_PROXY, HTTPS_PROXY, and NO_PROXY (both cases) to the build and sandbox
+    #[arg(long = "proxy", action = ArgAction::SetTrue)]
+    proxy: bool,
+
+    /// Trust an extra PEM CA certificate in the sandbox, e.g. a proxy's (repeatable)
+    #[arg(long = "ca-cert", value_name = "FILE", action = ArgAction::Append)]
+    ca_certs: Vec<PathBuf>,
+
+    /// Keep auth volumes under a named auth profile, e.g. work or personal
+    #[arg(
+        long = "auth-profile",
+        env = "DAVY_AUTH_PROFILE",
+        value_name = "NAME",
+        value_parser = auth::parse_profile
+    )]
+    auth_profile: Option<String>,
+
+    /// Mount host Pi, Codex, and Gemini auth read-only so the sandbox can't change it
+    #[arg(long = "auth-ro", action = ArgAction::SetTrue)]
+    auth_read_only: bool,
+
+    /// Enable all auth mounts (pi, codex, gemini, claude, gh, glab, aws, gcloud, npm, pip, cargo, aider, goose, opencode, cursor, copilot; azure with [auth] all_azure)
     #[arg(short = 'a', long = "auth-all", action = ArgAction::SetTrue)]
     auth_all: bool,
 
-    /// Docker image tag
-    #[arg(long = "image", env = "DAVY_IMAGE", default_value = DEFAULT_IMAGE)]
-    image: String,
+    /// Apply a named profile from the config files
+    #[arg(long = "profile", env = "DAVY_PROFILE", value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Docker image tag (default: davy-sandbox:latest)
+    #[arg(long = "image", env = "DAVY_IMAGE")]
+    image: Option<String>,
 
     /// Dockerfile to build (defaults to ~/.config/davy/rocky.Dockerfile, then ~/.config/davy/debian.Dockerfile)
     #[arg(long = "dockerfile", env = "DAVY_DOCKERFILE", value_name = "PATH")]
@@ -217,708 +953,970 @@ struct RunArgs {
     cmd: Vec<OsString>,
 }
 
-struct RuntimeSettings {
-    project_dir: PathBuf,
-    dockerfile: PathBuf,
-    context_dir: PathBuf,
-    image: String,
-    name: String,
-    host_uid: u32,
-    host_gid: u32,
-    keep: bool,
-    rebuild: bool,
-    no_build: bool,
-    docker_sock: Option<PathBuf>,
-    docker_sock_gid: Option<u32>,
-    expose_ssh: Option<u16>,
-    with_claude_auth: bool,
-    claude_auth_volume: String,
-    extra_docker_args: Vec<OsString>,
-    extra_env_args: Vec<OsString>,
-    cmd: Vec<OsString>,
-}
-
 fn main() {
     if let Err(err) = try_main() {
         eprintln!("davy: {err:#}");
-        std::process::exit(1);
+        let code = err
+            .downcast_ref::<DavyError>()
+            .map_or(1, DavyError::exit_code);
+        std::process::exit(code);
     }
 }
 
 fn try_main() -> Result<()> {
-    let cli = Cli::parse();
-
-    match cli.command {
-        Some(Commands::Auth {
+    let args = env::args_os().collect::<Vec<_>>();
+    reject_volume_shorthand(&args)?;
+    let cli = Cli::parse_from(args);
+    init_logging(cli.verbose);
+    davy::set_quiet(cli.quiet);
+    // Pods need no local container engine.
+    let command = match cli.command.unwrap_or(Commands::Run(Box::new(cli.run))) {
+        Commands::Run(args) if args.backend == Backend::K8s => {
+            davy::set_dry_run(args.dry_run);
+            let status = sandbox_builder(*args).run_pod()?;
+            return exit_with_status(status, "kubectl exec");
+        }
+        Commands::Dockerfile {
+            command: DockerfileCommands::Install { flavor, force },
+        } => {
+            dockerfiles::install(flavor, &dockerfiles::config_dir()?, force)?;
+            return Ok(());
+        }
+        Commands::Dockerfile {
+            command:
+                DockerfileCommands::Generate {
+                    agents,
+                    langs,
+                    output,
+                    force,
+                },
+        } => return generate_dockerfile(&agents, &langs, output, force),
+        command => command,
+    };
+    let mut engine = select_engine(cli.engine.as_deref())?;
+    if cli.docker_api {
+        engine = engine::with_docker_api(engine)?;
+    }
+    let engine = engine.as_ref();
+
+    match command {
+        Commands::Auth {
+            auth_profile,
+            command,
+        } => run_auth_command(engine, command, auth_profile.as_deref()),
+        Commands::Run(args) => run_container(engine, *args),
+        Commands::Build(args) => build_image(engine, args),
+        Commands::Ls(args) => list_containers(engine, args),
+        Commands::Rm(args) => remove_containers(engine, args),
+        Commands::Exec(args) => exec_in_container(engine, args),
+        Commands::Attach(args) => attach_to_container(engine, args),
+        Commands::Stop(args) => stop_containers(engine, args),
+        Commands::Kill(args) => kill_containers(engine, args),
+        Commands::Logs(args) => show_logs(engine, args),
+        Commands::Clean(args) => clean(engine, args),
+        Commands::Volumes(args) => match args.command {
+            None => list_volumes(engine, args.image.as_deref()),
+            Some(VolumesCommands::Rm { names, yes }) => remove_volumes(engine, &names, yes),
+        },
+        Commands::Cache(args) => match args.command {
+            CacheCommands::Ls => list_caches(engine, args.image.as_deref()),
+            CacheCommands::Rm { names, all, yes } => remove_caches(engine, &names, all, yes),
+        },
+        Commands::Skills {
+            command: SkillsCommands::Sync(args),
+        } => sync_skills(engine, args),
+        Commands::Home {
             command:
-                AuthCommands::Claude {
-                    command: ClaudeCommands::Reset,
+                HomeCommands::Reset {
+                    scope,
+                    project_dir,
+                    yes,
                 },
-        }) => reset_claude_auth_volume(),
-        None => run_container(cli.run),
+        } => reset_home(engine, scope, project_dir, yes),
+        Commands::Dockerfile { .. } => unreachable!("handled before selecting an engine"),
     }
 }
 
-fn run_container(args: RunArgs) -> Result<()> {
-    let mut settings = build_runtime_settings(args)?;
+fn init_logging(verbose: u8) {
+    let level = match verbose {
+        0 => return,
+        1 => tracing::Level::DEBUG,
+        _ => tracing::Level::TRACE,
+    };
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_max_level(level)
+        .with_target(false)
+        .without_time()
+        .init();
+}
 
-    maybe_build_image(&settings)?;
+/// Pick the container engine from `--engine`/`DAVY_ENGINE`, then the global
+/// config, then whatever is found on PATH.
+fn select_engine(from_cli: Option<&str>) -> Result<Box<dyn Engine>> {
+    let configured = match from_cli {
+        Some(name) => Some(name.to_owned()),
+        None => config::load_global()?.engine,
+    };
+    engine::select(configured.as_deref())
+}
 
-    if settings.with_claude_auth {
-        ensure_claude_volume_ready(&settings)?;
-    }
+fn run_container(engine: &dyn Engine, args: RunArgs) -> Result<()> {
+    davy::set_dry_run(args.dry_run);
+    let status = sandbox_builder(args).build(engine)?.run(engine)?;
+    exit_with_status(status, &format!("{} run", engine.name()))
+}
 
-    if settings.expose_ssh.is_some() {
-        let ssh_auth_content = collect_ssh_authorized_keys()?;
-        let encoded = STANDARD.encode(ssh_auth_content);
-        push_env(
-            &mut settings.extra_env_args,
-            format!("DAVY_SSH_AUTH_KEYS_B64={encoded}"),
-        );
+fn sandbox_builder(args: RunArgs) -> SandboxBuilder {
+    let mut builder = SandboxBuilder::new()
+        .docker(args.with_docker_sock)
+        .rebuild(args.rebuild)
+        .no_build(args.no_build)
+        .keep(args.keep)
+        .reuse(args.reuse)
+        .detach(args.detach)
+        .constrained(args.constrained)
+        .hardened(args.hardened)
+        .no_network(args.no_network)
+        .no_lan(args.no_lan)
+        .x11(args.x11)
+        .wayland(args.wayland)
+        .ollama(args.ollama)
+        .history(args.history)
+        .project_state(args.project_state)
+        .cache_cargo(args.cache_cargo)
+        .cache_node(args.cache_node)
+        .cache_python(args.cache_python)
+        .cache_go(args.cache_go)
+        .cache_cc(args.cache_cc)
+        .vscode(args.vscode)
+        .egress_proxy(args.egress_proxy)
+        .backend(args.backend)
+        .auth_claude(args.with_claude_auth)
+        .auth_gh(args.with_gh_auth)
+        .auth_glab(args.with_glab_auth)
+        .auth_gcloud(args.with_gcloud_auth)
+        .auth_azure(args.with_azure_auth)
+        .auth_npm(args.with_npm_auth)
+        .auth_pip(args.with_pip_auth)
+        .auth_cargo(args.with_cargo_auth)
+        .auth_aider(args.with_aider_auth)
+        .auth_goose(args.with_goose_auth)
+        .auth_cursor(args.with_cursor_auth)
+        .auth_copilot(args.with_copilot_auth)
+        .sops(args.sops)
+        .proxy(args.proxy)
+        .auth_all(args.auth_all)
+        .auth_read_only(args.auth_read_only)
+        .local_dockerfile(args.local_dockerfile)
+        .docker_args(args.extra_docker_args)
+        .command(args.cmd);
+    if let Some(dir) = args.project_dir {
+        builder = builder.project_dir(dir);
     }
-
-    if settings.cmd.is_empty() {
-        settings.cmd.push(OsString::from("bash"));
+    if let Some(timeout) = args.timeout {
+        builder = builder.timeout(timeout);
     }
-
-    if settings.with_claude_auth {
-        settings.cmd = wrap_bash_script(CLAUDE_LINK_SCRIPT, std::mem::take(&mut settings.cmd));
+    if let Some(timeout) = args.idle_timeout {
+        builder = builder.idle_timeout(timeout);
     }
-    if settings.expose_ssh.is_some() {
-        settings.cmd = wrap_bash_script(SSH_BOOTSTRAP_SCRIPT, std::mem::take(&mut settings.cmd));
+    if let Some(gpus) = args.gpus {
+        builder = builder.gpus(gpus);
     }
-
-    if let Some(docker_sock) = settings.docker_sock.as_ref() {
-        eprintln!(
-            "davy: docker socket mounted from {}. Container can control host Docker.",
-            docker_sock.display()
-        );
-        if let Some(gid) = settings.docker_sock_gid {
-            eprintln!("davy: adding supplementary group {gid} for docker socket access.");
-        }
+    if let Some(path) = args.claude_settings {
+        builder = builder.claude_settings(path);
     }
-    if let Some(port) = settings.expose_ssh {
-        eprintln!("davy: exposing host port {port} to container port 22.");
-        eprintln!("davy: SSH login user is 'dev' (key auth only).");
+    if let Some(mode) = args.skills {
+        builder = builder.skills(mode);
     }
-    if settings.with_claude_auth {
-        eprintln!(
-            "davy: Claude auth volume mounted at /home/dev/.claude-auth ({}).",
-            settings.claude_auth_volume
-        );
-        eprintln!("davy: first use requires running 'claude login' in-container.");
+    if let Some(scope) = args.persist_home {
+        builder = builder.persist_home(scope);
     }
-
-    let status = docker_run(&settings)?;
-    if status.success() {
-        return Ok(());
+    if let Some(source) = args.dotfiles {
+        builder = builder.dotfiles(source);
     }
-
-    match status.code() {
-        Some(code) => std::process::exit(code),
-        None => bail!("docker run terminated by signal"),
+    if let Some(profile) = args.seccomp {
+        builder = builder.seccomp(profile);
     }
-}
-
-fn build_runtime_settings(args: RunArgs) -> Result<RuntimeSettings> {
-    let host_uid = get_current_uid();
-    let host_gid = get_current_gid();
-
-    let project_dir = match args.project_dir {
-        Some(path) => path,
-        None => env::current_dir().context("failed to read current directory")?,
-    };
-    if !project_dir.is_dir() {
-        bail!("project dir not found: {}", project_dir.display());
+    if let Some(profile) = args.apparmor {
+        builder = builder.apparmor(profile);
     }
-
-    let dockerfile = resolve_dockerfile(args.dockerfile, args.local_dockerfile)?;
-    if !dockerfile.is_file() {
-        bail!("Dockerfile not found at: {}", dockerfile.display());
+    if let Some(size) = args.shm_size {
+        builder = builder.shm_size(size);
+    }
+    for ulimit in args.ulimits {
+        builder = builder.ulimit(ulimit);
+    }
+    for cache in args.caches {
+        builder = builder.cache(cache);
+    }
+    for device in args.devices {
+        builder = builder.device(device);
+    }
+    for entry in args.add_hosts {
+        builder = builder.add_host(entry);
+    }
+    if let Some(port) = args.code_server {
+        builder = builder.code_server(port);
+    }
+    if let Some(address) = args.web_terminal {
+        builder = builder.web_terminal(address);
+    }
+    for mapping in args.publish {
+        builder = builder.publish(mapping);
+    }
+    if let Some(hostname) = args.hostname {
+        builder = builder.hostname(hostname);
+    }
+    for server in args.dns {
+        builder = builder.dns(server);
+    }
+    for domain in args.dns_search {
+        builder = builder.dns_search(domain);
+    }
+    for domain in args.allowed_domains {
+        builder = builder.allow_domain(domain);
+    }
+    if let Some(memory) = args.memory {
+        builder = builder.memory(memory);
+    }
+    if let Some(cpus) = args.cpus {
+        builder = builder.cpus(cpus);
+    }
+    if let Some(limit) = args.pids_limit {
+        builder = builder.pids_limit(limit);
+    }
+    if let Some(tty) = args.tty.resolve() {
+        builder = builder.tty(tty);
+    }
+    if args.git_config || args.no_git_config {
+        builder = builder.git_config(args.git_config);
+    }
+    if let Some(name) = args.name {
+        builder = builder.name(name);
+    }
+    if let Some(path) = args.docker_sock {
+        builder = builder.docker_sock(path);
+    }
+    if let Some(mode) = args.project_mode {
+        builder = builder.project_mode(mode);
+    }
+    if let Some(path) = args.devcontainer {
+        builder = builder.devcontainer_file(path);
+    }
+    if let Some(project) = args.network_from_compose {
+        builder = builder.network_from_compose_project(project);
+    }
+    match args.aws_auth {
+        Some(Some(profile)) => builder = builder.aws_profile(profile),
+        Some(None) => builder = builder.auth_aws(true),
+        None => {}
+    }
+    for mount in args.auth_mounts {
+        builder = builder.mount_auth(mount);
+    }
+    match args.codex_auth {
+        Some(Some(store)) => builder = builder.codex_store(store),
+        Some(None) => builder = builder.auth_codex(true),
+        None => {}
+    }
+    match args.gemini_auth {
+        Some(Some(store)) => builder = builder.gemini_store(store),
+        Some(None) => builder = builder.auth_gemini(true),
+        None => {}
+    }
+    match args.pi_auth {
+        Some(Some(store)) => builder = builder.pi_store(store),
+        Some(None) => builder = builder.auth_pi(true),
+        None => {}
+    }
+    match args.opencode_auth {
+        Some(Some(store)) => builder = builder.opencode_store(store),
+        Some(None) => builder = builder.auth_opencode(true),
+        None => {}
+    }
+    match args.kubeconfig {
+        Some(Some(context)) => builder = builder.kube_context(context),
+        Some(None) => builder = builder.kubeconfig(true),
+        None => {}
+    }
+    if let Some(port) = args.expose_ssh {
+        builder = builder.expose_ssh(port);
     }
-
-    let context_dir = dockerfile
-        .parent()
-        .map(Path::to_path_buf)
-        .unwrap_or_else(|| PathBuf::from("."));
-
-    let with_pi_auth = args.with_pi_auth || args.auth_all;
-    let with_codex_auth = args.with_codex_auth || args.auth_all;
-    let with_gemini_auth = args.with_gemini_auth || args.auth_all;
-    let with_claude_auth = args.with_claude_auth || args.auth_all;
-    let allow_missing_auth = args.auth_all;
-
-    let claude_auth_volume = env::var("DAVY_CLAUDE_AUTH_VOLUME")
-        .unwrap_or_else(|_| format!("davy-claude-auth-{host_uid}-v1"));
-
-    let home = home_dir()?;
-
-    let mut extra_env_args = Vec::new();
     for kv in args.extra_env {
-        push_env(&mut extra_env_args, kv);
+        builder = builder.env(kv);
     }
     for key in args.pass_env {
-        let value = env::var(&key).unwrap_or_default();
-        push_env(&mut extra_env_args, format!("{key}={value}"));
-    }
-
-    let mut extra_docker_args = args.extra_docker_args;
-    if with_pi_auth {
-        add_bind_mount(
-            &mut extra_docker_args,
-            &home.join(".pi/agent"),
-            "/home/dev/.pi/agent",
-            "Pi auth",
-            allow_missing_auth,
-        )?;
-    }
-    if with_codex_auth {
-        if add_bind_mount(
-            &mut extra_docker_args,
-            &home.join(".codex"),
-            "/home/dev/.codex",
-            "Codex auth",
-            allow_missing_auth,
-        )? {
-            push_env(
-                &mut extra_env_args,
-                "CODEX_HOME=/home/dev/.codex".to_owned(),
-            );
-        }
+        builder = builder.pass_env(key);
     }
-    if with_gemini_auth {
-        add_bind_mount(
-            &mut extra_docker_args,
-            &home.join(".gemini"),
-            "/home/dev/.gemini",
-            "Gemini auth",
-            allow_missing_auth,
-        )?;
-    }
-    if !add_bind_mount(
-        &mut extra_docker_args,
-        &home.join(".agents/skills"),
-        "/home/dev/.agents/skills",
-        "agents skills",
-        true,
-    )? {
-        eprintln!("davy: warning: continuing without host skills mount.");
-    }
-    add_file_bind_mount(
-        &mut extra_docker_args,
-        &home.join(".config/git/ignore"),
-        "/home/dev/.config/git/ignore",
-        "global gitignore",
-        true,
-        true,
-    )?;
-    add_file_bind_mount(
-        &mut extra_docker_args,
-        &home.join(".gitconfig"),
-        "/home/dev/.gitconfig",
-        "global gitconfig",
-        true,
-        true,
-    )?;
-
-    let docker_sock = if args.with_docker_sock {
-        Some(resolve_docker_socket_path(args.docker_sock)?)
-    } else {
-        None
-    };
-    let docker_sock_gid = docker_sock_gid(docker_sock.as_deref())?;
-
-    let name = args
-        .name
-        .unwrap_or_else(|| default_container_name(&project_dir));
-
-    Ok(RuntimeSettings {
-        project_dir,
-        dockerfile,
-        context_dir,
-        image: args.image,
-        name,
-        host_uid,
-        host_gid,
-        keep: args.keep,
-        rebuild: args.rebuild,
-        no_build: args.no_build,
-        docker_sock,
-        docker_sock_gid,
-        expose_ssh: args.expose_ssh,
-        with_claude_auth,
-        claude_auth_volume,
-        extra_docker_args,
-        extra_env_args,
-        cmd: args.cmd,
-    })
-}
-
-fn resolve_dockerfile(from_cli: Option<PathBuf>, local: bool) -> Result<PathBuf> {
-    if let Some(path) = from_cli {
-        return Ok(path);
-    }
-
-    if local {
-        let cwd = env::current_dir().context("failed to read current directory")?;
-        let rocky = cwd.join("rocky.Dockerfile");
-        if rocky.is_file() {
-            return Ok(rocky);
-        }
-        let debian = cwd.join("debian.Dockerfile");
-        if debian.is_file() {
-            return Ok(debian);
-        }
-        bail!(
-            "no Dockerfile found in current directory (looked for {} and {})",
-            rocky.display(),
-            debian.display()
-        );
+    for path in args.env_files {
+        builder = builder.env_file(path);
     }
-
-    let config_dir = home_dir()?.join(".config/davy");
-    let rocky = config_dir.join("rocky.Dockerfile");
-    if rocky.is_file() {
-        return Ok(rocky);
+    for command in args.env_commands {
+        builder = builder.env_from_cmd(command);
     }
-    let debian = config_dir.join("debian.Dockerfile");
-    if debian.is_file() {
-        return Ok(debian);
+    for path in args.ca_certs {
+        builder = builder.ca_cert(path);
     }
-
-    bail!(
-        "no Dockerfile found (looked for {} and {}); use --dockerfile, --local-dockerfile, or DAVY_DOCKERFILE",
-        rocky.display(),
-        debian.display()
-    );
+    if let Some(profile) = args.profile {
+        builder = builder.profile(profile);
+    }
+    if let Some(profile) = args.auth_profile {
+        builder = builder.auth_profile(profile);
+    }
+    if let Some(image) = args.image {
+        builder = builder.image(image);
+    }
+    if let Some(path) = args.dockerfile {
+        builder = builder.dockerfile(path);
+    }
+    builder
 }
 
-fn default_container_name(project_dir: &Path) -> String {
-    let base = project_dir
-        .file_name()
-        .map(|s| s.to_string_lossy().into_owned())
-        .filter(|s| !s.is_empty())
-        .unwrap_or_else(|| "project".to_owned());
-
-    let timestamp = Local::now().format("%Y%m%d-%H%M%S");
-    format!("davy-{base}-{timestamp}")
+fn exec_in_container(engine: &dyn Engine, args: ExecArgs) -> Result<()> {
+    let name = resolve_container(engine, args.name, args.project_dir, false)?;
+    let status = sandbox::docker_exec(engine, &name, &args.cmd, args.tty.resolve())?;
+    exit_with_status(status, &format!("{} exec", engine.name()))
 }
 
-fn maybe_build_image(settings: &RuntimeSettings) -> Result<()> {
-    if settings.no_build {
-        if docker_image_exists(&settings.image)? {
-            return Ok(());
-        }
-        bail!(
-            "image '{}' not found (and --no-build was set)",
-            settings.image
-        );
-    }
-
-    if settings.rebuild {
-        return docker_build(settings, true, true);
+fn stop_containers(engine: &dyn Engine, args: StopArgs) -> Result<()> {
+    let names = resolve_targets(engine, args.target)?;
+    if names.is_empty() {
+        note!("no running davy containers to stop.");
+        return Ok(());
     }
 
-    if !docker_image_exists(&settings.image)? {
-        return docker_build(settings, false, false);
+    let mut cmd = engine.command();
+    cmd.arg("stop");
+    if let Some(time) = args.time {
+        cmd.arg("--time").arg(time.to_string());
     }
-
-    Ok(())
+    cmd.args(&names);
+    run_checked(&mut cmd, &format!("{} stop", engine.name()))
 }
 
-fn docker_build(settings: &RuntimeSettings, pull: bool, no_cache: bool) -> Result<()> {
-    let mut cmd = Command::new("docker");
-    cmd.arg("build");
-    if pull {
-        cmd.arg("--pull");
-    }
-    if no_cache {
-        cmd.arg("--no-cache");
+fn kill_containers(engine: &dyn Engine, args: KillArgs) -> Result<()> {
+    let names = resolve_targets(engine, args.target)?;
+    if names.is_empty() {
+        note!("no running davy containers to kill.");
+        return Ok(());
     }
 
-    cmd.arg("--build-arg")
-        .arg(format!("USER_UID={}", settings.host_uid))
-        .arg("--build-arg")
-        .arg(format!("USER_GID={}", settings.host_gid))
-        .arg("-f")
-        .arg(&settings.dockerfile)
-        .arg("-t")
-        .arg(&settings.image)
-        .arg(&settings.context_dir);
-
-    run_checked(&mut cmd, "docker build")
+    let mut cmd = engine.command();
+    cmd.arg("kill");
+    if let Some(signal) = args.signal.as_deref() {
+        cmd.arg("--signal").arg(signal);
+    }
+    cmd.args(&names);
+    run_checked(&mut cmd, &format!("{} kill", engine.name()))
 }
 
-fn docker_image_exists(image: &str) -> Result<bool> {
-    let status = Command::new("docker")
-        .arg("image")
-        .arg("inspect")
-        .arg(image)
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
+fn attach_to_container(engine: &dyn Engine, args: AttachArgs) -> Result<()> {
+    let name = resolve_container(engine, args.name, args.project_dir, false)?;
+    note!("attaching to '{name}'; detach with Ctrl-P Ctrl-Q.");
+
+    let status = engine
+        .command()
+        .arg("attach")
+        .arg(&name)
         .status()
-        .context("failed to run docker image inspect")?;
-
-    Ok(status.success())
-}
-
-fn ensure_claude_volume_ready(settings: &RuntimeSettings) -> Result<()> {
-    let mut create_volume = Command::new("docker");
-    create_volume
-        .arg("volume")
-        .arg("create")
-        .arg(&settings.claude_auth_volume);
-    run_checked(&mut create_volume, "docker volume create")?;
-
-    let mut init_volume = Command::new("docker");
-    init_volume
-        .arg("run")
-        .arg("--rm")
-        .arg("--user")
-        .arg("0:0")
-        .arg("-v")
-        .arg(format!("{}:/auth", settings.claude_auth_volume))
-        .arg(&settings.image)
-        .arg("bash")
-        .arg("-lc")
-        .arg(format!(
-            "mkdir -p /auth/.claude && touch /auth/.claude.json && chown -R {}:{} /auth",
-            settings.host_uid, settings.host_gid
-        ));
-    run_checked(
-        &mut init_volume,
-        "docker run (initialize Claude auth volume)",
-    )
+        .with_context(|| format!("failed to run {} attach", engine.name()))?;
+    exit_with_status(status, &format!("{} attach", engine.name()))
 }
 
-fn docker_run(settings: &RuntimeSettings) -> Result<ExitStatus> {
-    let mut cmd = Command::new("docker");
-    cmd.arg("run").arg("-it");
+fn show_logs(engine: &dyn Engine, args: LogsArgs) -> Result<()> {
+    let name = resolve_container(engine, args.name, args.project_dir, true)?;
 
-    if !settings.keep {
-        cmd.arg("--rm");
+    let mut cmd = engine.command();
+    cmd.arg("logs");
+    if args.follow {
+        cmd.arg("--follow");
+    }
+    if let Some(tail) = args.tail.as_deref() {
+        cmd.arg("--tail").arg(tail);
     }
+    cmd.arg(&name);
 
-    cmd.arg("--name")
-        .arg(&settings.name)
-        .arg("-v")
-        .arg(format!("{}:/project", settings.project_dir.display()))
-        .arg("-w")
-        .arg("/project");
+    let status = cmd
+        .status()
+        .with_context(|| format!("failed to run {} logs", engine.name()))?;
+    exit_with_status(status, &format!("{} logs", engine.name()))
+}
 
-    if settings.with_claude_auth {
-        cmd.arg("--mount").arg(format!(
-            "type=volume,src={},dst=/home/dev/.claude-auth",
-            settings.claude_auth_volume
-        ));
-    }
+fn clean(engine: &dyn Engine, args: CleanArgs) -> Result<()> {
+    let (stopped, remaining): (Vec<_>, Vec<_>) = containers::query(engine, true)?
+        .into_iter()
+        .partition(|container| container.is_stopped());
+    let stopped = stopped
+        .into_iter()
+        .map(|container| container.name)
+        .collect::<Vec<_>>();
+    let remaining = remaining
+        .into_iter()
+        .map(|container| container.name)
+        .collect::<Vec<_>>();
+    // Sidecars of the stopped containers removed here go with them.
+    let (sidecars, networks) = sidecars::orphans(engine, &remaining)?;
+    let private_files = davy::private_files_owners()?
+        .into_iter()
+        .filter(|owner| !remaining.contains(owner))
+        .collect::<Vec<_>>();
+    let images = containers::dangling_images(engine)?;
+    let volumes = if args.volumes {
+        containers::unused_volumes(engine)?
+    } else {
+        Vec::new()
+    };
 
-    if let Some(docker_sock) = settings.docker_sock.as_ref() {
-        cmd.arg("-v")
-            .arg(format!("{}:/var/run/docker.sock", docker_sock.display()));
-        if let Some(gid) = settings.docker_sock_gid {
-            cmd.arg("--group-add").arg(gid.to_string());
+    let groups = [
+        ("container", vec!["rm"], stopped),
+        ("sidecar", vec!["rm", "--force"], sidecars),
+        ("network", vec!["network", "rm"], networks),
+        ("image", vec!["image", "rm"], images),
+        ("volume", vec!["volume", "rm"], volumes),
+    ];
+    let mut removed_any = false;
+    for (kind, subcommand, items) in groups {
+        if items.is_empty() {
+            continue;
+        }
+        removed_any = true;
+        for item in &items {
+            if args.dry_run {
+                eprintln!("davy: would remove {kind} {item}");
+            } else {
+                note!("removing {kind} {item}");
+            }
+        }
+        if !args.dry_run {
+            let name = format!("{} {}", engine.name(), subcommand.join(" "));
+            let mut cmd = engine.command();
+            cmd.args(subcommand).args(&items).stdout(Stdio::null());
+            run_checked(&mut cmd, &name)?;
         }
     }
 
-    if let Some(port) = settings.expose_ssh {
-        cmd.arg("-p").arg(format!("{port}:22"));
+    for owner in &private_files {
+        removed_any = true;
+        if args.dry_run {
+            eprintln!("davy: would remove credentials generated for {owner}");
+        } else {
+            note!("removing credentials generated for {owner}");
+            davy::remove_private_files(owner)?;
+        }
     }
 
-    cmd.args(&settings.extra_env_args)
-        .args(&settings.extra_docker_args)
-        .arg(&settings.image)
-        .args(&settings.cmd);
+    if !removed_any {
+        note!("nothing to clean.");
+    }
+    Ok(())
+}
 
-    cmd.status().context("failed to run docker run")
+fn list_volumes(engine: &dyn Engine, image: Option<&str>) -> Result<()> {
+    let volumes = containers::volumes(engine)?;
+    print_volumes(engine, image, &volumes)
 }
 
-fn wrap_bash_script(script: &str, original_cmd: Vec<OsString>) -> Vec<OsString> {
-    let mut wrapped = vec![
-        OsString::from("bash"),
-        OsString::from("-lc"),
-        OsString::from(script),
-        OsString::from("--"),
-    ];
-    wrapped.extend(original_cmd);
-    wrapped
+fn list_caches(engine: &dyn Engine, image: Option<&str>) -> Result<()> {
+    let volumes = cache_volumes(engine)?;
+    print_volumes(engine, image, &volumes)
 }
 
-fn collect_ssh_authorized_keys() -> Result<String> {
-    let mut unique = HashSet::new();
-    let mut keys = Vec::new();
+fn print_volumes(
+    engine: &dyn Engine,
+    image: Option<&str>,
+    volumes: &[containers::VolumeInfo],
+) -> Result<()> {
+    let names = volumes
+        .iter()
+        .map(|volume| volume.name.as_str())
+        .collect::<Vec<_>>();
+    let image = image.unwrap_or(sandbox::DEFAULT_IMAGE);
+    let sizes = containers::volume_sizes(engine, image, &names).unwrap_or_else(|err| {
+        eprintln!("davy: warning: could not measure volumes: {err:#}");
+        vec![None; names.len()]
+    });
+    let rows = volumes
+        .iter()
+        .zip(sizes)
+        .map(|(volume, size)| {
+            vec![
+                volume.name.clone(),
+                volume.feature(),
+                size.map_or_else(|| "-".to_owned(), containers::format_size),
+                volume.created.map_or_else(
+                    || "-".to_owned(),
+                    |created| created.format("%Y-%m-%d %H:%M").to_string(),
+                ),
+            ]
+        })
+        .collect::<Vec<_>>();
+    print!(
+        "{}",
+        containers::render_columns(&["VOLUME", "FEATURE", "SIZE", "CREATED"], &rows)
+    );
+    Ok(())
+}
 
-    if let Ok(path) = env::var("DAVY_SSH_AUTHORIZED_KEYS_FILE") {
-        let key_path = PathBuf::from(&path);
-        if !key_path.is_file() {
-            bail!("DAVY_SSH_AUTHORIZED_KEYS_FILE not found: {path}");
-        }
-        collect_key_lines_from_file(&key_path, &mut unique, &mut keys)?;
-    } else {
-        let ssh_dir = home_dir()?.join(".ssh");
-        let authorized_keys = ssh_dir.join("authorized_keys");
-        if authorized_keys.is_file() {
-            collect_key_lines_from_file(&authorized_keys, &mut unique, &mut keys)?;
+fn remove_volumes(engine: &dyn Engine, names: &[String], yes: bool) -> Result<()> {
+    let volumes = containers::volumes(engine)?;
+    let mut selected = Vec::new();
+    for name in names {
+        match volumes.iter().find(|volume| &volume.name == name) {
+            Some(volume) => selected.push(volume),
+            None => bail!("'{name}' is not a davy volume"),
         }
+    }
+    confirm_and_remove_volumes(engine, selected, yes)
+}
+
+/// The current user's shared cache volumes.
+fn cache_volumes(engine: &dyn Engine) -> Result<Vec<containers::VolumeInfo>> {
+    let uid = get_current_uid();
+    let mut volumes = containers::volumes(engine)?;
+    volumes.retain(|volume| caches::cache_name(uid, &volume.name).is_some());
+    Ok(volumes)
+}
 
-        if ssh_dir.is_dir() {
-            let mut pubs = fs::read_dir(&ssh_dir)
-                .with_context(|| format!("failed to read {}", ssh_dir.display()))?
-                .filter_map(|entry| entry.ok().map(|e| e.path()))
-                .filter(|path| path.extension().is_some_and(|ext| ext == "pub"))
-                .collect::<Vec<_>>();
-            pubs.sort();
-
-            for path in pubs {
-                if path.is_file() {
-                    collect_key_lines_from_file(&path, &mut unique, &mut keys)?;
+fn remove_caches(engine: &dyn Engine, names: &[String], all: bool, yes: bool) -> Result<()> {
+    let uid = get_current_uid();
+    let volumes = cache_volumes(engine)?;
+    let selected = if all {
+        volumes.iter().collect::<Vec<_>>()
+    } else {
+        let mut selected = Vec::new();
+        for name in names {
+            let before = selected.len();
+            for volume in &volumes {
+                let cache = caches::cache_name(uid, &volume.name).unwrap_or_default();
+                let matches = cache == name
+                    || cache
+                        .strip_prefix(name.as_str())
+                        .is_some_and(|rest| rest.starts_with('-'));
+                if matches && !selected.contains(&volume) {
+                    selected.push(volume);
                 }
             }
+            if selected.len() == before {
+                bail!("no cache volume named '{name}'");
+            }
         }
+        selected
+    };
+    if selected.is_empty() {
+        note!("no cache volumes to remove");
+        return Ok(());
     }
-
-    if keys.is_empty() {
-        bail!("no SSH public keys found. Add ~/.ssh/*.pub or set DAVY_SSH_AUTHORIZED_KEYS_FILE");
-    }
-
-    Ok(format!("{}\n", keys.join("\n")))
+    confirm_and_remove_volumes(engine, selected, yes)
 }
 
-fn collect_key_lines_from_file(
-    path: &Path,
-    unique: &mut HashSet<String>,
-    output: &mut Vec<String>,
+fn confirm_and_remove_volumes(
+    engine: &dyn Engine,
+    selected: Vec<&containers::VolumeInfo>,
+    yes: bool,
 ) -> Result<()> {
-    let content = fs::read_to_string(path)
-        .with_context(|| format!("failed to read SSH keys from {}", path.display()))?;
-
-    for line in content
-        .lines()
-        .map(str::trim)
-        .filter(|line| !line.is_empty())
-    {
-        if unique.insert(line.to_owned()) {
-            output.push(line.to_owned());
+    if !yes {
+        if !std::io::stdin().is_terminal() {
+            bail!("refusing to remove volumes without a terminal to confirm; pass --yes");
+        }
+        for volume in &selected {
+            eprintln!("  {}  ({})", volume.name, volume.feature());
+        }
+        if selected.iter().any(|volume| volume.is_auth()) {
+            eprintln!("davy: warning: removing auth volumes logs their agents out");
+        }
+        if !confirm(&format!("Remove {} volume(s)?", selected.len()))? {
+            note!("nothing removed.");
+            return Ok(());
         }
     }
 
+    for volume in selected {
+        engine.remove_volume(&volume.name)?;
+        note!("removed volume '{}'", volume.name);
+    }
     Ok(())
 }
 
-fn reset_claude_auth_volume() -> Result<()> {
-    let uid = get_current_uid();
-    let volume = env::var("DAVY_CLAUDE_AUTH_VOLUME")
-        .unwrap_or_else(|_| format!("davy-claude-auth-{uid}-v1"));
-
-    let exists = Command::new("docker")
-        .arg("volume")
-        .arg("inspect")
-        .arg(&volume)
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .status()
-        .context("failed to run docker volume inspect")?
-        .success();
-
-    if exists {
-        let mut remove_volume = Command::new("docker");
-        remove_volume.arg("volume").arg("rm").arg("-f").arg(&volume);
-        run_checked(&mut remove_volume, "docker volume rm")?;
-        eprintln!("davy: removed Claude auth volume '{volume}'");
-    } else {
-        eprintln!("davy: Claude auth volume '{volume}' does not exist");
+fn reset_home(
+    engine: &dyn Engine,
+    scope: HomeScope,
+    project_dir: Option<PathBuf>,
+    yes: bool,
+) -> Result<()> {
+    let project_dir = sandbox::resolve_project_dir(project_dir)?;
+    let volume = home::volume_name(get_current_uid(), scope, &project_dir);
+    if !engine.volume_exists(&volume)? {
+        note!("home volume '{volume}' does not exist");
+        return Ok(());
     }
 
+    if !yes {
+        if !std::io::stdin().is_terminal() {
+            bail!("refusing to reset the home volume without a terminal to confirm; pass --yes");
+        }
+        if !confirm(&format!(
+            "Delete home volume '{volume}' and everything installed in it?"
+        ))? {
+            note!("nothing removed.");
+            return Ok(());
+        }
+    }
+    engine.remove_volume(&volume)?;
+    note!("removed home volume '{volume}'");
     Ok(())
 }
 
-fn run_checked(cmd: &mut Command, name: &str) -> Result<()> {
-    let status = cmd
-        .status()
-        .with_context(|| format!("failed to run {name}"))?;
-    if status.success() {
-        return Ok(());
+fn sync_skills(engine: &dyn Engine, args: SkillsSyncArgs) -> Result<()> {
+    let container = resolve_container(engine, args.name, args.project_dir, true)?;
+    if !args.yes && !std::io::stdin().is_terminal() {
+        bail!("refusing to copy skills without a terminal to review them; pass --yes");
     }
+    let host = davy::home_dir()?.join(".agents/skills");
+    let staging = std::env::temp_dir().join(format!("davy-skills-{}", std::process::id()));
+    std::fs::create_dir_all(&staging)
+        .with_context(|| format!("failed to create {}", staging.display()))?;
+    let result = (|| {
+        skills::copy_out(engine, &container, &staging)?;
+        let changes = skills::changed_skills(&host, &staging)?;
+        if changes.is_empty() {
+            note!("skills in '{container}' match ~/.agents/skills.");
+        }
+        for change in changes {
+            let what = if change.new { "new" } else { "changed" };
+            eprintln!("davy: {what} skill '{}':", change.name);
+            skills::show_diff(&host, &staging, &change.name);
+            if args.yes || confirm(&format!("Copy '{}' to ~/.agents/skills?", change.name))? {
+                skills::install(&host, &staging, &change.name)?;
+                note!("copied skill '{}'.", change.name);
+            }
+        }
+        Ok(())
+    })();
+    let _ = std::fs::remove_dir_all(&staging);
+    result
+}
 
-    match status.code() {
-        Some(code) => bail!("{name} exited with status code {code}"),
-        None => bail!("{name} terminated by signal"),
+fn generate_dockerfile(
+    agents: &[Agent],
+    langs: &[Lang],
+    output: Option<PathBuf>,
+    force: bool,
+) -> Result<()> {
+    let dockerfile = dockerfiles::generate(agents, langs);
+    let Some(path) = output else {
+        print!("{dockerfile}");
+        return Ok(());
+    };
+    if path.exists() && !force {
+        bail!(
+            "{} already exists; pass --force to replace it",
+            path.display()
+        );
     }
+    std::fs::write(&path, dockerfile)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    note!(
+        "wrote {}; build it with `davy --dockerfile {0}`.",
+        path.display()
+    );
+    Ok(())
 }
 
-fn home_dir() -> Result<PathBuf> {
-    if let Some(home) = env::var_os("HOME") {
-        return Ok(PathBuf::from(home));
-    }
+/// Ask a yes/no question on the terminal; anything but yes is no.
+fn confirm(question: &str) -> Result<bool> {
+    eprint!("{question} [y/N] ");
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .context("failed to read confirmation")?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
 
-    #[cfg(unix)]
-    {
-        return get_user_by_uid(get_current_uid())
-            .map(|user| user.home_dir().to_path_buf())
-            .context("HOME is not set and current user home directory could not be resolved");
+/// Validate an explicit container name, or pick the newest container for the
+/// project. Stopped containers are only considered when `all` is set.
+fn resolve_container(
+    engine: &dyn Engine,
+    name: Option<String>,
+    project_dir: Option<PathBuf>,
+    all: bool,
+) -> Result<String> {
+    if let Some(name) = name {
+        if !containers::is_davy_container(engine, &name)? {
+            return Err(DavyError::NotDavyContainer { name }.into());
+        }
+        return Ok(name);
     }
 
-    #[cfg(not(unix))]
-    {
-        bail!("HOME is not set");
+    let project_dir = sandbox::resolve_project_dir(project_dir)?;
+    let container = containers::find_for_project(engine, &project_dir, all)?;
+    match container {
+        Some(container) => Ok(container.name),
+        None => Err(DavyError::NoProjectContainer {
+            project: project_dir,
+            running: !all,
+        }
+        .into()),
     }
 }
 
-fn add_bind_mount(
-    args: &mut Vec<OsString>,
-    source: &Path,
-    target: &str,
-    label: &str,
-    allow_missing: bool,
-) -> Result<bool> {
-    if source.is_dir() {
-        push_volume(args, format!("{}:{target}", source.display()));
-        return Ok(true);
+fn resolve_targets(engine: &dyn Engine, target: TargetArgs) -> Result<Vec<String>> {
+    if !target.names.is_empty() {
+        for name in &target.names {
+            if !containers::is_davy_container(engine, name)? {
+                return Err(DavyError::NotDavyContainer { name: name.clone() }.into());
+            }
+        }
+        return Ok(target.names);
     }
 
-    if source.exists() {
-        bail!(
-            "{label} mount source is not a directory: {}",
-            source.display()
-        );
-    }
+    let containers = if target.all {
+        containers::query(engine, false)?
+    } else {
+        containers::for_project(
+            engine,
+            &sandbox::resolve_project_dir(target.project_dir)?,
+            false,
+        )?
+    };
+    Ok(containers
+        .into_iter()
+        .map(|container| container.name)
+        .collect())
+}
 
-    if allow_missing {
-        eprintln!(
-            "davy: warning: {label} mount source not found at {}; skipping.",
-            source.display()
-        );
-        return Ok(false);
+/// Mirror a child's exit status as davy's own; a signal N becomes 128+N.
+fn exit_with_status(status: ExitStatus, name: &str) -> Result<()> {
+    if status.success() {
+        return Ok(());
     }
 
-    bail!("{label} mount source not found: {}", source.display());
+    if status.code().is_none() {
+        note!("{name} terminated by signal.");
+    }
+    std::process::exit(davy::exit_code(status))
 }
 
-fn add_file_bind_mount(
-    args: &mut Vec<OsString>,
-    source: &Path,
-    target: &str,
-    label: &str,
-    read_only: bool,
-    allow_missing: bool,
-) -> Result<bool> {
-    if source.is_file() {
-        let suffix = if read_only { ":ro" } else { "" };
-        push_volume(args, format!("{}:{target}{suffix}", source.display()));
-        return Ok(true);
+fn build_image(engine: &dyn Engine, args: BuildArgs) -> Result<()> {
+    davy::set_dry_run(args.dry_run);
+    let project_dir = sandbox::resolve_project_dir(args.project_dir)?;
+    let config = sandbox::load_config(&project_dir, args.profile.as_deref())?;
+    let mut settings = sandbox::build_settings(
+        args.image.or(config.image),
+        args.dockerfile.or(config.dockerfile),
+        args.local_dockerfile,
+    )?;
+    if args.proxy || config.proxy.unwrap_or(false) {
+        settings.forward_proxy();
     }
+    sandbox::docker_build(engine, &settings, args.rebuild, args.rebuild)
+}
 
-    if source.exists() {
-        bail!("{label} mount source is not a file: {}", source.display());
+fn list_containers(engine: &dyn Engine, args: LsArgs) -> Result<()> {
+    let containers = containers::query(engine, args.all)?;
+    if args.json {
+        let json = containers::to_json(&containers, Local::now());
+        println!("{}", serde_json::to_string_pretty(&json)?);
+    } else {
+        print!("{}", containers::render_table(&containers, Local::now()));
     }
+    Ok(())
+}
 
-    if allow_missing {
-        return Ok(false);
+fn remove_containers(engine: &dyn Engine, args: RmArgs) -> Result<()> {
+    for name in &args.names {
+        if !containers::is_davy_container(engine, name)? {
+            return Err(DavyError::NotDavyContainer { name: name.clone() }.into());
+        }
     }
 
-    bail!("{label} mount source not found: {}", source.display());
+    let mut cmd = engine.command();
+    cmd.arg("rm");
+    if args.force {
+        cmd.arg("--force");
+    }
+    cmd.args(&args.names);
+    run_checked(&mut cmd, &format!("{} rm", engine.name()))?;
+    if !davy::dry_run() {
+        for name in &args.names {
+            davy::remove_private_files(name)?;
+        }
+    }
+    Ok(())
 }
 
-fn resolve_docker_socket_path(from_cli: Option<PathBuf>) -> Result<PathBuf> {
-    let socket = if let Some(path) = from_cli {
-        path
-    } else if let Some(path) = env::var("DOCKER_HOST")
-        .ok()
-        .as_deref()
-        .and_then(parse_unix_socket_from_docker_host)
-    {
-        path
-    } else if let Ok(host) = env::var("DOCKER_HOST") {
-        bail!(
-            "DOCKER_HOST is set to '{host}', but --docker needs a local unix socket. Set --docker-sock or DAVY_DOCKER_SOCK."
-        );
-    } else {
-        PathBuf::from("/var/run/docker.sock")
+fn run_auth_command(
+    engine: &dyn Engine,
+    command: AuthCommands,
+    profile: Option<&str>,
+) -> Result<()> {
+    let uid = get_current_uid();
+    let (label, kind, volume, command) = match command {
+        AuthCommands::List => return list_auth_volumes(engine),
+        AuthCommands::Status { image } => {
+            let image = image.unwrap_or_else(|| sandbox::DEFAULT_IMAGE.to_owned());
+            return auth_status(engine, &image, profile);
+        }
+        AuthCommands::Claude { command } => (
+            "Claude",
+            "claude-auth".to_owned(),
+            sandbox::claude_auth_volume(uid, profile),
+            command,
+        ),
+        AuthCommands::Pi { command } => agent_volume(&auth::PI, uid, profile, command),
+        AuthCommands::Codex { command } => agent_volume(&auth::CODEX, uid, profile, command),
+        AuthCommands::Gemini { command } => agent_volume(&auth::GEMINI, uid, profile, command),
+        AuthCommands::Opencode { command } => agent_volume(&auth::OPENCODE, uid, profile, command),
+        AuthCommands::Cursor { command } => agent_volume(&auth::CURSOR, uid, profile, command),
     };
-
-    let metadata = fs::metadata(&socket)
-        .with_context(|| format!("docker socket not found: {}", socket.display()))?;
-    #[cfg(unix)]
-    {
-        if !metadata.file_type().is_socket() {
-            bail!(
-                "docker socket path is not a unix socket: {}",
-                socket.display()
+    match command {
+        AuthVolumeCommands::Reset => remove_auth_volume(engine, label, &volume),
+        AuthVolumeCommands::Export {
+            file,
+            image,
+            encrypt,
+            recipients,
+        } => {
+            let image = image.unwrap_or_else(|| sandbox::DEFAULT_IMAGE.to_owned());
+            let encryption = if !recipients.is_empty() {
+                Some(auth::Encryption::Recipients(recipients))
+            } else {
+                encrypt.then_some(auth::Encryption::Passphrase)
+            };
+            auth::export_volume(engine, &image, &volume, &file, encryption.as_ref())?;
+            note!(
+                "exported {label} auth volume '{volume}' to {}",
+                file.display()
             );
+            Ok(())
+        }
+        AuthVolumeCommands::Import {
+            file,
+            image,
+            identity,
+            force,
+        } => {
+            let image = image.unwrap_or_else(|| sandbox::DEFAULT_IMAGE.to_owned());
+            auth::import_volume(
+                engine,
+                &image,
+                &volume,
+                &containers::auth_volume_labels(&kind, profile),
+                &file,
+                identity.as_deref(),
+                force,
+            )?;
+            note!(
+                "imported {} into {label} auth volume '{volume}'",
+                file.display()
+            );
+            Ok(())
         }
     }
-    #[cfg(not(unix))]
-    {
-        let _ = metadata;
-    }
-
-    Ok(socket)
 }
 
-fn parse_unix_socket_from_docker_host(docker_host: &str) -> Option<PathBuf> {
-    docker_host
-        .strip_prefix("unix://")
-        .filter(|path| !path.is_empty())
-        .map(PathBuf::from)
+fn agent_volume(
+    agent: &'static auth::AuthVolume,
+    uid: u32,
+    profile: Option<&str>,
+    command: AuthVolumeCommands,
+) -> (&'static str, String, String, AuthVolumeCommands) {
+    (
+        agent.label,
+        agent.kind(),
+        agent.volume_name(uid, profile),
+        command,
+    )
 }
 
-fn docker_sock_gid(path: Option<&Path>) -> Result<Option<u32>> {
-    let Some(path) = path else {
-        return Ok(None);
-    };
+fn list_auth_volumes(engine: &dyn Engine) -> Result<()> {
+    let rows = containers::auth_volumes(engine)?
+        .into_iter()
+        .map(|volume| {
+            vec![
+                volume.profile.unwrap_or_else(|| "default".to_owned()),
+                volume.agent,
+                volume.name,
+            ]
+        })
+        .collect::<Vec<_>>();
+    print!(
+        "{}",
+        containers::render_columns(&["PROFILE", "AGENT", "VOLUME"], &rows)
+    );
+    Ok(())
+}
 
-    #[cfg(unix)]
-    {
-        let metadata = fs::metadata(path).with_context(|| {
-            format!(
-                "failed to read metadata for docker socket at {}",
-                path.display()
-            )
-        })?;
-        Ok(Some(metadata.gid()))
-    }
+fn auth_status(engine: &dyn Engine, image: &str, profile: Option<&str>) -> Result<()> {
+    let home = davy::home_dir()?;
+    let now = Local::now();
+    let rows = auth::status(engine, image, profile, &home)?
+        .into_iter()
+        .map(|status| {
+            let host = match status.host {
+                Some((path, true)) => match path.strip_prefix(&home) {
+                    Ok(rest) => format!("~/{}", rest.display()),
+                    Err(_) => path.display().to_string(),
+                },
+                Some((_, false)) => "missing".to_owned(),
+                None => "-".to_owned(),
+            };
+            vec![
+                status.agent.to_owned(),
+                host,
+                status.volume.unwrap_or_else(|| "-".to_owned()),
+                status.last_used.map_or_else(
+                    || "-".to_owned(),
+                    |time| time.format("%Y-%m-%d %H:%M").to_string(),
+                ),
+                status
+                    .token
+                    .map_or_else(|| "-".to_owned(), |token| token.describe(now)),
+            ]
+        })
+        .collect::<Vec<_>>();
+    print!(
+        "{}",
+        containers::render_columns(&["AGENT", "HOST", "VOLUME", "LAST USED", "TOKEN"], &rows)
+    );
+    Ok(())
+}
 
-    #[cfg(not(unix))]
-    {
-        let _ = path;
-        Ok(None)
+fn remove_auth_volume(engine: &dyn Engine, label: &str, volume: &str) -> Result<()> {
+    if engine.volume_exists(volume)? {
+        engine.remove_volume(volume)?;
+        note!("removed {label} auth volume '{volume}'");
+    } else {
+        note!("{label} auth volume '{volume}' does not exist");
     }
-}
 
-fn push_env(args: &mut Vec<OsString>, value: impl Into<OsString>) {
-    args.push(OsString::from("-e"));
-    args.push(value.into());
+    Ok(())
 }
 
-fn push_volume(args: &mut Vec<OsString>, volume: impl Into<OsString>) {
-    args.push(OsString::from("-v"));
-    args.push(volume.into());
+/// `-v` used to pass through to `docker run` as `-v SRC:DST`; it is now
+/// `--verbose`, so refuse the old form rather than hand docker a stray
+/// `SRC:DST`.
+fn reject_volume_shorthand(args: &[OsString]) -> Result<()> {
+    let args = args
+        .iter()
+        .map(|arg| arg.to_string_lossy())
+        .take_while(|arg| arg != "--")
+        .collect::<Vec<_>>();
+    for pair in args.windows(2) {
+        if pair[0] == "-v" && !pair[1].starts_with('-') && pair[1].contains(':') {
+            bail!(
+                "-v is short for --verbose; use --volume {} to mount a volume",
+                pair[1]
+            );
+        }
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn default_name_has_prefix() {
-        let name = default_container_name(Path::new("/tmp/my-project"));
-        assert!(name.starts_with("davy-my-project-"));
-        assert_eq!(name.len(), "davy-my-project-YYYYMMDD-HHMMSS".len());
-    }
-
-    #[test]
-    fn wrap_script_prefixes_command() {
-        let wrapped = wrap_bash_script("echo hi", vec![OsString::from("bash")]);
-        let expected = vec![
-            OsString::from("bash"),
-            OsString::from("-lc"),
-            OsString::from("echo hi"),
-            OsString::from("--"),
-            OsString::from("bash"),
-        ];
-        assert_eq!(wrapped, expected);
-    }
-
     #[test]
     fn clap_parses_extra_docker_args_and_command() {
         let cli = Cli::try_parse_from([
@@ -943,6 +1941,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn rejects_v_as_a_volume_flag() {
+        let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+        let err = reject_volume_shorthand(&args(&["davy", "-v", "/a:/b"])).unwrap_err();
+        assert!(err.to_string().contains("use --volume /a:/b"));
+        assert!(reject_volume_shorthand(&args(&["davy", "-v", "--volume", "/a:/b"])).is_ok());
+        assert!(reject_volume_shorthand(&args(&["davy", "-v", "--", "echo", "a:b"])).is_ok());
+        assert!(reject_volume_shorthand(&args(&["davy", "--", "cmd", "-v", "a:b"])).is_ok());
+    }
+
     #[test]
     fn clap_expose_ssh_defaults_to_222() {
         let cli = Cli::try_parse_from(["davy", "--expose-ssh"]).expect("CLI should parse");
@@ -950,35 +1958,407 @@ mod tests {
     }
 
     #[test]
-    fn clap_parses_passthrough_docker_args_without_command() {
-        let cli = Cli::try_parse_from(["davy", "--privileged", "--network", "host"])
+    fn clap_parses_web_terminal() {
+        let cli = Cli::try_parse_from(["davy", "--web-terminal"]).expect("CLI should parse");
+        assert_eq!(cli.run.web_terminal.map(|t| t.port), Some(7681));
+        let cli = Cli::try_parse_from(["davy", "--web-terminal", "0.0.0.0:9000", "--", "claude"])
             .expect("CLI should parse");
         assert_eq!(
-            cli.run.extra_docker_args,
-            vec![
-                OsString::from("--privileged"),
-                OsString::from("--network"),
-                OsString::from("host")
-            ]
+            cli.run.web_terminal.map(|t| t.to_string()).as_deref(),
+            Some("0.0.0.0:9000")
         );
-        assert!(cli.run.cmd.is_empty());
+        assert_eq!(cli.run.cmd, vec![OsString::from("claude")]);
     }
 
     #[test]
-    fn clap_parses_auth_claude_reset_subcommand() {
-        let cli =
-            Cli::try_parse_from(["davy", "auth", "claude", "reset"]).expect("CLI should parse");
+    fn clap_parses_code_server() {
+        let cli = Cli::try_parse_from(["davy", "--code-server"]).expect("CLI should parse");
+        assert_eq!(cli.run.code_server, Some(8080));
+        let cli = Cli::try_parse_from(["davy", "--code-server", "9443"]).expect("CLI should parse");
+        assert_eq!(cli.run.code_server, Some(9443));
+    }
 
-        assert!(matches!(
-            cli.command,
-            Some(Commands::Auth {
-                command: AuthCommands::Claude {
-                    command: ClaudeCommands::Reset
-                }
+    #[test]
+    fn clap_parses_git_config_flags() {
+        let cli = Cli::try_parse_from(["davy", "--no-git-config"]).expect("CLI should parse");
+        assert!(cli.run.no_git_config);
+        let cli = Cli::try_parse_from(["davy", "--no-git-config", "--git-config"])
+            .expect("CLI should parse");
+        assert!(cli.run.git_config);
+        assert!(!cli.run.no_git_config);
+    }
+
+    #[test]
+    fn clap_parses_display_flags() {
+        let cli = Cli::try_parse_from(["davy", "--x11"]).expect("CLI should parse");
+        assert!(cli.run.x11);
+        assert!(!cli.run.wayland);
+        let cli = Cli::try_parse_from(["davy", "--wayland"]).expect("CLI should parse");
+        assert!(cli.run.wayland);
+    }
+
+    #[test]
+    fn clap_parses_vscode_flag() {
+        let cli =
+            Cli::try_parse_from(["davy", "--vscode", "-s", "2200"]).expect("CLI should parse");
+        assert!(cli.run.vscode);
+        assert_eq!(cli.run.expose_ssh, Some(2200));
+    }
+
+    #[test]
+    fn clap_parses_passthrough_docker_args_without_command() {
+        let cli = Cli::try_parse_from(["davy", "--privileged", "--network", "host"])
+            .expect("CLI should parse");
+        assert_eq!(
+            cli.run.extra_docker_args,
+            vec![
+                OsString::from("--privileged"),
+                OsString::from("--network"),
+                OsString::from("host")
+            ]
+        );
+        assert!(cli.run.cmd.is_empty());
+    }
+
+    #[test]
+    fn clap_parses_auth_claude_reset_subcommand() {
+        let cli =
+            Cli::try_parse_from(["davy", "auth", "claude", "reset"]).expect("CLI should parse");
+
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Auth {
+                command: AuthCommands::Claude {
+                    command: AuthVolumeCommands::Reset
+                },
+                ..
             })
         ));
     }
 
+    #[test]
+    fn clap_parses_auth_export_and_import() {
+        let cli = Cli::try_parse_from(["davy", "auth", "pi", "export", "pi.tar.gz"])
+            .expect("CLI should parse");
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Auth {
+                command: AuthCommands::Pi {
+                    command: AuthVolumeCommands::Export {
+                        ref file,
+                        image: None,
+                        encrypt: false,
+                        ..
+                    }
+                },
+                ..
+            }) if file == &PathBuf::from("pi.tar.gz")
+        ));
+
+        let cli = Cli::try_parse_from(["davy", "auth", "claude", "import", "c.tar.gz", "--force"])
+            .expect("CLI should parse");
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Auth {
+                command: AuthCommands::Claude {
+                    command: AuthVolumeCommands::Import { force: true, .. }
+                },
+                ..
+            })
+        ));
+
+        let cli = Cli::try_parse_from([
+            "davy",
+            "auth",
+            "codex",
+            "export",
+            "codex.tar.gz.age",
+            "--recipient",
+            "age1abc",
+            "--recipient",
+            "age1def",
+        ])
+        .expect("CLI should parse");
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Auth {
+                command: AuthCommands::Codex {
+                    command: AuthVolumeCommands::Export { ref recipients, .. }
+                },
+                ..
+            }) if recipients == &["age1abc", "age1def"]
+        ));
+    }
+
+    #[test]
+    fn clap_parses_volumes_subcommands() {
+        let cli = Cli::try_parse_from(["davy", "volumes"]).expect("CLI should parse");
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Volumes(VolumesArgs { command: None, .. }))
+        ));
+
+        let cli = Cli::try_parse_from(["davy", "volumes", "rm", "-y", "davy-a", "davy-b"])
+            .expect("CLI should parse");
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Volumes(VolumesArgs {
+                command: Some(VolumesCommands::Rm { ref names, yes: true }),
+                ..
+            })) if names == &["davy-a", "davy-b"]
+        ));
+        assert!(Cli::try_parse_from(["davy", "volumes", "rm"]).is_err());
+    }
+
+    #[test]
+    fn clap_parses_skills_mode_and_sync() {
+        let cli = Cli::try_parse_from(["davy", "--skills", "copy", "--dotfiles", "~/dotfiles"])
+            .expect("CLI should parse");
+        assert_eq!(cli.run.skills, Some(SkillsMode::Copy));
+        assert_eq!(cli.run.dotfiles.as_deref(), Some("~/dotfiles"));
+        assert!(Cli::try_parse_from(["davy", "--skills", "maybe"]).is_err());
+
+        let cli = Cli::try_parse_from(["davy", "skills", "sync", "-p", "/work/app", "--yes"])
+            .expect("CLI should parse");
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Skills {
+                command: SkillsCommands::Sync(SkillsSyncArgs {
+                    name: None,
+                    project_dir: Some(_),
+                    yes: true,
+                }),
+            })
+        ));
+    }
+
+    #[test]
+    fn clap_parses_auth_profiles() {
+        let cli = Cli::try_parse_from(["davy", "auth", "codex", "reset", "--auth-profile", "work"])
+            .expect("CLI should parse");
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Auth {
+                auth_profile: Some(ref profile),
+                command: AuthCommands::Codex { .. },
+            }) if profile == "work"
+        ));
+        let cli = Cli::try_parse_from(["davy", "auth", "list"]).expect("CLI should parse");
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Auth {
+                command: AuthCommands::List,
+                ..
+            })
+        ));
+        let cli = Cli::try_parse_from(["davy", "--auth-profile", "personal", "--auth-claude"])
+            .expect("CLI should parse");
+        assert_eq!(cli.run.auth_profile.as_deref(), Some("personal"));
+        assert!(Cli::try_parse_from(["davy", "--auth-profile", "../x"]).is_err());
+    }
+
+    #[test]
+    fn clap_parses_tool_auth_flags() {
+        let cli = Cli::try_parse_from([
+            "davy",
+            "--auth-gh",
+            "--auth-glab",
+            "--auth-gcloud",
+            "--auth-azure",
+            "--auth-npm",
+            "--auth-pip",
+            "--auth-cargo",
+            "--auth-aider",
+            "--auth-goose",
+            "--auth-cursor",
+            "--auth-copilot",
+        ])
+        .expect("CLI should parse");
+        assert!(cli.run.with_gh_auth);
+        assert!(cli.run.with_glab_auth);
+        assert!(cli.run.with_gcloud_auth);
+        assert!(cli.run.with_azure_auth);
+        assert!(cli.run.with_npm_auth);
+        assert!(cli.run.with_pip_auth);
+        assert!(cli.run.with_cargo_auth);
+        assert!(cli.run.with_aider_auth);
+        assert!(cli.run.with_goose_auth);
+        assert!(cli.run.with_cursor_auth);
+        assert!(cli.run.with_copilot_auth);
+        let cli = Cli::try_parse_from([
+            "davy",
+            "--mount-auth",
+            "/etc/foo:/home/dev/.config/foo:ro",
+            "--mount-auth",
+            "/etc/bar:/home/dev/.bar",
+        ])
+        .expect("CLI should parse");
+        assert_eq!(cli.run.auth_mounts.len(), 2);
+        assert!(Cli::try_parse_from(["davy", "--mount-auth", "/etc/foo"]).is_err());
+        assert_eq!(cli.run.aws_auth, None);
+        let cli = Cli::try_parse_from(["davy", "--auth-aws"]).expect("CLI should parse");
+        assert_eq!(cli.run.aws_auth, Some(None));
+        let cli = Cli::try_parse_from(["davy", "--auth-aws", "prod", "--", "aws", "s3", "ls"])
+            .expect("CLI should parse");
+        assert_eq!(cli.run.aws_auth, Some(Some("prod".to_owned())));
+        assert_eq!(cli.run.cmd, ["aws", "s3", "ls"]);
+        let cli =
+            Cli::try_parse_from(["davy", "--kubeconfig", "kind-dev"]).expect("CLI should parse");
+        assert_eq!(cli.run.kubeconfig, Some(Some("kind-dev".to_owned())));
+        let cli = Cli::try_parse_from(["davy", "--sops", "--", "make", "test"])
+            .expect("CLI should parse");
+        assert!(cli.run.sops);
+        let cli = Cli::try_parse_from(["davy", "build", "--proxy"]).expect("CLI should parse");
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Build(BuildArgs { proxy: true, .. }))
+        ));
+        let cli = Cli::try_parse_from(["davy", "--ca-cert", "corp.pem", "--ca-cert", "proxy.crt"])
+            .expect("CLI should parse");
+        assert_eq!(
+            cli.run.ca_certs,
+            [PathBuf::from("corp.pem"), PathBuf::from("proxy.crt")]
+        );
+        let cli = Cli::try_parse_from(["davy", "--auth-opencode"]).expect("CLI should parse");
+        assert_eq!(cli.run.opencode_auth, Some(None));
+        let cli = Cli::try_parse_from(["davy", "--auth-opencode", "volume", "--", "opencode"])
+            .expect("CLI should parse");
+        assert_eq!(cli.run.opencode_auth, Some(Some(AuthStore::Volume)));
+        assert_eq!(cli.run.cmd, ["opencode"]);
+        let cli = Cli::try_parse_from(["davy", "--auth-codex=volume"]).expect("CLI should parse");
+        assert_eq!(cli.run.codex_auth, Some(Some(AuthStore::Volume)));
+        let cli = Cli::try_parse_from(["davy", "--auth-gemini", "--auth-codex"])
+            .expect("CLI should parse");
+        assert_eq!(cli.run.gemini_auth, Some(None));
+        assert_eq!(cli.run.codex_auth, Some(None));
+        let cli = Cli::try_parse_from(["davy", "--auth-pi", "volume"]).expect("CLI should parse");
+        assert_eq!(cli.run.pi_auth, Some(Some(AuthStore::Volume)));
+        assert!(!cli.run.auth_read_only);
+        let cli =
+            Cli::try_parse_from(["davy", "--auth-codex", "--auth-ro"]).expect("CLI should parse");
+        assert_eq!(cli.run.codex_auth, Some(None));
+        assert!(cli.run.auth_read_only);
+        let cli =
+            Cli::try_parse_from(["davy", "--claude-settings", "claude.json", "--auth-claude"])
+                .expect("CLI should parse");
+        assert_eq!(cli.run.claude_settings, Some(PathBuf::from("claude.json")));
+    }
+
+    #[test]
+    fn clap_parses_run_subcommand() {
+        let cli = Cli::try_parse_from([
+            "davy",
+            "run",
+            "--keep",
+            "--reuse",
+            "--privileged",
+            "--",
+            "npm",
+            "test",
+        ])
+        .expect("CLI should parse");
+        let Some(Commands::Run(args)) = cli.command else {
+            panic!("expected run subcommand");
+        };
+        assert!(args.keep);
+        assert!(args.reuse);
+        assert_eq!(args.extra_docker_args, vec![OsString::from("--privileged")]);
+        assert_eq!(
+            args.cmd,
+            vec![OsString::from("npm"), OsString::from("test")]
+        );
+    }
+
+    #[test]
+    fn clap_parses_build_ls_and_rm_subcommands() {
+        let cli = Cli::try_parse_from(["davy", "build", "--rebuild"]).expect("CLI should parse");
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Build(BuildArgs { rebuild: true, .. }))
+        ));
+
+        let cli = Cli::try_parse_from(["davy", "ls", "-a"]).expect("CLI should parse");
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Ls(LsArgs {
+                all: true,
+                json: false
+            }))
+        ));
+
+        let cli = Cli::try_parse_from(["davy", "ls", "--json"]).expect("CLI should parse");
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Ls(LsArgs { json: true, .. }))
+        ));
+
+        let cli = Cli::try_parse_from(["davy", "rm", "-f", "a", "b"]).expect("CLI should parse");
+        let Some(Commands::Rm(args)) = cli.command else {
+            panic!("expected rm subcommand");
+        };
+        assert!(args.force);
+        assert_eq!(args.names, vec!["a".to_owned(), "b".to_owned()]);
+    }
+
+    #[test]
+    fn clap_parses_exec_subcommand() {
+        let cli = Cli::try_parse_from(["davy", "exec", "davy-app", "--", "ls", "-la"])
+            .expect("CLI should parse");
+        let Some(Commands::Exec(args)) = cli.command else {
+            panic!("expected exec subcommand");
+        };
+        assert_eq!(args.name.as_deref(), Some("davy-app"));
+        assert_eq!(args.cmd, vec![OsString::from("ls"), OsString::from("-la")]);
+
+        assert!(Cli::try_parse_from(["davy", "exec", "davy-app", "-p", "/tmp"]).is_err());
+    }
+
+    #[test]
+    fn clap_parses_stop_and_kill_targets() {
+        let cli =
+            Cli::try_parse_from(["davy", "stop", "--all", "-t", "3"]).expect("CLI should parse");
+        let Some(Commands::Stop(args)) = cli.command else {
+            panic!("expected stop subcommand");
+        };
+        assert!(args.target.all);
+        assert_eq!(args.time, Some(3));
+
+        let cli = Cli::try_parse_from(["davy", "kill", "-p", "/tmp", "-s", "TERM"])
+            .expect("CLI should parse");
+        let Some(Commands::Kill(args)) = cli.command else {
+            panic!("expected kill subcommand");
+        };
+        assert_eq!(args.target.project_dir, Some(PathBuf::from("/tmp")));
+        assert_eq!(args.signal.as_deref(), Some("TERM"));
+
+        assert!(Cli::try_parse_from(["davy", "stop", "name", "--all"]).is_err());
+    }
+
+    #[test]
+    fn clap_parses_logs_subcommand() {
+        let cli =
+            Cli::try_parse_from(["davy", "logs", "-f", "--tail", "50"]).expect("CLI should parse");
+        let Some(Commands::Logs(args)) = cli.command else {
+            panic!("expected logs subcommand");
+        };
+        assert!(args.follow);
+        assert_eq!(args.name, None);
+        assert_eq!(args.tail.as_deref(), Some("50"));
+    }
+
+    #[test]
+    fn clap_parses_clean_subcommand() {
+        let cli = Cli::try_parse_from(["davy", "clean", "--volumes", "--dry-run"])
+            .expect("CLI should parse");
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Clean(CleanArgs {
+                volumes: true,
+                dry_run: true
+            }))
+        ));
+    }
+
     #[test]
     fn clap_parses_docker_sock_path() {
         let cli = Cli::try_parse_from(["davy", "--docker", "--docker-sock", "/tmp/docker.sock"])
@@ -988,22 +2368,387 @@ mod tests {
     }
 
     #[test]
-    fn parse_unix_docker_host_extracts_socket_path() {
-        let socket = parse_unix_socket_from_docker_host("unix:///run/user/1000/docker.sock");
-        assert_eq!(socket, Some(PathBuf::from("/run/user/1000/docker.sock")));
+    fn clap_parses_profile_flag() {
+        let cli = Cli::try_parse_from(["davy", "--profile", "work"]).expect("CLI should parse");
+        assert_eq!(cli.run.profile.as_deref(), Some("work"));
+    }
+
+    #[test]
+    fn clap_parses_backend() {
+        let cli =
+            Cli::try_parse_from(["davy", "run", "--backend", "k8s"]).expect("CLI should parse");
+        let Some(Commands::Run(args)) = cli.command else {
+            panic!("expected run subcommand");
+        };
+        assert_eq!(args.backend, Backend::K8s);
+        let cli = Cli::try_parse_from(["davy"]).expect("CLI should parse");
+        assert_eq!(cli.run.backend, Backend::Container);
+    }
+
+    #[test]
+    fn clap_parses_network_from_compose() {
+        let cli =
+            Cli::try_parse_from(["davy", "--network-from-compose"]).expect("CLI should parse");
+        assert_eq!(cli.run.network_from_compose.as_deref(), Some(""));
+        let cli = Cli::try_parse_from(["davy", "--network-from-compose", "shop"])
+            .expect("CLI should parse");
+        assert_eq!(cli.run.network_from_compose.as_deref(), Some("shop"));
+    }
+
+    #[test]
+    fn clap_parses_project_mode() {
+        let cli =
+            Cli::try_parse_from(["davy", "--project-mode", "volume"]).expect("CLI should parse");
+        assert_eq!(cli.run.project_mode, Some(ProjectMode::Volume));
+        assert!(Cli::try_parse_from(["davy", "--project-mode", "rsync"]).is_err());
+    }
+
+    #[test]
+    fn clap_counts_verbose_flags() {
+        let cli =
+            Cli::try_parse_from(["davy", "-vv", "--volume", "/a:/b"]).expect("CLI should parse");
+        assert_eq!(cli.verbose, 2);
+        assert_eq!(
+            cli.run.extra_docker_args,
+            vec![OsString::from("--volume"), OsString::from("/a:/b")]
+        );
+        let cli = Cli::try_parse_from(["davy", "ls", "-v"]).expect("CLI should parse");
+        assert_eq!(cli.verbose, 1);
+        let cli = Cli::try_parse_from(["davy", "build", "-q"]).expect("CLI should parse");
+        assert!(cli.quiet);
+    }
+
+    #[test]
+    fn clap_parses_tty_overrides() {
+        let cli = Cli::try_parse_from(["davy"]).expect("CLI should parse");
+        assert_eq!(cli.run.tty.resolve(), None);
+        let cli = Cli::try_parse_from(["davy", "--no-tty"]).expect("CLI should parse");
+        assert_eq!(cli.run.tty.resolve(), Some(false));
+        let cli = Cli::try_parse_from(["davy", "--no-tty", "--tty"]).expect("CLI should parse");
+        assert_eq!(cli.run.tty.resolve(), Some(true));
+    }
+
+    #[test]
+    fn clap_parses_detach_and_attach() {
+        let cli = Cli::try_parse_from(["davy", "-d", "-s"]).expect("CLI should parse");
+        assert!(cli.run.detach);
+        assert_eq!(cli.run.expose_ssh, Some(222));
+        let cli = Cli::try_parse_from(["davy", "attach", "davy-app-1"]).expect("CLI should parse");
+        let Some(Commands::Attach(args)) = cli.command else {
+            panic!("expected attach subcommand");
+        };
+        assert_eq!(args.name.as_deref(), Some("davy-app-1"));
+    }
+
+    #[test]
+    fn clap_parses_timeout_durations() {
+        let cli = Cli::try_parse_from(["davy", "--timeout", "1h30m"]).expect("CLI should parse");
+        assert_eq!(cli.run.timeout, Some(Duration::from_secs(90 * 60)));
+        assert!(Cli::try_parse_from(["davy", "--timeout", "soon"]).is_err());
+        let cli = Cli::try_parse_from(["davy", "--keep", "--idle-timeout", "2h"])
+            .expect("CLI should parse");
+        assert_eq!(cli.run.idle_timeout, Some(Duration::from_secs(2 * 3600)));
+    }
+
+    #[test]
+    fn clap_parses_resource_limits() {
+        let cli = Cli::try_parse_from([
+            "davy",
+            "--constrained",
+            "--hardened",
+            "--no-network",
+            "--no-lan",
+            "--memory",
+            "4g",
+            "--cpus",
+            "0.5",
+            "--pids-limit",
+            "512",
+        ])
+        .expect("CLI should parse");
+        assert!(cli.run.constrained);
+        assert!(cli.run.hardened);
+        assert!(cli.run.no_network);
+        assert!(cli.run.no_lan);
+        assert_eq!(cli.run.memory.as_deref(), Some("4g"));
+        assert_eq!(cli.run.cpus, Some(0.5));
+        assert_eq!(cli.run.pids_limit, Some(512));
+        assert!(Cli::try_parse_from(["davy", "--cpus", "two"]).is_err());
+    }
+
+    #[test]
+    fn clap_parses_gpus() {
+        let cli =
+            Cli::try_parse_from(["davy", "--gpus", "--", "nvidia-smi"]).expect("CLI should parse");
+        assert_eq!(cli.run.gpus, Some(Gpus::All));
+        assert_eq!(cli.run.cmd, vec![OsString::from("nvidia-smi")]);
+        let cli = Cli::try_parse_from(["davy", "--gpus", "2"]).expect("CLI should parse");
+        assert_eq!(cli.run.gpus, Some(Gpus::Count(2)));
+        assert!(Cli::try_parse_from(["davy", "--gpus", "0"]).is_err());
     }
 
     #[test]
-    fn parse_non_unix_docker_host_returns_none() {
+    fn clap_parses_repeated_devices() {
+        let cli = Cli::try_parse_from([
+            "davy",
+            "--device",
+            "/dev/ttyUSB0",
+            "--device",
+            "/dev/bus/usb/001/004:/dev/probe",
+        ])
+        .expect("CLI should parse");
         assert_eq!(
-            parse_unix_socket_from_docker_host("tcp://127.0.0.1:2375"),
-            None
+            cli.run.devices,
+            vec!["/dev/ttyUSB0", "/dev/bus/usb/001/004:/dev/probe"]
         );
     }
 
+    #[test]
+    fn clap_parses_shm_size_and_ulimits() {
+        let cli = Cli::try_parse_from([
+            "davy",
+            "--shm-size",
+            "2g",
+            "--ulimit",
+            "nofile=65536",
+            "--ulimit",
+            "nproc=1024:2048",
+        ])
+        .expect("CLI should parse");
+        assert_eq!(cli.run.shm_size.as_deref(), Some("2g"));
+        assert_eq!(cli.run.ulimits.len(), 2);
+        assert_eq!(cli.run.ulimits[1].hard, 2048);
+        assert!(Cli::try_parse_from(["davy", "--ulimit", "files=10"]).is_err());
+    }
+
+    #[test]
+    fn clap_parses_security_profiles() {
+        let cli = Cli::try_parse_from([
+            "davy",
+            "--seccomp",
+            "davy-restricted",
+            "--apparmor",
+            "davy-agent",
+        ])
+        .expect("CLI should parse");
+        assert_eq!(cli.run.seccomp, Some(PathBuf::from("davy-restricted")));
+        assert_eq!(cli.run.apparmor.as_deref(), Some("davy-agent"));
+    }
+
+    #[test]
+    fn clap_parses_add_host() {
+        let cli = Cli::try_parse_from([
+            "davy",
+            "--add-host",
+            "llm.local:10.0.0.5",
+            "--add-host",
+            "api:host-gateway",
+        ])
+        .expect("CLI should parse");
+        assert_eq!(cli.run.add_hosts.len(), 2);
+        assert_eq!(cli.run.add_hosts[0].to_string(), "llm.local:10.0.0.5");
+        assert!(Cli::try_parse_from(["davy", "--add-host", "llm.local:10.0.0"]).is_err());
+    }
+
+    #[test]
+    fn clap_parses_publish() {
+        let cli = Cli::try_parse_from([
+            "davy",
+            "--publish",
+            "3000:3000",
+            "--publish",
+            "0.0.0.0:8000:8000/tcp",
+        ])
+        .expect("CLI should parse");
+        assert_eq!(cli.run.publish.len(), 2);
+        assert_eq!(cli.run.publish[0].to_string(), "127.0.0.1:3000:3000/tcp");
+        assert!(Cli::try_parse_from(["davy", "--publish", "3000:99999"]).is_err());
+    }
+
+    #[test]
+    fn clap_parses_dns_flags() {
+        let cli = Cli::try_parse_from([
+            "davy",
+            "--dns",
+            "10.0.0.2",
+            "--dns",
+            "fd00::53",
+            "--dns-search",
+            "corp.example.com",
+        ])
+        .expect("CLI should parse");
+        assert_eq!(cli.run.dns.len(), 2);
+        assert_eq!(cli.run.dns_search, ["corp.example.com"]);
+        assert!(Cli::try_parse_from(["davy", "--dns", "corp-dns"]).is_err());
+        assert!(Cli::try_parse_from(["davy", "--dns-search", "bad domain"]).is_err());
+        let cli =
+            Cli::try_parse_from(["davy", "--hostname", "agent-box"]).expect("CLI should parse");
+        assert_eq!(cli.run.hostname.as_deref(), Some("agent-box"));
+        assert!(Cli::try_parse_from(["davy", "--hostname", "agent_box"]).is_err());
+    }
+
+    #[test]
+    fn clap_parses_egress_allowlist() {
+        let cli = Cli::try_parse_from([
+            "davy",
+            "--allow-domain",
+            "github.com",
+            "--allow-domain",
+            "*.npmjs.org",
+        ])
+        .expect("CLI should parse");
+        assert!(!cli.run.egress_proxy);
+        assert_eq!(cli.run.allowed_domains, ["github.com", "*.npmjs.org"]);
+        let cli = Cli::try_parse_from(["davy", "--egress-proxy"]).expect("CLI should parse");
+        assert!(cli.run.egress_proxy);
+    }
+
+    #[test]
+    fn clap_parses_dry_run() {
+        let cli =
+            Cli::try_parse_from(["davy", "--dry-run", "--", "make"]).expect("CLI should parse");
+        assert!(cli.run.dry_run);
+        assert_eq!(cli.run.cmd, vec![OsString::from("make")]);
+    }
+
     #[test]
     fn clap_parses_local_dockerfile_flag() {
         let cli = Cli::try_parse_from(["davy", "--local-dockerfile"]).expect("CLI should parse");
         assert!(cli.run.local_dockerfile);
     }
+
+    #[test]
+    fn clap_parses_persist_home() {
+        let cli = Cli::try_parse_from(["davy", "--persist-home"]).expect("CLI should parse");
+        assert_eq!(cli.run.persist_home, Some(HomeScope::User));
+        let cli = Cli::try_parse_from(["davy", "--persist-home", "project", "--", "claude"])
+            .expect("CLI should parse");
+        assert_eq!(cli.run.persist_home, Some(HomeScope::Project));
+
+        let cli = Cli::try_parse_from(["davy", "home", "reset", "project", "-p", "app", "-y"])
+            .expect("CLI should parse");
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Home {
+                command: HomeCommands::Reset {
+                    scope: HomeScope::Project,
+                    project_dir: Some(ref dir),
+                    yes: true
+                }
+            }) if dir == &PathBuf::from("app")
+        ));
+    }
+
+    #[test]
+    fn clap_parses_dockerfile_install() {
+        let cli = Cli::try_parse_from(["davy", "dockerfile", "install"]).expect("CLI should parse");
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Dockerfile {
+                command: DockerfileCommands::Install {
+                    flavor: Flavor::Rocky,
+                    force: false
+                }
+            })
+        ));
+        let cli = Cli::try_parse_from([
+            "davy",
+            "dockerfile",
+            "generate",
+            "--agents",
+            "claude,codex",
+            "--langs",
+            "rust,node,python",
+        ])
+        .expect("CLI should parse");
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Dockerfile {
+                command: DockerfileCommands::Generate { ref agents, ref langs, output: None, .. }
+            }) if agents == &[Agent::Claude, Agent::Codex]
+                && langs == &[Lang::Rust, Lang::Node, Lang::Python]
+        ));
+        let cli =
+            Cli::try_parse_from(["davy", "dockerfile", "install", "--flavor", "debian", "-f"])
+                .expect("CLI should parse");
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Dockerfile {
+                command: DockerfileCommands::Install {
+                    flavor: Flavor::Debian,
+                    force: true
+                }
+            })
+        ));
+    }
+
+    #[test]
+    fn clap_parses_env_files() {
+        let cli = Cli::try_parse_from(["davy", "--env-file", "a.env", "--env-file", "b.env"])
+            .expect("CLI should parse");
+        assert_eq!(
+            cli.run.env_files,
+            [PathBuf::from("a.env"), PathBuf::from("b.env")]
+        );
+
+        let cli = Cli::try_parse_from([
+            "davy",
+            "--env-from-cmd",
+            "GITHUB_TOKEN=gh auth token",
+            "--",
+            "claude",
+        ])
+        .expect("CLI should parse");
+        assert_eq!(cli.run.env_commands[0].key, "GITHUB_TOKEN");
+        assert_eq!(cli.run.env_commands[0].command, "gh auth token");
+    }
+
+    #[test]
+    fn clap_parses_history_flag() {
+        let cli = Cli::try_parse_from(["davy", "--history", "--project-state"])
+            .expect("CLI should parse");
+        assert!(cli.run.history);
+        assert!(cli.run.project_state);
+    }
+
+    #[test]
+    fn clap_parses_cache_flags() {
+        let cli = Cli::try_parse_from(["davy", "--cache-cargo", "--", "cargo", "build"])
+            .expect("CLI should parse");
+        assert!(cli.run.cache_cargo);
+        assert!(!cli.run.cache_node);
+
+        let cli = Cli::try_parse_from(["davy", "--cache-node", "--cache-python"])
+            .expect("CLI should parse");
+        assert!(cli.run.cache_node);
+        assert!(cli.run.cache_python);
+
+        let cli = Cli::try_parse_from(["davy", "--cache-go", "--cache-cc", "--", "make"])
+            .expect("CLI should parse");
+        assert!(cli.run.cache_go);
+        assert!(cli.run.cache_cc);
+
+        let cli = Cli::try_parse_from([
+            "davy",
+            "--cache",
+            "bundler:/home/dev/.bundle/cache",
+            "--cache",
+            "gradle:/home/dev/.gradle/caches",
+        ])
+        .expect("CLI should parse");
+        assert_eq!(cli.run.caches.len(), 2);
+        assert_eq!(cli.run.caches[1].name, "gradle");
+        assert!(Cli::try_parse_from(["davy", "--cache", "gradle"]).is_err());
+
+        let cli =
+            Cli::try_parse_from(["davy", "cache", "rm", "cargo", "-y"]).expect("CLI should parse");
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Cache(CacheArgs {
+                command: CacheCommands::Rm { ref names, all: false, yes: true },
+                ..
+            })) if names == &["cargo"]
+        ));
+        assert!(Cli::try_parse_from(["davy", "cache", "rm"]).is_err());
+    }
 }
@@ -0,0 +1,385 @@
+use std::ffi::OsString;
+use std::fmt;
+use std::net::IpAddr;
+use std::process::Stdio;
+use std::str::FromStr;
+
+use anyhow::Result;
+
+use crate::containers::MANAGED_LABEL;
+use crate::engine::Engine;
+use crate::run_checked;
+
+/// A bridge network shared between sandboxes, where they resolve each other
+/// by container name. Written `davy` or `davy:NAME`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManagedNetwork {
+    name: Option<String>,
+}
+
+impl ManagedNetwork {
+    /// Name of the engine network: `davy` or `davy-NAME`.
+    pub fn network_name(&self) -> String {
+        match &self.name {
+            Some(name) => format!("davy-{name}"),
+            None => "davy".to_owned(),
+        }
+    }
+
+    /// Create the network unless it exists. Another sandbox may create it at
+    /// the same time, so a failed create is fine if the network is there.
+    pub fn ensure(&self, engine: &dyn Engine) -> Result<()> {
+        let network = self.network_name();
+        if exists(engine, &network) {
+            return Ok(());
+        }
+        let mut create = engine.command();
+        create
+            .arg("network")
+            .arg("create")
+            .arg("--label")
+            .arg(format!("{MANAGED_LABEL}=true"))
+            .arg(&network)
+            .stdout(Stdio::null());
+        match run_checked(&mut create, &format!("{} network create", engine.name())) {
+            Err(_) if exists(engine, &network) => Ok(()),
+            result => result,
+        }
+    }
+}
+
+fn exists(engine: &dyn Engine, network: &str) -> bool {
+    engine
+        .command()
+        .arg("network")
+        .arg("inspect")
+        .arg(network)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+impl FromStr for ManagedNetwork {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<ManagedNetwork, String> {
+        let name = match value.strip_prefix("davy") {
+            Some("") => None,
+            Some(rest) => match rest.strip_prefix(':') {
+                Some(name)
+                    if !name.is_empty()
+                        && name
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || "_.-".contains(c)) =>
+                {
+                    Some(name.to_owned())
+                }
+                _ => return Err(format!("invalid network '{value}' (expected davy[:NAME])")),
+            },
+            None => return Err(format!("invalid network '{value}' (expected davy[:NAME])")),
+        };
+        Ok(ManagedNetwork { name })
+    }
+}
+
+impl fmt::Display for ManagedNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "davy:{name}"),
+            None => f.write_str("davy"),
+        }
+    }
+}
+
+/// Name the sandbox reaches the host by.
+pub const HOST_ALIAS: &str = "host.docker.internal";
+
+/// An `/etc/hosts` entry from `--add-host NAME:IP`. The address is an IP or
+/// `host-gateway`, the engine's address for the host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostEntry {
+    pub name: String,
+    pub address: HostAddress,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostAddress {
+    Ip(IpAddr),
+    HostGateway,
+}
+
+impl HostEntry {
+    /// `host.docker.internal` pointing at the host.
+    pub fn host_gateway() -> HostEntry {
+        HostEntry {
+            name: HOST_ALIAS.to_owned(),
+            address: HostAddress::HostGateway,
+        }
+    }
+}
+
+impl FromStr for HostEntry {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<HostEntry, String> {
+        // IPv6 addresses contain colons, so split at the first one (or `=`).
+        let (name, address) = value
+            .split_once(['=', ':'])
+            .ok_or_else(|| format!("invalid host entry '{value}' (expected NAME:IP)"))?;
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+        {
+            return Err(format!("invalid host name '{name}' in '{value}'"));
+        }
+        let address = match address {
+            "host-gateway" => HostAddress::HostGateway,
+            address => HostAddress::Ip(
+                address
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .parse()
+                    .map_err(|_| format!("invalid IP address '{address}' in '{value}'"))?,
+            ),
+        };
+        Ok(HostEntry {
+            name: name.to_owned(),
+            address,
+        })
+    }
+}
+
+impl fmt::Display for HostEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.address {
+            HostAddress::Ip(ip) => write!(f, "{}:{ip}", self.name),
+            HostAddress::HostGateway => write!(f, "{}:host-gateway", self.name),
+        }
+    }
+}
+
+/// Protocols `--publish` accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+    Sctp,
+}
+
+impl Protocol {
+    fn as_str(self) -> &'static str {
+        match self {
+            Protocol::Tcp => "tcp",
+            Protocol::Udp => "udp",
+            Protocol::Sctp => "sctp",
+        }
+    }
+}
+
+/// A `--publish [IP:]HOST:CONTAINER[/PROTO]` mapping. Without an IP the port
+/// is bound to 127.0.0.1 only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortMapping {
+    pub host_ip: IpAddr,
+    pub host_port: u16,
+    pub container_port: u16,
+    pub protocol: Protocol,
+}
+
+impl FromStr for PortMapping {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<PortMapping, String> {
+        let invalid = || {
+            format!("invalid port mapping '{value}' (expected [IP:]HOST:CONTAINER[/tcp|udp|sctp])")
+        };
+        let (ports, protocol) = match value.rsplit_once('/') {
+            Some((ports, "tcp")) => (ports, Protocol::Tcp),
+            Some((ports, "udp")) => (ports, Protocol::Udp),
+            Some((ports, "sctp")) => (ports, Protocol::Sctp),
+            Some(_) => return Err(invalid()),
+            None => (value, Protocol::Tcp),
+        };
+        let (rest, container) = ports.rsplit_once(':').unwrap_or(("", ports));
+        let (host_ip, host) = match rest.rsplit_once(':') {
+            Some((ip, host)) => {
+                let ip = ip.trim_start_matches('[').trim_end_matches(']');
+                (ip.parse().map_err(|_| invalid())?, host)
+            }
+            None => (IpAddr::from([127, 0, 0, 1]), rest),
+        };
+        let port = |port: &str| port.parse::<u16>().ok().filter(|port| *port > 0);
+        let container_port = port(container).ok_or_else(invalid)?;
+        let host_port = if host.is_empty() {
+            container_port
+        } else {
+            port(host).ok_or_else(invalid)?
+        };
+        Ok(PortMapping {
+            host_ip,
+            host_port,
+            container_port,
+            protocol,
+        })
+    }
+}
+
+impl fmt::Display for PortMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.host_ip {
+            IpAddr::V4(ip) => write!(f, "{ip}:")?,
+            IpAddr::V6(ip) => write!(f, "[{ip}]:")?,
+        }
+        write!(
+            f,
+            "{}:{}/{}",
+            self.host_port,
+            self.container_port,
+            self.protocol.as_str()
+        )
+    }
+}
+
+/// Check a `--dns-search` domain.
+pub fn parse_search_domain(value: &str) -> Result<String, String> {
+    if !is_dns_name(value) {
+        return Err(format!("invalid search domain '{value}'"));
+    }
+    Ok(value.to_ascii_lowercase())
+}
+
+/// Check a `--hostname`.
+pub fn parse_hostname(value: &str) -> Result<String, String> {
+    if !is_dns_name(value) || value.len() > 253 {
+        return Err(format!(
+            "invalid hostname '{value}' (letters, digits, and '-', up to 63 per label)"
+        ));
+    }
+    Ok(value.to_owned())
+}
+
+fn is_dns_name(value: &str) -> bool {
+    !value.is_empty()
+        && value.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+/// Pull `--network davy[:NAME]` (or `--network=...`) out of passthrough
+/// `run` arguments; other networks are left for the engine.
+pub fn take_managed(args: &mut Vec<OsString>) -> Option<ManagedNetwork> {
+    let mut found = None;
+    let mut index = 0;
+    while index < args.len() {
+        let arg = args[index].to_str().unwrap_or_default();
+        let (value, len) = match arg.strip_prefix("--network=") {
+            Some(value) => (Some(value), 1),
+            None if arg == "--network" => (args.get(index + 1).and_then(|v| v.to_str()), 2),
+            None => (None, 1),
+        };
+        match value.and_then(|value| value.parse().ok()) {
+            Some(network) => {
+                found = Some(network);
+                args.drain(index..index + len);
+            }
+            None => index += len,
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_managed_networks() {
+        let network: ManagedNetwork = "davy".parse().unwrap();
+        assert_eq!(network.network_name(), "davy");
+        let network: ManagedNetwork = "davy:web".parse().unwrap();
+        assert_eq!(network.network_name(), "davy-web");
+        assert_eq!(network.to_string(), "davy:web");
+        for invalid in ["host", "davy:", "davy:a b", "davyx"] {
+            assert!(invalid.parse::<ManagedNetwork>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn parses_host_entries() {
+        let entry: HostEntry = "llm.local:192.168.1.20".parse().unwrap();
+        assert_eq!(entry.to_string(), "llm.local:192.168.1.20");
+        let entry: HostEntry = "v6=[::1]".parse().unwrap();
+        assert_eq!(entry.to_string(), "v6:::1");
+        let entry: HostEntry = "gw:host-gateway".parse().unwrap();
+        assert_eq!(entry.address, HostAddress::HostGateway);
+        assert_eq!(
+            HostEntry::host_gateway().to_string(),
+            "host.docker.internal:host-gateway"
+        );
+        for invalid in ["nohost", "a b:1.2.3.4", "db:300.1.1.1", ":1.2.3.4"] {
+            assert!(invalid.parse::<HostEntry>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn parses_port_mappings() {
+        let mapping: PortMapping = "3000:3000".parse().unwrap();
+        assert_eq!(mapping.to_string(), "127.0.0.1:3000:3000/tcp");
+        let mapping: PortMapping = "8080".parse().unwrap();
+        assert_eq!((mapping.host_port, mapping.container_port), (8080, 8080));
+        let mapping: PortMapping = "0.0.0.0:5353:53/udp".parse().unwrap();
+        assert_eq!(mapping.to_string(), "0.0.0.0:5353:53/udp");
+        let mapping: PortMapping = "[::1]:9000:9000".parse().unwrap();
+        assert_eq!(mapping.to_string(), "[::1]:9000:9000/tcp");
+        for invalid in [
+            "",
+            "0:80",
+            "70000:80",
+            "3000:3000/icmp",
+            "host:3000:3000",
+            "a:b",
+        ] {
+            assert!(invalid.parse::<PortMapping>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn validates_search_domains() {
+        assert_eq!(
+            parse_search_domain("Corp.Example.com").unwrap(),
+            "corp.example.com"
+        );
+        for invalid in [
+            "",
+            "corp..example",
+            "-corp.example",
+            "corp example",
+            "corp/x",
+        ] {
+            assert!(parse_search_domain(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn takes_managed_network_from_docker_args() {
+        let mut args: Vec<OsString> = ["--privileged", "--network", "davy:api", "--init"]
+            .into_iter()
+            .map(OsString::from)
+            .collect();
+        assert_eq!(take_managed(&mut args), Some("davy:api".parse().unwrap()));
+        assert_eq!(args, ["--privileged", "--init"]);
+
+        let mut args: Vec<OsString> = ["--network", "host", "--network=davy"]
+            .into_iter()
+            .map(OsString::from)
+            .collect();
+        assert_eq!(take_managed(&mut args), Some("davy".parse().unwrap()));
+        assert_eq!(args, ["--network", "host"]);
+    }
+}
@@ -0,0 +1,141 @@
+//! Reaching an Ollama server on the host from the sandbox.
+
+use std::fs;
+use std::net::{IpAddr, Ipv6Addr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::network::HOST_ALIAS;
+
+/// Port Ollama listens on without `OLLAMA_HOST`.
+pub const DEFAULT_PORT: u16 = 11434;
+
+/// Host and port from an `OLLAMA_HOST` value (`[scheme://]host[:port]`),
+/// defaulting like Ollama does.
+pub fn listen_addr(ollama_host: Option<&str>) -> (String, u16) {
+    let value = ollama_host.map(str::trim).unwrap_or_default();
+    let value = value.split_once("://").map_or(value, |(_, rest)| rest);
+    let value = value.trim_end_matches('/');
+    let (host, port) = match value.rsplit_once(':') {
+        Some((host, port)) if !host.ends_with(':') => match port.parse() {
+            Ok(port) => (host, port),
+            Err(_) => (value, DEFAULT_PORT),
+        },
+        _ => (value, DEFAULT_PORT),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let host = if host.is_empty() { "127.0.0.1" } else { host };
+    (host.to_owned(), port)
+}
+
+/// Whether something accepts connections on `port` at `host`; a wildcard
+/// host is checked on loopback.
+pub fn answers(host: &str, port: u16) -> bool {
+    let host = match host.parse::<IpAddr>() {
+        Ok(ip) if ip.is_unspecified() => "127.0.0.1",
+        _ => host,
+    };
+    let Ok(addrs) = (host, port).to_socket_addrs() else {
+        return false;
+    };
+    addrs
+        .into_iter()
+        .any(|addr| TcpStream::connect_timeout(&addr, Duration::from_secs(1)).is_ok())
+}
+
+/// Whether every socket listening on `port` is bound to loopback, per
+/// `/proc/net/tcp` and `/proc/net/tcp6`. `None` when that cannot be told.
+pub fn loopback_only(port: u16) -> Option<bool> {
+    let mut addrs = Vec::new();
+    for table in ["/proc/net/tcp", "/proc/net/tcp6"] {
+        if let Ok(text) = fs::read_to_string(table) {
+            addrs.extend(listening_addrs(&text, port));
+        }
+    }
+    if addrs.is_empty() {
+        return None;
+    }
+    Some(addrs.iter().all(IpAddr::is_loopback))
+}
+
+/// Environment pointing Ollama clients (and aider, which reads
+/// `OLLAMA_API_BASE`) at the host.
+pub fn sandbox_env(port: u16) -> Vec<String> {
+    let url = format!("http://{HOST_ALIAS}:{port}");
+    vec![
+        format!("OLLAMA_HOST={url}"),
+        format!("OLLAMA_API_BASE={url}"),
+    ]
+}
+
+/// Local addresses of the sockets in LISTEN state on `port` in a
+/// `/proc/net/tcp*` table.
+fn listening_addrs(table: &str, port: u16) -> Vec<IpAddr> {
+    const LISTEN: &str = "0A";
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            let local = fields.next()?;
+            let state = fields.nth(1)?;
+            let (addr, local_port) = local.split_once(':')?;
+            (state == LISTEN && u16::from_str_radix(local_port, 16).ok()? == port)
+                .then(|| proc_addr(addr))
+                .flatten()
+        })
+        .collect()
+}
+
+/// The kernel prints addresses as 32-bit words in host byte order.
+fn proc_addr(hex: &str) -> Option<IpAddr> {
+    let words = (0..hex.len())
+        .step_by(8)
+        .map(|i| u32::from_str_radix(hex.get(i..i + 8)?, 16).ok())
+        .collect::<Option<Vec<_>>>()?;
+    let bytes = words
+        .iter()
+        .flat_map(|word| word.to_ne_bytes())
+        .collect::<Vec<_>>();
+    match bytes.len() {
+        4 => Some(IpAddr::from(<[u8; 4]>::try_from(bytes).ok()?)),
+        16 => {
+            let ip = Ipv6Addr::from(<[u8; 16]>::try_from(bytes).ok()?);
+            Some(ip.to_ipv4_mapped().map_or(IpAddr::V6(ip), IpAddr::V4))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    #[test]
+    fn parses_ollama_host() {
+        assert_eq!(listen_addr(None), ("127.0.0.1".to_owned(), DEFAULT_PORT));
+        assert_eq!(listen_addr(Some("0.0.0.0")), ("0.0.0.0".to_owned(), 11434));
+        assert_eq!(
+            listen_addr(Some("http://localhost:8080/")),
+            ("localhost".to_owned(), 8080)
+        );
+        assert_eq!(listen_addr(Some("[::]:11500")), ("::".to_owned(), 11500));
+    }
+
+    #[test]
+    fn finds_listening_sockets() {
+        let table = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:2CAA 00000000:0000 0A 00000000:00000000 00:00000000 00000000   998        0 1 1 0 100 0 0 10 0
+   1: 0100007F:2CAA 0100007F:D1C2 01 00000000:00000000 00:00000000 00000000  1000        0 2 1 0 20 4 30 10 -1
+   2: 00000000:0016 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 3 1 0 100 0 0 10 0
+";
+        if cfg!(target_endian = "little") {
+            assert_eq!(
+                listening_addrs(table, DEFAULT_PORT),
+                [IpAddr::V4(Ipv4Addr::LOCALHOST)]
+            );
+        }
+        assert_eq!(listening_addrs(table, 22).len(), 1);
+    }
+}
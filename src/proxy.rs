@@ -0,0 +1,71 @@
+//! Forwarding the host's proxy settings to image builds and sandboxes.
+
+use std::env;
+
+/// Proxy variables, in both of the cases tools read.
+pub const VARS: &[&str] = &[
+    "HTTP_PROXY",
+    "HTTPS_PROXY",
+    "NO_PROXY",
+    "http_proxy",
+    "https_proxy",
+    "no_proxy",
+];
+
+/// The proxy variables set on the host, as `(KEY, VALUE)`.
+pub fn host_vars() -> Vec<(String, String)> {
+    VARS.iter()
+        .filter_map(|key| {
+            env::var(key)
+                .ok()
+                .filter(|value| !value.is_empty())
+                .map(|value| ((*key).to_owned(), value))
+        })
+        .collect()
+}
+
+/// The host's proxy variables for a build or sandbox, with warnings when
+/// there are none or they point at loopback.
+pub fn forwarded() -> Vec<(String, String)> {
+    let vars = host_vars();
+    if vars.is_empty() {
+        eprintln!("davy: warning: --proxy found no proxy variables set on this machine.");
+    }
+    for (key, value) in &vars {
+        if !key.eq_ignore_ascii_case("NO_PROXY") && is_loopback(value) {
+            eprintln!(
+                "davy: warning: {key} points at this machine's loopback, which builds and sandboxes cannot reach."
+            );
+        }
+    }
+    vars
+}
+
+/// Whether a proxy URL points at this machine's loopback, which builds and
+/// sandboxes cannot reach.
+pub fn is_loopback(url: &str) -> bool {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split('/').next().unwrap_or(rest);
+    let host_port = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    let host = match host_port.strip_prefix('[') {
+        Some(bracketed) => bracketed.split(']').next().unwrap_or(bracketed),
+        None => host_port.split(':').next().unwrap_or(host_port),
+    };
+    host.eq_ignore_ascii_case("localhost") || host.starts_with("127.") || host == "::1"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_loopback_proxies() {
+        assert!(is_loopback("http://127.0.0.1:3128"));
+        assert!(is_loopback("http://user:pw@localhost:8080/"));
+        assert!(is_loopback("[::1]:3128"));
+        assert!(!is_loopback("http://proxy.corp.example:3128"));
+        assert!(!is_loopback("http://10.0.0.5:3128"));
+    }
+}
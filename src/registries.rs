@@ -0,0 +1,175 @@
+//! Package registry credentials, cut down to what the sandbox needs.
+
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+
+/// The registry and auth lines of an `.npmrc`, without settings such as
+/// `prefix` or `cafile` whose host paths mean nothing in the sandbox.
+/// Also returns the variables the kept lines reference as `${VAR}`.
+pub fn npmrc_auth(npmrc: &str) -> (String, Vec<String>) {
+    let mut kept = String::new();
+    let mut vars = BTreeSet::new();
+    for line in npmrc.lines() {
+        let trimmed = line.trim();
+        let Some((key, _)) = trimmed.split_once('=') else {
+            continue;
+        };
+        let key = key.trim();
+        let auth = key.starts_with("//")
+            || key == "registry"
+            || key.ends_with(":registry")
+            || key.starts_with("_auth")
+            || key == "always-auth"
+            || key == "email";
+        if !auth || trimmed.starts_with(['#', ';']) {
+            continue;
+        }
+        kept.push_str(trimmed);
+        kept.push('\n');
+        vars.extend(referenced_vars(trimmed));
+    }
+    (kept, vars.into_iter().collect())
+}
+
+/// Variables pip and uv read their index URLs from.
+pub const PYTHON_INDEX_VARS: [&str; 7] = [
+    "PIP_INDEX_URL",
+    "PIP_EXTRA_INDEX_URL",
+    "PIP_TRUSTED_HOST",
+    "UV_INDEX_URL",
+    "UV_EXTRA_INDEX_URL",
+    "UV_DEFAULT_INDEX",
+    "UV_INDEX",
+];
+
+/// Hosts of the URLs in `text` (a pip.conf or index variables).
+pub fn url_hosts(text: &str) -> Vec<String> {
+    let mut hosts = BTreeSet::new();
+    for (_, rest) in text
+        .split_whitespace()
+        .filter_map(|word| word.split_once("://"))
+    {
+        let authority = rest.split('/').next().unwrap_or(rest);
+        let host = authority.rsplit('@').next().unwrap_or(authority);
+        let host = host.split(':').next().unwrap_or(host);
+        if !host.is_empty() {
+            hosts.insert(host.to_ascii_lowercase());
+        }
+    }
+    hosts.into_iter().collect()
+}
+
+/// The `machine` entries of a `.netrc` for `hosts`, one per line.
+pub fn netrc_for(netrc: &str, hosts: &[String]) -> String {
+    let mut entries = String::new();
+    let mut tokens = netrc.split_whitespace().peekable();
+    while let Some(token) = tokens.next() {
+        match token {
+            "machine" => {
+                let Some(machine) = tokens.next() else {
+                    break;
+                };
+                let mut entry = format!("machine {machine}");
+                while let Some(next) =
+                    tokens.next_if(|next| !matches!(*next, "machine" | "default" | "macdef"))
+                {
+                    entry.push(' ');
+                    entry.push_str(next);
+                }
+                if hosts.iter().any(|host| host.eq_ignore_ascii_case(machine)) {
+                    entries.push_str(&entry);
+                    entries.push('\n');
+                }
+            }
+            // Macros run until a blank line, which whitespace splitting
+            // loses; stop rather than misread them.
+            "macdef" => break,
+            _ => {}
+        }
+    }
+    entries
+}
+
+/// The registry tables of a Cargo `config.toml` (`[registry]`,
+/// `[registries.*]`, `[source.*]`), without build settings whose host paths
+/// mean nothing in the sandbox.
+pub fn cargo_registry_config(config: &str) -> Result<String> {
+    let mut table: toml::Table = toml::from_str(config)?;
+    table.retain(|key, _| matches!(key, "registry" | "registries" | "source"));
+    Ok(toml::to_string(&table)?)
+}
+
+/// `CARGO_REGISTRY_TOKEN` and `CARGO_REGISTRIES_<NAME>_TOKEN`.
+pub fn is_cargo_token_var(key: &str) -> bool {
+    key == "CARGO_REGISTRY_TOKEN"
+        || key
+            .strip_prefix("CARGO_REGISTRIES_")
+            .is_some_and(|rest| rest.ends_with("_TOKEN"))
+}
+
+/// Names in `${NAME}` references.
+fn referenced_vars(line: &str) -> Vec<String> {
+    line.split("${")
+        .skip(1)
+        .filter_map(|rest| rest.split_once('}'))
+        .map(|(name, _)| name.trim_end_matches('?').to_owned())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn npmrc_keeps_only_registry_auth() {
+        let npmrc = "\
+prefix=/Users/me/.npm-global
+registry=https://registry.npmjs.org/
+@acme:registry=https://npm.acme.dev/
+//npm.acme.dev/:_authToken=${ACME_NPM_TOKEN}
+; //old.example/:_authToken=stale
+cafile=/etc/ssl/acme.pem
+";
+        let (kept, vars) = npmrc_auth(npmrc);
+        assert_eq!(
+            kept,
+            "registry=https://registry.npmjs.org/\n@acme:registry=https://npm.acme.dev/\n//npm.acme.dev/:_authToken=${ACME_NPM_TOKEN}\n"
+        );
+        assert_eq!(vars, ["ACME_NPM_TOKEN"]);
+    }
+
+    #[test]
+    fn cargo_config_keeps_only_registries() {
+        let config = r#"
+[build]
+target-dir = "/Users/me/target"
+
+[registries.acme]
+index = "sparse+https://crates.acme.dev/index/"
+
+[registry]
+default = "acme"
+"#;
+        let kept: toml::Table = toml::from_str(&cargo_registry_config(config).unwrap()).unwrap();
+        assert!(kept.get("build").is_none());
+        assert_eq!(kept["registry"]["default"].as_str(), Some("acme"));
+        assert!(kept["registries"]["acme"]["index"].is_str());
+        assert!(is_cargo_token_var("CARGO_REGISTRIES_ACME_TOKEN"));
+        assert!(!is_cargo_token_var("CARGO_REGISTRIES_ACME_INDEX"));
+    }
+
+    #[test]
+    fn netrc_keeps_only_index_hosts() {
+        let hosts = url_hosts(
+            "[global]\nindex-url = https://ci@pypi.acme.dev:8443/simple\nextra-index-url =\n    https://pypi.org/simple",
+        );
+        assert_eq!(hosts, ["pypi.acme.dev", "pypi.org"]);
+        let netrc = "machine github.com login me password gh\nmachine pypi.acme.dev\n  login ci\n  password secret\ndefault login anonymous\n";
+        assert_eq!(
+            netrc_for(netrc, &hosts),
+            "machine pypi.acme.dev login ci password secret\n"
+        );
+    }
+}
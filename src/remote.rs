@@ -0,0 +1,232 @@
+use std::env;
+use std::path::Path;
+use std::process::Stdio;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::containers;
+use crate::engine::Engine;
+use crate::run_checked;
+
+/// How the project directory reaches the container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectMode {
+    /// Bind mount for local daemons, copy for remote ones
+    Auto,
+    /// Bind mount the project directory
+    Bind,
+    /// Copy the project into a volume before the run and back afterwards
+    Copy,
+    /// Copy the project into a persistent volume on first use only
+    Volume,
+}
+
+impl ProjectMode {
+    /// Resolve `Auto` against where the daemon lives.
+    pub fn resolve(self, remote: bool) -> ProjectMode {
+        match self {
+            ProjectMode::Auto if remote => ProjectMode::Copy,
+            ProjectMode::Auto => ProjectMode::Bind,
+            mode => mode,
+        }
+    }
+}
+
+/// Endpoint of the docker daemon (`DOCKER_HOST`, else the active context,
+/// which honors `DOCKER_CONTEXT`). Only docker-family engines are inspected.
+pub fn docker_endpoint(engine: &dyn Engine) -> Option<String> {
+    if engine.binary() != "docker" {
+        return None;
+    }
+    if let Some(host) = env::var("DOCKER_HOST").ok().filter(|host| !host.is_empty()) {
+        return Some(host);
+    }
+
+    let output = engine
+        .command()
+        .arg("context")
+        .arg("inspect")
+        .arg("--format")
+        .arg("{{.Endpoints.docker.Host}}")
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_owned()).filter(|host| !host.is_empty())
+}
+
+/// Whether an endpoint points at a daemon on another machine, where host
+/// paths cannot be bind-mounted.
+pub fn is_remote_endpoint(endpoint: &str) -> bool {
+    if endpoint.starts_with("unix://") || endpoint.starts_with("npipe://") {
+        return false;
+    }
+    let Some(rest) = endpoint
+        .strip_prefix("tcp://")
+        .or_else(|| endpoint.strip_prefix("http://"))
+        .or_else(|| endpoint.strip_prefix("https://"))
+    else {
+        // ssh:// and anything unrecognized
+        return true;
+    };
+    let host = rest.split(['/', ':']).next().unwrap_or_default();
+    let host = if rest.starts_with('[') {
+        rest.split(']')
+            .next()
+            .unwrap_or_default()
+            .trim_start_matches('[')
+    } else {
+        host
+    };
+    !matches!(host, "localhost" | "127.0.0.1" | "::1")
+}
+
+/// Host name in an endpoint such as `ssh://me@build-box` or
+/// `tcp://10.0.0.5:2376`.
+pub fn endpoint_host(endpoint: &str) -> Option<String> {
+    let rest = endpoint
+        .split_once("://")
+        .map_or(endpoint, |(_, rest)| rest);
+    let rest = rest.rsplit_once('@').map_or(rest, |(_, host)| host);
+    let host = match rest.strip_prefix('[') {
+        Some(v6) => v6.split(']').next().unwrap_or_default(),
+        None => rest.split([':', '/']).next().unwrap_or_default(),
+    };
+    (!host.is_empty()).then(|| host.to_owned())
+}
+
+/// Name of the volume holding a copy of `project_dir`.
+pub fn project_volume_name(host_uid: u32, project_dir: &Path) -> String {
+    format!("davy-project-{host_uid}-{}", project_hash(project_dir))
+}
+
+/// First 16 hex digits of the SHA-256 of `project_dir`. It names volumes,
+/// claims, and images that outlive davy, so it must not change between builds.
+pub fn project_hash(project_dir: &Path) -> String {
+    let digest = Sha256::digest(project_dir.as_os_str().as_encoded_bytes());
+    digest[..8]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Copy the project into `volume` through a stopped helper container.
+pub fn copy_project_in(
+    engine: &dyn Engine,
+    image: &str,
+    volume: &str,
+    project_dir: &Path,
+) -> Result<()> {
+    with_helper(engine, image, volume, |helper| {
+        let mut cmd = engine.command();
+        cmd.arg("cp")
+            .arg("--archive")
+            .arg(format!("{}/.", project_dir.display()))
+            .arg(format!("{helper}:/project"));
+        run_checked(
+            &mut cmd,
+            &format!("{} cp (project to volume)", engine.name()),
+        )
+    })
+}
+
+/// Copy `/project` from `volume` back over the host project directory.
+pub fn copy_project_out(
+    engine: &dyn Engine,
+    image: &str,
+    volume: &str,
+    project_dir: &Path,
+) -> Result<()> {
+    with_helper(engine, image, volume, |helper| {
+        let mut cmd = engine.command();
+        cmd.arg("cp")
+            .arg(format!("{helper}:/project/."))
+            .arg(project_dir);
+        run_checked(
+            &mut cmd,
+            &format!("{} cp (volume to project)", engine.name()),
+        )
+    })
+}
+
+fn with_helper(
+    engine: &dyn Engine,
+    image: &str,
+    volume: &str,
+    action: impl FnOnce(&str) -> Result<()>,
+) -> Result<()> {
+    let helper = format!("{volume}-xfer");
+    let mut create = engine.command();
+    create
+        .arg("create")
+        .arg("--name")
+        .arg(&helper)
+        .arg("--label")
+        .arg(format!("{}=true", containers::MANAGED_LABEL))
+        .arg("-v")
+        .arg(format!("{volume}:/project"))
+        .arg(image)
+        .arg("true")
+        .stdout(Stdio::null());
+    run_checked(
+        &mut create,
+        &format!("{} create (transfer helper)", engine.name()),
+    )?;
+
+    let result = action(&helper);
+
+    let mut remove = engine.command();
+    remove
+        .arg("rm")
+        .arg("--force")
+        .arg(&helper)
+        .stdout(Stdio::null());
+    let removed = run_checked(
+        &mut remove,
+        &format!("{} rm (transfer helper)", engine.name()),
+    );
+    result.and(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_endpoints() {
+        assert!(!is_remote_endpoint("unix:///var/run/docker.sock"));
+        assert!(!is_remote_endpoint("tcp://127.0.0.1:2375"));
+        assert!(!is_remote_endpoint("tcp://localhost:2375"));
+        assert!(!is_remote_endpoint("tcp://[::1]:2375"));
+        assert!(is_remote_endpoint("tcp://10.0.0.5:2376"));
+        assert!(is_remote_endpoint("ssh://me@build-box"));
+        assert_eq!(
+            endpoint_host("ssh://me@build-box:2222").as_deref(),
+            Some("build-box")
+        );
+        assert_eq!(
+            endpoint_host("tcp://[fd00::5]:2376").as_deref(),
+            Some("fd00::5")
+        );
+    }
+
+    #[test]
+    fn auto_mode_copies_for_remote_daemons() {
+        assert_eq!(ProjectMode::Auto.resolve(true), ProjectMode::Copy);
+        assert_eq!(ProjectMode::Auto.resolve(false), ProjectMode::Bind);
+        assert_eq!(ProjectMode::Volume.resolve(false), ProjectMode::Volume);
+    }
+
+    #[test]
+    fn project_volume_name_is_stable() {
+        let a = project_volume_name(1000, Path::new("/work/app"));
+        assert_eq!(a, "davy-project-1000-70467eff2e0a2364");
+        assert_ne!(a, project_volume_name(1000, Path::new("/work/other")));
+    }
+}
@@ -0,0 +1,221 @@
+use std::ffi::OsString;
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{Result, bail};
+
+/// Resource names docker accepts for `--ulimit`.
+const ULIMIT_NAMES: [&str; 15] = [
+    "core",
+    "cpu",
+    "data",
+    "fsize",
+    "locks",
+    "memlock",
+    "msgqueue",
+    "nice",
+    "nofile",
+    "nproc",
+    "rss",
+    "rtprio",
+    "rttime",
+    "sigpending",
+    "stack",
+];
+
+/// Resource limits for the sandbox container.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Resources {
+    /// Memory limit in docker's format, e.g. `512m` or `8g`.
+    pub memory: Option<String>,
+    pub cpus: Option<f64>,
+    pub pids_limit: Option<u32>,
+    /// Size of `/dev/shm`, in the same format as `memory`.
+    pub shm_size: Option<String>,
+    pub ulimits: Vec<Ulimit>,
+}
+
+impl Resources {
+    /// Defaults for `--constrained`: 8 GiB of memory, 2 CPUs, 4096 processes
+    /// (threads count too).
+    pub fn constrained() -> Resources {
+        Resources {
+            memory: Some("8g".to_owned()),
+            cpus: Some(2.0),
+            pids_limit: Some(4096),
+            ..Resources::default()
+        }
+    }
+
+    pub(crate) fn validate(&self) -> Result<()> {
+        if let Some(memory) = &self.memory
+            && !is_size(memory)
+        {
+            bail!("invalid memory limit '{memory}' (expected e.g. 512m or 8g)");
+        }
+        if let Some(cpus) = self.cpus
+            && cpus <= 0.0
+        {
+            bail!("invalid CPU limit {cpus} (must be greater than 0)");
+        }
+        if self.pids_limit == Some(0) {
+            bail!("invalid pids limit 0");
+        }
+        if let Some(size) = &self.shm_size
+            && !is_size(size)
+        {
+            bail!("invalid shm size '{size}' (expected e.g. 512m or 2g)");
+        }
+        Ok(())
+    }
+
+    pub(crate) fn run_args(&self) -> Vec<OsString> {
+        let mut args = Vec::new();
+        if let Some(memory) = &self.memory {
+            args.push(OsString::from("--memory"));
+            args.push(OsString::from(memory));
+        }
+        if let Some(cpus) = self.cpus {
+            args.push(OsString::from("--cpus"));
+            args.push(OsString::from(cpus.to_string()));
+        }
+        if let Some(pids) = self.pids_limit {
+            args.push(OsString::from("--pids-limit"));
+            args.push(OsString::from(pids.to_string()));
+        }
+        if let Some(size) = &self.shm_size {
+            args.push(OsString::from("--shm-size"));
+            args.push(OsString::from(size));
+        }
+        for ulimit in &self.ulimits {
+            args.push(OsString::from("--ulimit"));
+            args.push(OsString::from(ulimit.to_string()));
+        }
+        args
+    }
+}
+
+/// A `--ulimit NAME=SOFT[:HARD]` setting; `-1` means unlimited.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ulimit {
+    pub name: String,
+    pub soft: i64,
+    pub hard: i64,
+}
+
+impl FromStr for Ulimit {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Ulimit, String> {
+        let invalid = || format!("invalid ulimit '{value}' (expected NAME=SOFT[:HARD])");
+        let (name, limits) = value.split_once('=').ok_or_else(invalid)?;
+        if !ULIMIT_NAMES.contains(&name) {
+            return Err(format!(
+                "unknown ulimit '{name}' (expected one of: {})",
+                ULIMIT_NAMES.join(", ")
+            ));
+        }
+        let parse = |limit: &str| {
+            limit
+                .parse::<i64>()
+                .ok()
+                .filter(|limit| *limit >= -1)
+                .ok_or_else(invalid)
+        };
+        let (soft, hard) = match limits.split_once(':') {
+            Some((soft, hard)) => (parse(soft)?, parse(hard)?),
+            None => (parse(limits)?, parse(limits)?),
+        };
+        if hard != -1 && (soft == -1 || soft > hard) {
+            return Err(format!(
+                "invalid ulimit '{value}' (soft limit is above the hard limit)"
+            ));
+        }
+        Ok(Ulimit {
+            name: name.to_owned(),
+            soft,
+            hard,
+        })
+    }
+}
+
+impl fmt::Display for Ulimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}:{}", self.name, self.soft, self.hard)
+    }
+}
+
+/// Whether `value` is a size docker accepts: `8g`, `512MiB`, `1073741824`.
+fn is_size(value: &str) -> bool {
+    let rest = value.strip_suffix(['b', 'B']).unwrap_or(value);
+    let rest = rest.strip_suffix(['i', 'I']).unwrap_or(rest);
+    let rest = rest
+        .strip_suffix(|c: char| "kKmMgGtTpP".contains(c))
+        .unwrap_or(rest);
+    let number = rest.strip_suffix(' ').unwrap_or(rest);
+    number.chars().all(|c| c.is_ascii_digit() || c == '.')
+        && number.parse::<f64>().is_ok_and(|value| value > 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resource_limits_become_run_args() {
+        let resources = Resources {
+            cpus: Some(1.5),
+            shm_size: Some("2g".to_owned()),
+            ulimits: vec!["nofile=65536".parse().unwrap()],
+            ..Resources::constrained()
+        };
+        resources.validate().expect("limits should be valid");
+        let args: Vec<_> = resources
+            .run_args()
+            .into_iter()
+            .map(|arg| arg.into_string().unwrap())
+            .collect();
+        assert_eq!(
+            args,
+            [
+                "--memory",
+                "8g",
+                "--cpus",
+                "1.5",
+                "--pids-limit",
+                "4096",
+                "--shm-size",
+                "2g",
+                "--ulimit",
+                "nofile=65536:65536"
+            ]
+        );
+        for memory in ["512m", "1.5g", "2GiB", "8gb", "1073741824"] {
+            let resources = Resources {
+                memory: Some(memory.to_owned()),
+                ..Resources::default()
+            };
+            assert!(resources.validate().is_ok(), "{memory}");
+        }
+        for memory in ["lots", "0g", "g", "8x", "-1g"] {
+            let resources = Resources {
+                memory: Some(memory.to_owned()),
+                ..Resources::default()
+            };
+            assert!(resources.validate().is_err(), "{memory}");
+        }
+    }
+
+    #[test]
+    fn parses_ulimits() {
+        let ulimit: Ulimit = "nofile=1024:65536".parse().unwrap();
+        assert_eq!((ulimit.soft, ulimit.hard), (1024, 65536));
+        let ulimit: Ulimit = "memlock=-1".parse().unwrap();
+        assert_eq!(ulimit.to_string(), "memlock=-1:-1");
+        assert!("nofile".parse::<Ulimit>().is_err());
+        assert!("files=10".parse::<Ulimit>().is_err());
+        assert!("nofile=100:10".parse::<Ulimit>().is_err());
+        assert!("nofile=-1:10".parse::<Ulimit>().is_err());
+        assert!("nproc=lots".parse::<Ulimit>().is_err());
+    }
+}
@@ -0,0 +1,4130 @@
+//! Sandbox construction and execution: turns options (from the CLI or a
+//! [`SandboxBuilder`]) into a container run.
+
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io::IsTerminal;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD;
+use chrono::Local;
+#[cfg(unix)]
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use users::{get_current_gid, get_current_uid};
+
+use crate::auth::{self, AuthStore, AuthVolume};
+use crate::caches::{self, CacheMount};
+use crate::certs;
+use crate::config::{self, Config, K8sConfig, SidecarConfig};
+use crate::devcontainer::{self, ImageSource};
+use crate::devices::{self, Gpus};
+use crate::display;
+use crate::dockerfiles;
+use crate::dotfiles;
+use crate::egress::{self, Egress};
+use crate::engine::{BuildRequest, Engine};
+use crate::envfile::{self, EnvCommand};
+use crate::error::DavyError;
+use crate::git;
+use crate::home::{self, HomeScope};
+use crate::ide::{self, SshTarget, WebTerminal};
+use crate::k8s::{self, Backend, Kubectl};
+use crate::lima::{self, LimaVm};
+use crate::network::{self, HostEntry, ManagedNetwork, PortMapping};
+use crate::note;
+use crate::ollama;
+use crate::proxy;
+use crate::registries;
+use crate::remote::{self, ProjectMode};
+use crate::resources::{Resources, Ulimit};
+use crate::secrets::{self, SecretRef};
+use crate::security;
+use crate::signals::{self, SignalForwarder, Stopped};
+use crate::skills::{self, SkillsMode};
+use crate::state;
+use crate::{
+    PrivateFiles, compose, containers, home_dir, lan, run_checked, run_status,
+    set_private_files_owner, sidecars, write_private_file,
+};
+
+/// Image tag used when none is configured.
+pub const DEFAULT_IMAGE: &str = "davy-sandbox:latest";
+/// Host port for SSH when `--expose-ssh` gives none.
+pub const DEFAULT_SSH_PORT: u16 = 222;
+const CLAUDE_LINK_SCRIPT: &str = r#"set -e
+mkdir -p /home/dev/.claude-auth/.claude
+touch /home/dev/.claude-auth/.claude.json
+
+if [ -e /home/dev/.claude ] && [ ! -L /home/dev/.claude ]; then
+  rm -rf /home/dev/.claude
+fi
+if [ -e /home/dev/.claude.json ] && [ ! -L /home/dev/.claude.json ]; then
+  rm -f /home/dev/.claude.json
+fi
+
+ln -sfn /home/dev/.claude-auth/.claude /home/dev/.claude
+ln -sfn /home/dev/.claude-auth/.claude.json /home/dev/.claude.json
+export CLAUDE_CONFIG_DIR=/home/dev/.claude
+
+exec "$@""#;
+
+const CLAUDE_SETTINGS_SCRIPT: &str = r#"set -e
+mkdir -p /home/dev/.claude
+printf '%s' "$DAVY_CLAUDE_SETTINGS_B64" | base64 -d >/home/dev/.claude/settings.json
+unset DAVY_CLAUDE_SETTINGS_B64
+
+exec "$@""#;
+
+const SSH_BOOTSTRAP_SCRIPT: &str = r#"set -e
+if ! command -v sshd >/dev/null 2>&1; then
+  echo "davy: sshd is not installed in image. Rebuild with the latest rocky.Dockerfile." >&2
+  exit 1
+fi
+
+if ! command -v ps >/dev/null 2>&1; then
+  echo "davy: 'ps' is required for remote IDE SSH helpers (VS Code and derivatives)." >&2
+  echo "davy: rebuild with the latest rocky.Dockerfile." >&2
+  exit 1
+fi
+
+if ! command -v flock >/dev/null 2>&1; then
+  echo "davy: 'flock' is required for remote IDE SSH helpers (VS Code and derivatives)." >&2
+  echo "davy: rebuild with the latest rocky.Dockerfile." >&2
+  exit 1
+fi
+
+mkdir -p /home/dev/.ssh
+chmod 700 /home/dev/.ssh
+umask 077
+if [ ! -f /run/davy/authorized_keys ]; then
+  echo "davy: /run/davy/authorized_keys is missing." >&2
+  exit 1
+fi
+cat /run/davy/authorized_keys >/home/dev/.ssh/authorized_keys
+if [ ! -s /home/dev/.ssh/authorized_keys ]; then
+  echo "davy: authorized_keys is empty." >&2
+  exit 1
+fi
+chmod 600 /home/dev/.ssh/authorized_keys
+
+sudo mkdir -p /run/sshd
+if ! ls /etc/ssh/ssh_host_*_key >/dev/null 2>&1; then
+  sudo ssh-keygen -A >/dev/null
+fi
+
+sudo /usr/sbin/sshd \
+  -o PermitRootLogin=no \
+  -o PasswordAuthentication=no \
+  -o KbdInteractiveAuthentication=no \
+  -o ChallengeResponseAuthentication=no \
+  -o PubkeyAuthentication=yes \
+  -o AuthorizedKeysFile=.ssh/authorized_keys \
+  -o PidFile=/tmp/davy-sshd.pid
+
+exec "$@""#;
+
+/// Installs code-server into its volume on first use and starts it on
+/// port 8080 in the background, with the password davy generated.
+const CODE_SERVER_SCRIPT: &str = r#"set -e
+prefix=/home/dev/.cache/davy-code-server
+export PATH="$prefix/bin:$PATH"
+if ! command -v code-server >/dev/null 2>&1; then
+  echo "davy: installing code-server (first run only)..." >&2
+  curl -fsSL https://code-server.dev/install.sh \
+    | sh -s -- --method standalone --prefix "$prefix" >&2
+fi
+PASSWORD="$DAVY_CODE_SERVER_PASSWORD" nohup code-server \
+  --bind-addr 0.0.0.0:8080 \
+  --auth password \
+  --disable-telemetry \
+  /project >/tmp/davy-code-server.log 2>&1 &
+unset DAVY_CODE_SERVER_PASSWORD
+
+exec "$@""#;
+
+/// Runs the command in a tmux session that ttyd serves to the browser, and
+/// attaches this terminal to it too when there is one.
+const WEB_TERMINAL_SCRIPT: &str = r#"set -e
+if ! command -v ttyd >/dev/null 2>&1 || ! command -v tmux >/dev/null 2>&1; then
+  echo "davy: --web-terminal needs ttyd and tmux in the image. Rebuild with the latest rocky.Dockerfile." >&2
+  exit 1
+fi
+
+status=/tmp/davy-web-terminal.status
+rm -f "$status"
+tmux new-session -d -s davy -x 200 -y 50 \
+  bash -c '"$@"; echo $? >/tmp/davy-web-terminal.status' bash "$@"
+
+ttyd_args=(--port 7681)
+if ttyd --help 2>&1 | grep -q -- --writable; then
+  ttyd_args+=(--writable)
+fi
+if [ -n "${DAVY_WEB_TERMINAL_CREDENTIAL:-}" ]; then
+  ttyd_args+=(--credential "$DAVY_WEB_TERMINAL_CREDENTIAL")
+fi
+ttyd "${ttyd_args[@]}" tmux attach-session -t davy >/tmp/davy-ttyd.log 2>&1 &
+
+if [ -t 0 ] && [ -t 1 ]; then
+  tmux attach-session -t davy || true
+fi
+while tmux has-session -t davy 2>/dev/null; do
+  sleep 1
+done
+exit "$(cat "$status" 2>/dev/null || echo 1)""#;
+
+/// Stops the sandbox from the inside once it has had no `exec`/SSH sessions
+/// and next to no CPU use for `DAVY_IDLE_TIMEOUT_SECS`. Needs `--init`, since
+/// the command it signals must not be PID 1.
+const IDLE_WATCH_SCRIPT: &str = r#"main=$$
+limit=${DAVY_IDLE_TIMEOUT_SECS:?}
+interval=$(( limit < 60 ? limit : 60 ))
+
+cpu_usec() {
+  local key value
+  if [ -r /sys/fs/cgroup/cpu.stat ]; then
+    while read -r key value; do
+      if [ "$key" = usage_usec ]; then echo "$value"; return; fi
+    done </sys/fs/cgroup/cpu.stat
+  elif [ -r /sys/fs/cgroup/cpuacct/cpuacct.usage ]; then
+    read -r value </sys/fs/cgroup/cpuacct/cpuacct.usage
+    echo $(( value / 1000 ))
+    return
+  fi
+  echo 0
+}
+
+# `exec` sessions have no parent inside the container; SSH logins show up as
+# "sshd: user@tty".
+has_sessions() {
+  local dir stat fields cmd
+  for dir in /proc/[0-9]*; do
+    [ "${dir#/proc/}" = 1 ] && continue
+    stat=$(cat "$dir/stat" 2>/dev/null) || continue
+    read -r -a fields <<<"${stat##*) }"
+    [ "${fields[1]}" = 0 ] && return 0
+    cmd=
+    IFS= read -r -d '' cmd <"$dir/cmdline" 2>/dev/null
+    case "$cmd" in "sshd: "*@*) return 0 ;; esac
+  done
+  return 1
+}
+
+(
+  idle=0
+  last=$(cpu_usec)
+  while sleep "$interval"; do
+    now=$(cpu_usec)
+    # Busy means more than 1% of a CPU over the interval.
+    if has_sessions || [ $(( now - last )) -gt $(( interval * 10000 )) ]; then
+      idle=0
+    else
+      idle=$(( idle + interval ))
+    fi
+    last=$now
+    if [ "$idle" -ge "$limit" ]; then
+      echo "davy: sandbox idle for ${limit}s; stopping it." >&2
+      kill -TERM "$main" 2>/dev/null
+      sleep 10
+      kill -KILL "$main" 2>/dev/null
+      exit 0
+    fi
+  done
+) </dev/null &
+
+exec "$@""#;
+
+/// A host tool config to mount like the built-in auth mounts:
+/// `SRC:DST[:ro|rw]`, where `~` in `SRC` is the host home and in `DST` the
+/// sandbox user's.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthMount {
+    pub source: PathBuf,
+    pub target: String,
+    pub read_only: bool,
+}
+
+impl FromStr for AuthMount {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<AuthMount, String> {
+        let invalid = || format!("invalid auth mount '{spec}' (expected SRC:DST[:ro|rw])");
+        let mut parts = spec.split(':');
+        let (Some(source), Some(target)) = (parts.next(), parts.next()) else {
+            return Err(invalid());
+        };
+        let read_only = match parts.next() {
+            None | Some("rw") => false,
+            Some("ro") => true,
+            Some(_) => return Err(invalid()),
+        };
+        if source.is_empty() || parts.next().is_some() {
+            return Err(invalid());
+        }
+        let source = match source.strip_prefix("~/") {
+            Some(rest) => home_dir().map_err(|err| err.to_string())?.join(rest),
+            None => PathBuf::from(source),
+        };
+        let target = match target.strip_prefix("~/") {
+            Some(rest) => format!("/home/dev/{rest}"),
+            None => target.to_owned(),
+        };
+        if !target.starts_with('/') {
+            return Err(format!(
+                "auth mount target '{target}' must be an absolute path or start with ~/"
+            ));
+        }
+        Ok(AuthMount {
+            source,
+            target,
+            read_only,
+        })
+    }
+}
+
+/// How to build the sandbox image.
+pub struct BuildSettings {
+    dockerfile: PathBuf,
+    /// Build context; `None` sends the Dockerfile alone where the engine
+    /// allows it.
+    context_dir: Option<PathBuf>,
+    image: String,
+    build_args: Vec<(String, String)>,
+    host_uid: u32,
+    host_gid: u32,
+}
+
+impl BuildSettings {
+    /// Pass the host's proxy variables to the build as build args.
+    pub fn forward_proxy(&mut self) {
+        self.build_args.extend(proxy::forwarded());
+    }
+}
+
+/// A fully resolved sandbox, ready to [`run`](Sandbox::run).
+pub struct Sandbox {
+    project_dir: PathBuf,
+    project_mode: ProjectMode,
+    project_volume: String,
+    build: BuildSettings,
+    name: String,
+    host_uid: u32,
+    host_gid: u32,
+    keep: bool,
+    reuse: bool,
+    /// `None`: allocate a TTY when stdin and stdout are terminals.
+    tty: Option<bool>,
+    detach: bool,
+    timeout: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    resources: Resources,
+    hardened: bool,
+    no_network: bool,
+    no_lan: bool,
+    egress: Option<Egress>,
+    /// `--security-opt` values for seccomp and AppArmor.
+    security_opts: Vec<String>,
+    gpus: Option<Gpus>,
+    rebuild: bool,
+    no_build: bool,
+    /// The image is pulled by `run` rather than built (devcontainer `image`).
+    skip_build: bool,
+    docker_sock: Option<PathBuf>,
+    docker_sock_gid: Option<u32>,
+    expose_ssh: Option<u16>,
+    /// Host that published ports are on: the remote daemon's, or localhost.
+    daemon_host: String,
+    /// Where an IDE reaches sshd, with `expose_ssh`.
+    ssh_target: Option<SshTarget>,
+    open_vscode: bool,
+    web_terminal: Option<WebTerminal>,
+    /// Host port for code-server, and its password.
+    code_server: Option<(u16, String)>,
+    code_server_volume: String,
+    /// Volume holding `HISTFILE`, with `history`.
+    history_volume: Option<String>,
+    /// Volume mounted over `/home/dev`, with `persist_home`.
+    home_volume: Option<String>,
+    /// Per-project volume for agent session history, with `project_state`.
+    state_volume: Option<String>,
+    /// Agent directories linked into the state volume, or unlinked without it.
+    state_dirs: Vec<&'static str>,
+    /// Shared cache volumes and where they are mounted.
+    cache_volumes: Vec<(String, String)>,
+    /// Startup scripts of the enabled caches.
+    cache_scripts: Vec<&'static str>,
+    /// Variables set from host commands, run when the sandbox starts.
+    env_commands: Vec<EnvCommand>,
+    /// Variables read from secret managers, also run when the sandbox starts.
+    secrets: Vec<(String, SecretRef)>,
+    /// Their values, passed to the engine by name only.
+    secret_env: Vec<(String, String)>,
+    publish: Vec<PortMapping>,
+    with_claude_auth: bool,
+    claude_auth_volume: String,
+    /// settings.json written into the sandbox's Claude config at startup.
+    claude_settings: Option<String>,
+    /// Agents whose auth lives in a davy volume, besides Claude.
+    auth_volumes: Vec<&'static AuthVolume>,
+    /// Auth profile the auth volumes belong to.
+    auth_profile: Option<String>,
+    extra_docker_args: Vec<OsString>,
+    extra_env_args: Vec<OsString>,
+    sidecars: BTreeMap<String, SidecarConfig>,
+    compose_network: Option<String>,
+    network: Option<ManagedNetwork>,
+    add_hosts: Vec<HostEntry>,
+    hostname: String,
+    dns: Vec<IpAddr>,
+    dns_search: Vec<String>,
+    post_create: Option<String>,
+    /// Give the agent a writable copy of the host's skills.
+    copy_skills: bool,
+    /// Apply the dotfiles mounted at [`dotfiles::MOUNT_DIR`].
+    dotfiles: bool,
+    /// Trust the certificates mounted at [`certs::MOUNT_DIR`].
+    ca_certs: bool,
+    cmd: Vec<OsString>,
+}
+
+/// Options for a sandbox, mirroring the `davy run` flags. Config files and
+/// defaults are applied by [`build`](SandboxBuilder::build).
+///
+/// ```no_run
+/// use davy::SandboxBuilder;
+///
+/// let engine = davy::engine::select(None)?;
+/// let sandbox = SandboxBuilder::new()
+///     .project_dir("/work/app")
+///     .env("CI=1")
+///     .command(["npm", "test"])
+///     .build(engine.as_ref())?;
+/// let status = sandbox.run(engine.as_ref())?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SandboxBuilder {
+    project_dir: Option<PathBuf>,
+    name: Option<String>,
+    with_docker_sock: bool,
+    docker_sock: Option<PathBuf>,
+    rebuild: bool,
+    no_build: bool,
+    keep: bool,
+    reuse: bool,
+    tty: Option<bool>,
+    git_config: Option<bool>,
+    detach: bool,
+    timeout: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    resources: Resources,
+    constrained: bool,
+    hardened: bool,
+    no_network: bool,
+    no_lan: bool,
+    egress_proxy: bool,
+    allowed_domains: Vec<String>,
+    seccomp: Option<PathBuf>,
+    apparmor: Option<String>,
+    gpus: Option<Gpus>,
+    devices: Vec<String>,
+    backend: Backend,
+    project_mode: Option<ProjectMode>,
+    /// Empty path: look in the standard locations.
+    devcontainer: Option<PathBuf>,
+    /// Empty name: derive it from the project dir.
+    network_from_compose: Option<String>,
+    network: Option<ManagedNetwork>,
+    add_hosts: Vec<HostEntry>,
+    hostname: Option<String>,
+    dns: Vec<IpAddr>,
+    dns_search: Vec<String>,
+    expose_ssh: Option<u16>,
+    open_vscode: bool,
+    web_terminal: Option<WebTerminal>,
+    code_server: Option<u16>,
+    x11: bool,
+    wayland: bool,
+    ollama: bool,
+    history: bool,
+    persist_home: Option<HomeScope>,
+    project_state: bool,
+    cache_cargo: bool,
+    cache_node: bool,
+    cache_python: bool,
+    cache_go: bool,
+    cache_cc: bool,
+    caches: Vec<CacheMount>,
+    publish: Vec<PortMapping>,
+    extra_env: Vec<String>,
+    pass_env: Vec<String>,
+    env_files: Vec<PathBuf>,
+    env_commands: Vec<EnvCommand>,
+    with_pi_auth: bool,
+    pi_store: Option<AuthStore>,
+    with_codex_auth: bool,
+    codex_store: Option<AuthStore>,
+    with_gemini_auth: bool,
+    gemini_store: Option<AuthStore>,
+    with_claude_auth: bool,
+    claude_settings: Option<PathBuf>,
+    skills: Option<SkillsMode>,
+    dotfiles: Option<String>,
+    with_gh_auth: bool,
+    with_glab_auth: bool,
+    with_aws_auth: bool,
+    aws_profile: Option<String>,
+    with_gcloud_auth: bool,
+    with_azure_auth: bool,
+    with_kubeconfig: bool,
+    kube_context: Option<String>,
+    with_sops: bool,
+    proxy: bool,
+    ca_certs: Vec<PathBuf>,
+    with_npm_auth: bool,
+    with_pip_auth: bool,
+    with_cargo_auth: bool,
+    with_aider_auth: bool,
+    with_goose_auth: bool,
+    with_opencode_auth: bool,
+    opencode_store: Option<AuthStore>,
+    with_cursor_auth: bool,
+    with_copilot_auth: bool,
+    auth_mounts: Vec<AuthMount>,
+    auth_all: bool,
+    auth_read_only: bool,
+    auth_profile: Option<String>,
+    profile: Option<String>,
+    image: Option<String>,
+    dockerfile: Option<PathBuf>,
+    local_dockerfile: bool,
+    extra_docker_args: Vec<OsString>,
+    cmd: Vec<OsString>,
+}
+
+impl SandboxBuilder {
+    pub fn new() -> SandboxBuilder {
+        SandboxBuilder::default()
+    }
+
+    /// Project mounted at `/project` (default: the current directory).
+    pub fn project_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.project_dir = Some(dir.into());
+        self
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Mount the host docker socket.
+    pub fn docker(mut self, enabled: bool) -> Self {
+        self.with_docker_sock = enabled;
+        self
+    }
+
+    pub fn docker_sock(mut self, path: impl Into<PathBuf>) -> Self {
+        self.docker_sock = Some(path.into());
+        self
+    }
+
+    pub fn rebuild(mut self, enabled: bool) -> Self {
+        self.rebuild = enabled;
+        self
+    }
+
+    pub fn no_build(mut self, enabled: bool) -> Self {
+        self.no_build = enabled;
+        self
+    }
+
+    pub fn keep(mut self, enabled: bool) -> Self {
+        self.keep = enabled;
+        self
+    }
+
+    pub fn reuse(mut self, enabled: bool) -> Self {
+        self.reuse = enabled;
+        self
+    }
+
+    /// Start the sandbox in the background; [`run`](Sandbox::run) returns
+    /// once it is up.
+    pub fn detach(mut self, enabled: bool) -> Self {
+        self.detach = enabled;
+        self
+    }
+
+    /// Stop and remove the container if it is still running after
+    /// `timeout`; [`run`](Sandbox::run) then fails with
+    /// [`DavyError::TimedOut`].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Stop the container once it has had no exec or SSH sessions and no
+    /// real CPU use for `timeout`, even after davy has exited (`keep`,
+    /// `detach`).
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Memory limit in docker's format, e.g. `8g`.
+    pub fn memory(mut self, limit: impl Into<String>) -> Self {
+        self.resources.memory = Some(limit.into());
+        self
+    }
+
+    pub fn cpus(mut self, cpus: f64) -> Self {
+        self.resources.cpus = Some(cpus);
+        self
+    }
+
+    pub fn pids_limit(mut self, limit: u32) -> Self {
+        self.resources.pids_limit = Some(limit);
+        self
+    }
+
+    /// Size of `/dev/shm`, e.g. `2g`.
+    pub fn shm_size(mut self, size: impl Into<String>) -> Self {
+        self.resources.shm_size = Some(size.into());
+        self
+    }
+
+    pub fn ulimit(mut self, ulimit: Ulimit) -> Self {
+        self.resources.ulimits.push(ulimit);
+        self
+    }
+
+    /// Fill unset limits from [`Resources::constrained`].
+    pub fn constrained(mut self, enabled: bool) -> Self {
+        self.constrained = enabled;
+        self
+    }
+
+    /// Least-privilege mode: drop all capabilities, forbid privilege
+    /// escalation (so no `sudo`), and cap the number of processes.
+    pub fn hardened(mut self, enabled: bool) -> Self {
+        self.hardened = enabled;
+        self
+    }
+
+    /// Run with `--network none`: no network access at all.
+    pub fn no_network(mut self, enabled: bool) -> Self {
+        self.no_network = enabled;
+        self
+    }
+
+    /// Firewall the sandbox off from private address ranges and the host
+    /// gateway while keeping internet access.
+    pub fn no_lan(mut self, enabled: bool) -> Self {
+        self.no_lan = enabled;
+        self
+    }
+
+    /// Send all traffic through a filtering proxy sidecar that logs every
+    /// request; the sandbox itself is on an internal network.
+    pub fn egress_proxy(mut self, enabled: bool) -> Self {
+        self.egress_proxy = enabled;
+        self
+    }
+
+    /// Let the egress proxy through to `domain` and its subdomains. Implies
+    /// [`egress_proxy`](SandboxBuilder::egress_proxy).
+    pub fn allow_domain(mut self, domain: impl Into<String>) -> Self {
+        self.allowed_domains.push(domain.into());
+        self
+    }
+
+    /// Seccomp profile: a JSON file, `davy-restricted` (bundled), or
+    /// `unconfined`.
+    pub fn seccomp(mut self, profile: impl Into<PathBuf>) -> Self {
+        self.seccomp = Some(profile.into());
+        self
+    }
+
+    /// AppArmor profile, which must already be loaded on the docker host.
+    pub fn apparmor(mut self, profile: impl Into<String>) -> Self {
+        self.apparmor = Some(profile.into());
+        self
+    }
+
+    /// Pass GPUs through (needs the NVIDIA Container Toolkit).
+    pub fn gpus(mut self, gpus: Gpus) -> Self {
+        self.gpus = Some(gpus);
+        self
+    }
+
+    /// Pass a host device through, as `HOST[:CONTAINER[:PERMISSIONS]]`. The
+    /// device's group is added to the container user.
+    pub fn device(mut self, spec: impl Into<String>) -> Self {
+        self.devices.push(spec.into());
+        self
+    }
+
+    /// Force a TTY on or off instead of detecting one.
+    pub fn tty(mut self, enabled: bool) -> Self {
+        self.tty = Some(enabled);
+        self
+    }
+
+    /// Pass the host's git identity in and trust /project (default: on).
+    pub fn git_config(mut self, enabled: bool) -> Self {
+        self.git_config = Some(enabled);
+        self
+    }
+
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn project_mode(mut self, mode: ProjectMode) -> Self {
+        self.project_mode = Some(mode);
+        self
+    }
+
+    /// Use the project's devcontainer.json from the standard locations.
+    pub fn devcontainer(mut self) -> Self {
+        self.devcontainer = Some(PathBuf::new());
+        self
+    }
+
+    /// Use a devcontainer.json at `path`, relative to the project dir.
+    pub fn devcontainer_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.devcontainer = Some(path.into());
+        self
+    }
+
+    /// Join a shared davy network (created if needed), where sandboxes reach
+    /// each other by container name.
+    pub fn network(mut self, network: ManagedNetwork) -> Self {
+        self.network = Some(network);
+        self
+    }
+
+    /// Add an `/etc/hosts` entry. On Linux `host.docker.internal` is mapped
+    /// to the host unless overridden here.
+    pub fn add_host(mut self, entry: HostEntry) -> Self {
+        self.add_hosts.push(entry);
+        self
+    }
+
+    /// Serve the sandbox's terminal to the browser with ttyd, published on
+    /// `address` (127.0.0.1 unless it names another IP).
+    pub fn web_terminal(mut self, address: WebTerminal) -> Self {
+        self.web_terminal = Some(address);
+        self
+    }
+
+    /// Run code-server (installed into a volume on first use) and publish it
+    /// on 127.0.0.1:`port`.
+    pub fn code_server(mut self, port: u16) -> Self {
+        self.code_server = Some(port);
+        self
+    }
+
+    /// Forward the host's X11 display and xauth cookie.
+    pub fn x11(mut self, enabled: bool) -> Self {
+        self.x11 = enabled;
+        self
+    }
+
+    /// Point Ollama clients in the sandbox at an Ollama server on the host.
+    pub fn ollama(mut self, enabled: bool) -> Self {
+        self.ollama = enabled;
+        self
+    }
+
+    /// Keep shell history in a per-user volume so it survives across runs.
+    pub fn history(mut self, enabled: bool) -> Self {
+        self.history = enabled;
+        self
+    }
+
+    /// Mount a persistent volume over `/home/dev`, shared per user or per
+    /// project.
+    pub fn persist_home(mut self, scope: HomeScope) -> Self {
+        self.persist_home = Some(scope);
+        self
+    }
+
+    /// Keep Claude and Codex session history in a volume for the project.
+    pub fn project_state(mut self, enabled: bool) -> Self {
+        self.project_state = enabled;
+        self
+    }
+
+    /// Share Cargo's registry and git checkouts between sandboxes.
+    pub fn cache_cargo(mut self, enabled: bool) -> Self {
+        self.cache_cargo = enabled;
+        self
+    }
+
+    /// Share the npm cache, pnpm store, and Yarn cache between sandboxes.
+    pub fn cache_node(mut self, enabled: bool) -> Self {
+        self.cache_node = enabled;
+        self
+    }
+
+    /// Share the pip and uv caches between sandboxes.
+    pub fn cache_python(mut self, enabled: bool) -> Self {
+        self.cache_python = enabled;
+        self
+    }
+
+    /// Share Go's module and build caches between sandboxes.
+    pub fn cache_go(mut self, enabled: bool) -> Self {
+        self.cache_go = enabled;
+        self
+    }
+
+    /// Share ccache's and sccache's caches between sandboxes, using sccache
+    /// for rustc when the image has it.
+    pub fn cache_cc(mut self, enabled: bool) -> Self {
+        self.cache_cc = enabled;
+        self
+    }
+
+    /// Mount the shared cache volume `cache.name` at `cache.path`.
+    pub fn cache(mut self, cache: CacheMount) -> Self {
+        self.caches.push(cache);
+        self
+    }
+
+    /// Forward the host's Wayland socket.
+    pub fn wayland(mut self, enabled: bool) -> Self {
+        self.wayland = enabled;
+        self
+    }
+
+    /// Publish a container port, e.g. a dev server the agent starts.
+    pub fn publish(mut self, mapping: PortMapping) -> Self {
+        self.publish.push(mapping);
+        self
+    }
+
+    /// Container hostname (default: `davy-<project>`).
+    pub fn hostname(mut self, hostname: impl Into<String>) -> Self {
+        self.hostname = Some(hostname.into());
+        self
+    }
+
+    /// Use `server` for DNS instead of the engine's default resolver.
+    pub fn dns(mut self, server: IpAddr) -> Self {
+        self.dns.push(server);
+        self
+    }
+
+    /// Add a DNS search domain.
+    pub fn dns_search(mut self, domain: impl Into<String>) -> Self {
+        self.dns_search.push(domain.into());
+        self
+    }
+
+    /// Join the network of the project's running compose stack.
+    pub fn network_from_compose(mut self) -> Self {
+        self.network_from_compose = Some(String::new());
+        self
+    }
+
+    pub fn network_from_compose_project(mut self, project: impl Into<String>) -> Self {
+        self.network_from_compose = Some(project.into());
+        self
+    }
+
+    /// Publish host `port` to container port 22.
+    pub fn expose_ssh(mut self, port: u16) -> Self {
+        self.expose_ssh = Some(port);
+        self
+    }
+
+    /// Open VS Code on /project over SSH once sshd is ready. Exposes SSH on
+    /// port 222 unless [`expose_ssh`](SandboxBuilder::expose_ssh) is set.
+    pub fn vscode(mut self, enabled: bool) -> Self {
+        self.open_vscode = enabled;
+        self
+    }
+
+    /// Add a `KEY=VALUE` environment variable.
+    pub fn env(mut self, kv: impl Into<String>) -> Self {
+        self.extra_env.push(kv.into());
+        self
+    }
+
+    /// Forward a host environment variable by name.
+    pub fn pass_env(mut self, key: impl Into<String>) -> Self {
+        self.pass_env.push(key.into());
+        self
+    }
+
+    /// Set the variables in a docker-style env file; later `env` values win.
+    pub fn env_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.env_files.push(path.into());
+        self
+    }
+
+    /// Set a variable from a host command's output when the sandbox starts.
+    /// The value is handed to the engine through its environment, never its
+    /// arguments.
+    pub fn env_from_cmd(mut self, command: EnvCommand) -> Self {
+        self.env_commands.push(command);
+        self
+    }
+
+    pub fn auth_pi(mut self, enabled: bool) -> Self {
+        self.with_pi_auth = enabled;
+        self
+    }
+
+    /// Where Pi auth lives; implies `auth_pi`.
+    pub fn pi_store(mut self, store: AuthStore) -> Self {
+        self.pi_store = Some(store);
+        self
+    }
+
+    pub fn auth_codex(mut self, enabled: bool) -> Self {
+        self.with_codex_auth = enabled;
+        self
+    }
+
+    /// Where Codex auth lives; implies `auth_codex`.
+    pub fn codex_store(mut self, store: AuthStore) -> Self {
+        self.codex_store = Some(store);
+        self
+    }
+
+    pub fn auth_gemini(mut self, enabled: bool) -> Self {
+        self.with_gemini_auth = enabled;
+        self
+    }
+
+    /// Where Gemini auth lives; implies `auth_gemini`.
+    pub fn gemini_store(mut self, store: AuthStore) -> Self {
+        self.gemini_store = Some(store);
+        self
+    }
+
+    pub fn auth_claude(mut self, enabled: bool) -> Self {
+        self.with_claude_auth = enabled;
+        self
+    }
+
+    /// Write this settings.json into the sandbox's Claude config at startup.
+    pub fn claude_settings(mut self, path: impl Into<PathBuf>) -> Self {
+        self.claude_settings = Some(path.into());
+        self
+    }
+
+    /// How the host's `~/.agents/skills` reaches the sandbox.
+    pub fn skills(mut self, mode: SkillsMode) -> Self {
+        self.skills = Some(mode);
+        self
+    }
+
+    /// Apply dotfiles from a git URL or host directory at startup.
+    pub fn dotfiles(mut self, source: impl Into<String>) -> Self {
+        self.dotfiles = Some(source.into());
+        self
+    }
+
+    /// Mount the GitHub CLI config read-only and forward `GH_TOKEN`.
+    pub fn auth_gh(mut self, enabled: bool) -> Self {
+        self.with_gh_auth = enabled;
+        self
+    }
+
+    /// Mount the GitLab CLI config and forward `GITLAB_TOKEN`.
+    pub fn auth_glab(mut self, enabled: bool) -> Self {
+        self.with_glab_auth = enabled;
+        self
+    }
+
+    /// Mount `~/.aws` read-only and forward the AWS profile and region.
+    pub fn auth_aws(mut self, enabled: bool) -> Self {
+        self.with_aws_auth = enabled;
+        self
+    }
+
+    /// Set `AWS_PROFILE` in the sandbox (implies `auth_aws`).
+    pub fn aws_profile(mut self, profile: impl Into<String>) -> Self {
+        self.with_aws_auth = true;
+        self.aws_profile = Some(profile.into());
+        self
+    }
+
+    /// Mount the gcloud config and point Google client libraries at the
+    /// application-default credentials.
+    pub fn auth_gcloud(mut self, enabled: bool) -> Self {
+        self.with_gcloud_auth = enabled;
+        self
+    }
+
+    /// Mount the Azure CLI config. `auth_all` only includes it when the
+    /// config sets `[auth] all_azure`.
+    pub fn auth_azure(mut self, enabled: bool) -> Self {
+        self.with_azure_auth = enabled;
+        self
+    }
+
+    /// Mount a flattened copy of the host kubeconfig read-only.
+    pub fn kubeconfig(mut self, enabled: bool) -> Self {
+        self.with_kubeconfig = enabled;
+        self
+    }
+
+    /// Limit the mounted kubeconfig to one context (implies `kubeconfig`).
+    pub fn kube_context(mut self, context: impl Into<String>) -> Self {
+        self.with_kubeconfig = true;
+        self.kube_context = Some(context.into());
+        self
+    }
+
+    /// Mount the host's sops age key read-only and point `SOPS_AGE_KEY_FILE` at it.
+    pub fn sops(mut self, enabled: bool) -> Self {
+        self.with_sops = enabled;
+        self
+    }
+
+    /// Pass the host's `HTTP_PROXY`, `HTTPS_PROXY`, and `NO_PROXY`, in both
+    /// cases, to the image build and the sandbox.
+    pub fn proxy(mut self, enabled: bool) -> Self {
+        self.proxy = enabled;
+        self
+    }
+
+    /// Add a PEM CA certificate to the sandbox's trust store at startup.
+    pub fn ca_cert(mut self, path: impl Into<PathBuf>) -> Self {
+        self.ca_certs.push(path.into());
+        self
+    }
+
+    /// Mount the registry auth lines of the host `.npmrc`.
+    pub fn auth_npm(mut self, enabled: bool) -> Self {
+        self.with_npm_auth = enabled;
+        self
+    }
+
+    /// Mount the host pip config and the `.netrc` entries of its indexes,
+    /// and forward the pip and uv index variables.
+    pub fn auth_pip(mut self, enabled: bool) -> Self {
+        self.with_pip_auth = enabled;
+        self
+    }
+
+    /// Mount the host Cargo registry credentials and registry config.
+    pub fn auth_cargo(mut self, enabled: bool) -> Self {
+        self.with_cargo_auth = enabled;
+        self
+    }
+
+    /// Mount the host aider config and forward the aider and OpenAI variables.
+    pub fn auth_aider(mut self, enabled: bool) -> Self {
+        self.with_aider_auth = enabled;
+        self
+    }
+
+    /// Mount the host Goose config and forward its provider keys.
+    pub fn auth_goose(mut self, enabled: bool) -> Self {
+        self.with_goose_auth = enabled;
+        self
+    }
+
+    /// Mount the host OpenCode auth and config.
+    pub fn auth_opencode(mut self, enabled: bool) -> Self {
+        self.with_opencode_auth = enabled;
+        self
+    }
+
+    /// Where OpenCode auth lives; implies `auth_opencode`.
+    pub fn opencode_store(mut self, store: AuthStore) -> Self {
+        self.opencode_store = Some(store);
+        self
+    }
+
+    /// Keep cursor-agent auth in a persistent davy volume.
+    pub fn auth_cursor(mut self, enabled: bool) -> Self {
+        self.with_cursor_auth = enabled;
+        self
+    }
+
+    /// Mount the host Copilot CLI config and forward the GitHub tokens.
+    pub fn auth_copilot(mut self, enabled: bool) -> Self {
+        self.with_copilot_auth = enabled;
+        self
+    }
+
+    /// Mount another tool's config the way the auth flags do.
+    pub fn mount_auth(mut self, mount: AuthMount) -> Self {
+        self.auth_mounts.push(mount);
+        self
+    }
+
+    /// Keep auth volumes in a named profile, apart from the default ones.
+    pub fn auth_profile(mut self, profile: impl Into<String>) -> Self {
+        self.auth_profile = Some(profile.into());
+        self
+    }
+
+    /// Enable every auth mount, skipping missing ones.
+    pub fn auth_all(mut self, enabled: bool) -> Self {
+        self.auth_all = enabled;
+        self
+    }
+
+    /// Mount host Pi, Codex, and Gemini auth read-only.
+    pub fn auth_read_only(mut self, enabled: bool) -> Self {
+        self.auth_read_only = enabled;
+        self
+    }
+
+    pub fn profile(mut self, profile: impl Into<String>) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    pub fn image(mut self, image: impl Into<String>) -> Self {
+        self.image = Some(image.into());
+        self
+    }
+
+    pub fn dockerfile(mut self, path: impl Into<PathBuf>) -> Self {
+        self.dockerfile = Some(path.into());
+        self
+    }
+
+    /// Look for the Dockerfile in the current directory.
+    pub fn local_dockerfile(mut self, enabled: bool) -> Self {
+        self.local_dockerfile = enabled;
+        self
+    }
+
+    /// Extra `docker run` arguments.
+    pub fn docker_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.extra_docker_args
+            .extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Command to run instead of bash.
+    pub fn command<I, S>(mut self, cmd: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.cmd = cmd.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Resolve the options against config files and the engine.
+    pub fn build(self, engine: &dyn Engine) -> Result<Sandbox> {
+        self.build_settings(engine).inspect_err(|_| {
+            // Nothing will mount the credentials written so far.
+            drop(PrivateFiles::remove_on_drop(true));
+        })
+    }
+
+    fn build_settings(self, engine: &dyn Engine) -> Result<Sandbox> {
+        let mut args = self;
+        let host_uid = get_current_uid();
+        let host_gid = get_current_gid();
+
+        let project_dir = resolve_project_dir(args.project_dir)?;
+        let config = load_config(&project_dir, args.profile.as_deref())?;
+        let name = args
+            .name
+            .take()
+            .unwrap_or_else(|| default_container_name(&project_dir));
+        set_private_files_owner(&name);
+        let sandbox_env = collect_env(
+            &config,
+            &project_dir,
+            &args.pass_env,
+            &args.env_files,
+            args.extra_env,
+        )?;
+
+        let endpoint = remote::docker_endpoint(engine);
+        tracing::debug!(
+            "docker endpoint: {}",
+            endpoint.as_deref().unwrap_or("unknown")
+        );
+        let remote_host = endpoint
+            .as_deref()
+            .filter(|endpoint| remote::is_remote_endpoint(endpoint));
+        let requested_mode = args
+            .project_mode
+            .or(config.project_mode)
+            .unwrap_or(ProjectMode::Auto);
+        let project_mode = requested_mode.resolve(remote_host.is_some());
+        tracing::debug!("project mode: {project_mode:?} (requested {requested_mode:?})");
+        if let Some(host) = remote_host {
+            match requested_mode {
+                ProjectMode::Auto => note!(
+                    "docker daemon at {host} is remote; copying the project instead of bind-mounting it (see --project-mode)."
+                ),
+                ProjectMode::Bind => eprintln!(
+                    "davy: warning: docker daemon at {host} is remote; {} is bind-mounted from the remote host's filesystem.",
+                    project_dir.display()
+                ),
+                ProjectMode::Copy | ProjectMode::Volume => {}
+            }
+        }
+        if args.detach && project_mode == ProjectMode::Copy {
+            bail!(
+                "--detach cannot copy the project back after the run; use --project-mode volume or bind"
+            );
+        }
+        let hardened = args.hardened || config.hardened.unwrap_or(false);
+        let no_network = args.no_network || config.no_network.unwrap_or(false);
+        if no_network {
+            if !config.sidecars.is_empty() {
+                bail!("sidecars need a network; they cannot be used with --no-network");
+            }
+            if args.network_from_compose.is_some() {
+                bail!("--network-from-compose cannot be used with --no-network");
+            }
+        }
+        let managed_network =
+            match network::take_managed(&mut args.extra_docker_args).or(args.network) {
+                Some(network) => Some(network),
+                None => config
+                    .network
+                    .as_deref()
+                    .map(|network| network.parse().map_err(anyhow::Error::msg))
+                    .transpose()
+                    .context("invalid network in config")?,
+            };
+        if let Some(network) = &managed_network
+            && no_network
+        {
+            bail!("--network {network} cannot be used with --no-network");
+        }
+        // Nothing to firewall without a network.
+        let no_lan = (args.no_lan || config.no_lan.unwrap_or(false)) && !no_network;
+        let egress = if args.egress_proxy
+            || !args.allowed_domains.is_empty()
+            || config.egress.proxy.unwrap_or(false)
+            || !config.egress.allow.is_empty()
+        {
+            if no_network {
+                bail!("--egress-proxy cannot be used with --no-network, which blocks all traffic");
+            }
+            if args.network_from_compose.is_some() {
+                bail!("--egress-proxy cannot be used with --network-from-compose");
+            }
+            if let Some(network) = &managed_network {
+                bail!("--egress-proxy cannot be used with --network {network}");
+            }
+            let mut allow = config.egress.allow;
+            allow.extend(args.allowed_domains);
+            Some(Egress::new(allow, config.egress.image)?)
+        } else {
+            None
+        };
+        let defaults = if args.constrained || config.constrained.unwrap_or(false) {
+            Resources::constrained()
+        } else if hardened {
+            Resources {
+                pids_limit: Resources::constrained().pids_limit,
+                ..Resources::default()
+            }
+        } else {
+            Resources::default()
+        };
+        let resources = Resources {
+            memory: args.resources.memory.or(config.memory).or(defaults.memory),
+            cpus: args.resources.cpus.or(config.cpus).or(defaults.cpus),
+            pids_limit: args
+                .resources
+                .pids_limit
+                .or(config.pids_limit)
+                .or(defaults.pids_limit),
+            shm_size: args.resources.shm_size.or(config.shm_size),
+            ulimits: config
+                .ulimits
+                .iter()
+                .map(|ulimit| ulimit.parse().map_err(anyhow::Error::msg))
+                .chain(args.resources.ulimits.into_iter().map(Ok))
+                .collect::<Result<_>>()
+                .context("invalid ulimit in config")?,
+        };
+        resources.validate()?;
+        tracing::debug!("resource limits: {resources:?}");
+        if args.detach && args.timeout.is_some() {
+            bail!("--timeout needs davy to wait on the sandbox; it cannot be used with --detach");
+        }
+        let project_volume = remote::project_volume_name(host_uid, &project_dir);
+
+        let home = home_dir()?;
+        let vm = endpoint
+            .as_deref()
+            .and_then(|endpoint| LimaVm::from_endpoint(endpoint, &home));
+        if let Some(vm) = &vm
+            && project_mode == ProjectMode::Bind
+            && !lima::is_shared(&project_dir, &vm.shared_paths(&home))
+        {
+            eprintln!(
+                "davy: warning: {} is not shared with the {} VM '{}', so /project will be empty. Add it to the VM's mounts or use --project-mode copy.",
+                project_dir.display(),
+                vm.kind.name(),
+                vm.instance
+            );
+        }
+
+        let mut devcontainer = match &args.devcontainer {
+            Some(path) => {
+                let path = devcontainer::find(
+                    &project_dir,
+                    Some(path.as_path()).filter(|path| !path.as_os_str().is_empty()),
+                )?;
+                note!("using dev container definition {}.", path.display());
+                Some(devcontainer::load(&path, &project_dir)?)
+            }
+            None => None,
+        };
+
+        // --image and --dockerfile still win over the dev container's image.
+        let cli_image = args.image.is_some() || args.dockerfile.is_some();
+        let devcontainer_image = devcontainer
+            .as_mut()
+            .and_then(|dc| dc.image.take())
+            .filter(|_| !cli_image);
+        let (mut build, skip_build) = match devcontainer_image {
+            Some(ImageSource::Image(image)) => (
+                BuildSettings {
+                    dockerfile: PathBuf::new(),
+                    context_dir: None,
+                    image,
+                    build_args: Vec::new(),
+                    host_uid,
+                    host_gid,
+                },
+                true,
+            ),
+            Some(ImageSource::Dockerfile {
+                dockerfile,
+                context_dir,
+                build_args,
+            }) => (
+                BuildSettings {
+                    dockerfile,
+                    context_dir: Some(context_dir),
+                    image: format!(
+                        "davy-devcontainer-{}:latest",
+                        remote::project_hash(&project_dir)
+                    ),
+                    build_args,
+                    host_uid,
+                    host_gid,
+                },
+                false,
+            ),
+            None => (
+                build_settings(
+                    args.image.or(config.image),
+                    args.dockerfile.or(config.dockerfile),
+                    args.local_dockerfile,
+                )?,
+                false,
+            ),
+        };
+
+        let forward_proxy = args.proxy || config.proxy.unwrap_or(false);
+        if forward_proxy {
+            build.forward_proxy();
+        }
+
+        let auth_all = args.auth_all || config.auth.all.unwrap_or(false);
+        let auth_read_only = args.auth_read_only || config.auth.read_only.unwrap_or(false);
+        let pi_auth = auth::resolve_store(
+            args.pi_store,
+            config.auth.pi_store,
+            args.with_pi_auth || config.auth.pi.unwrap_or(false) || auth_all,
+        );
+        let codex_auth = auth::resolve_store(
+            args.codex_store,
+            config.auth.codex_store,
+            args.with_codex_auth || config.auth.codex.unwrap_or(false) || auth_all,
+        );
+        let gemini_auth = auth::resolve_store(
+            args.gemini_store,
+            config.auth.gemini_store,
+            args.with_gemini_auth || config.auth.gemini.unwrap_or(false) || auth_all,
+        );
+        let with_claude_auth =
+            args.with_claude_auth || config.auth.claude.unwrap_or(false) || auth_all;
+        let with_gh_auth = args.with_gh_auth || config.auth.gh.unwrap_or(false) || auth_all;
+        let with_glab_auth = args.with_glab_auth || config.auth.glab.unwrap_or(false) || auth_all;
+        let aws_profile = args.aws_profile.or(config.auth.aws_profile);
+        let with_aws_auth = args.with_aws_auth
+            || config.auth.aws.unwrap_or(false)
+            || aws_profile.is_some()
+            || auth_all;
+        let with_gcloud_auth =
+            args.with_gcloud_auth || config.auth.gcloud.unwrap_or(false) || auth_all;
+        // The Azure token cache covers every subscription, so `--auth-all`
+        // only mounts it when asked to.
+        let with_azure_auth = args.with_azure_auth
+            || config.auth.azure.unwrap_or(false)
+            || (auth_all && config.auth.all_azure.unwrap_or(false));
+        let with_npm_auth = args.with_npm_auth || config.auth.npm.unwrap_or(false) || auth_all;
+        let with_pip_auth = args.with_pip_auth || config.auth.pip.unwrap_or(false) || auth_all;
+        let with_cargo_auth =
+            args.with_cargo_auth || config.auth.cargo.unwrap_or(false) || auth_all;
+        let with_aider_auth =
+            args.with_aider_auth || config.auth.aider.unwrap_or(false) || auth_all;
+        let with_goose_auth =
+            args.with_goose_auth || config.auth.goose.unwrap_or(false) || auth_all;
+        let opencode_auth = auth::resolve_store(
+            args.opencode_store,
+            config.auth.opencode_store,
+            args.with_opencode_auth || config.auth.opencode.unwrap_or(false) || auth_all,
+        );
+        let with_cursor_auth =
+            args.with_cursor_auth || config.auth.cursor.unwrap_or(false) || auth_all;
+        let with_copilot_auth =
+            args.with_copilot_auth || config.auth.copilot.unwrap_or(false) || auth_all;
+        let auth_mounts = config
+            .auth
+            .mounts
+            .iter()
+            .map(|spec| spec.parse().map_err(anyhow::Error::msg))
+            .chain(args.auth_mounts.into_iter().map(Ok))
+            .collect::<Result<Vec<AuthMount>>>()
+            .context("invalid auth mount in config")?;
+        let allow_missing_auth = auth_all;
+
+        let auth_profile = args
+            .auth_profile
+            .or(config.auth.profile)
+            .map(|profile| auth::parse_profile(&profile))
+            .transpose()
+            .map_err(anyhow::Error::msg)
+            .context("invalid auth profile")?;
+        let claude_auth_volume = claude_auth_volume(host_uid, auth_profile.as_deref());
+        let claude_settings = args
+            .claude_settings
+            .or(config.claude_settings)
+            .map(|path| read_claude_settings(&path))
+            .transpose()?;
+
+        if remote_host.is_some()
+            && (pi_auth == Some(AuthStore::Host)
+                || codex_auth == Some(AuthStore::Host)
+                || gemini_auth == Some(AuthStore::Host)
+                || with_gh_auth
+                || with_glab_auth
+                || with_aws_auth
+                || with_gcloud_auth
+                || with_azure_auth
+                || with_pip_auth
+                || with_cargo_auth
+                || with_aider_auth
+                || with_goose_auth
+                || opencode_auth == Some(AuthStore::Host)
+                || with_copilot_auth
+                || !auth_mounts.is_empty()
+                || !config.mounts.is_empty())
+        {
+            eprintln!(
+                "davy: warning: auth and config mounts refer to paths on the remote docker host."
+            );
+        }
+
+        let mut extra_env_args = Vec::new();
+        if forward_proxy {
+            // By name, so credentials in proxy URLs stay out of the arguments.
+            for (key, _) in proxy::host_vars() {
+                push_env(&mut extra_env_args, key);
+            }
+        }
+        if let Some(gpus) = args.gpus {
+            devices::check_nvidia_toolkit(engine, remote_host.is_none() && vm.is_none())?;
+            push_env(
+                &mut extra_env_args,
+                format!("NVIDIA_VISIBLE_DEVICES={}", gpus.visible_devices()),
+            );
+        }
+        let devcontainer_env = devcontainer
+            .as_mut()
+            .map(|dc| std::mem::take(&mut dc.env))
+            .unwrap_or_default();
+        let egress_env = egress
+            .as_ref()
+            .map(|_| egress::sandbox_env(config.sidecars.keys()))
+            .unwrap_or_default();
+        let git_env = if args.git_config.or(config.git_config).unwrap_or(true) {
+            git::sandbox_env(&project_dir)
+        } else {
+            Vec::new()
+        };
+        for kv in git_env
+            .into_iter()
+            .chain(sidecars::sandbox_env(&config.sidecars))
+            .chain(egress_env)
+            .chain(devcontainer_env)
+            .chain(sandbox_env)
+        {
+            push_env(&mut extra_env_args, kv);
+        }
+
+        let mut extra_docker_args: Vec<OsString> =
+            config.docker_args.iter().map(OsString::from).collect();
+        for mount in &config.mounts {
+            let source = mount.split(':').next().unwrap_or_default();
+            if !Path::new(source).exists() {
+                return Err(DavyError::MountSourceMissing {
+                    label: "config".to_owned(),
+                    path: PathBuf::from(source),
+                }
+                .into());
+            }
+            push_volume(&mut extra_docker_args, mount);
+        }
+        if let Some(dc) = &devcontainer {
+            for mount in &dc.mounts {
+                extra_docker_args.push(OsString::from("--mount"));
+                extra_docker_args.push(OsString::from(mount));
+            }
+            let isolated = no_network || egress.is_some();
+            if isolated && !dc.forward_ports.is_empty() {
+                eprintln!(
+                    "davy: warning: not forwarding devcontainer ports with {}.",
+                    if no_network {
+                        "--no-network"
+                    } else {
+                        "--egress-proxy"
+                    }
+                );
+            }
+            for port in dc.forward_ports.iter().filter(|_| !isolated) {
+                extra_docker_args.push(OsString::from("-p"));
+                extra_docker_args.push(OsString::from(format!("127.0.0.1:{port}:{port}")));
+            }
+        }
+        extra_docker_args.extend(args.extra_docker_args);
+        let x11 = args.x11 || config.x11.unwrap_or(false);
+        let wayland = args.wayland || config.wayland.unwrap_or(false);
+        if (x11 || wayland) && (remote_host.is_some() || vm.is_some()) {
+            bail!("--x11 and --wayland need the container engine on this machine");
+        }
+        let displays = [
+            x11.then(display::x11),
+            wayland.then(|| display::wayland(host_uid)),
+        ];
+        for passthrough in displays.into_iter().flatten() {
+            let passthrough = passthrough?;
+            for volume in passthrough.volumes {
+                push_volume(&mut extra_docker_args, volume);
+            }
+            for value in passthrough.env {
+                push_env(&mut extra_env_args, value);
+            }
+        }
+
+        let mut add_hosts = config
+            .add_hosts
+            .iter()
+            .map(|entry| entry.parse().map_err(anyhow::Error::msg))
+            .chain(args.add_hosts.into_iter().map(Ok))
+            .collect::<Result<Vec<HostEntry>>>()
+            .context("invalid add_hosts entry in config")?;
+        let hostname = match args.hostname.or(config.hostname) {
+            Some(hostname) => network::parse_hostname(&hostname).map_err(anyhow::Error::msg)?,
+            None => default_hostname(&project_dir),
+        };
+        let mut dns = config.dns;
+        dns.extend(args.dns);
+        let dns_search = config
+            .dns_search
+            .iter()
+            .chain(&args.dns_search)
+            .map(|domain| network::parse_search_domain(domain).map_err(anyhow::Error::msg))
+            .collect::<Result<Vec<_>>>()?;
+        // Docker Desktop and Podman map the host alias themselves; Docker
+        // Engine on Linux needs `host-gateway`.
+        let maps_host_alias = engine.maps_host_alias()
+            || add_hosts
+                .iter()
+                .any(|entry| entry.name == network::HOST_ALIAS)
+            || extra_docker_args
+                .iter()
+                .any(|arg| arg.to_string_lossy().contains(network::HOST_ALIAS));
+        if cfg!(target_os = "linux") && !maps_host_alias && !no_network && egress.is_none() {
+            tracing::debug!("mapping {} to the host gateway", network::HOST_ALIAS);
+            add_hosts.push(HostEntry::host_gateway());
+        }
+        let home_volume = args
+            .persist_home
+            .or(config.persist_home)
+            .map(|scope| home::volume_name(host_uid, scope, &project_dir));
+        let env_commands = config
+            .env_from_cmd
+            .iter()
+            .map(|(key, command)| EnvCommand {
+                key: key.clone(),
+                command: command.clone(),
+            })
+            .chain(args.env_commands.iter().cloned())
+            .collect::<Vec<_>>();
+        let secrets = config
+            .secrets
+            .iter()
+            .map(|(key, reference)| {
+                let reference = reference
+                    .parse()
+                    .map_err(anyhow::Error::msg)
+                    .with_context(|| format!("invalid secret {key} in config"))?;
+                Ok((key.clone(), reference))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut cache_volumes = Vec::new();
+        let mut cache_scripts = Vec::new();
+        for (enabled, cache) in [
+            (
+                args.cache_cargo || config.cache.cargo.unwrap_or(false),
+                &caches::CARGO,
+            ),
+            (
+                args.cache_node || config.cache.node.unwrap_or(false),
+                &caches::NODE,
+            ),
+            (
+                args.cache_python || config.cache.python.unwrap_or(false),
+                &caches::PYTHON,
+            ),
+            (
+                args.cache_go || config.cache.go.unwrap_or(false),
+                &caches::GO,
+            ),
+            (
+                args.cache_cc || config.cache.cc.unwrap_or(false),
+                &caches::CC,
+            ),
+        ] {
+            if enabled {
+                cache_volumes.extend(cache.volumes(host_uid));
+                for kv in cache.env {
+                    push_env(&mut extra_env_args, (*kv).to_owned());
+                }
+                cache_scripts.extend(cache.script);
+            }
+        }
+        let custom_caches = config
+            .cache
+            .mounts
+            .iter()
+            .map(|cache| cache.parse::<CacheMount>().map_err(anyhow::Error::msg))
+            .chain(args.caches.iter().cloned().map(Ok))
+            .collect::<Result<Vec<_>>>()
+            .context("invalid cache in config")?;
+        for cache in custom_caches {
+            let volume = caches::volume_name(host_uid, &cache.name);
+            cache_volumes.retain(|(_, path)| *path != cache.path);
+            cache_volumes.push((volume, cache.path));
+        }
+        let history_volume =
+            (args.history || config.history.unwrap_or(false)).then(|| history_volume(host_uid));
+        if history_volume.is_some() {
+            push_env(
+                &mut extra_env_args,
+                format!("HISTFILE={HISTORY_DIR}/history"),
+            );
+        }
+        if args.ollama || config.ollama.unwrap_or(false) {
+            if no_network || egress.is_some() || no_lan {
+                bail!(
+                    "--ollama reaches the host, which --no-network, --egress-proxy, and --no-lan do not allow"
+                );
+            }
+            let (host, port) = ollama::listen_addr(env::var("OLLAMA_HOST").ok().as_deref());
+            if remote_host.is_some() || vm.is_some() {
+                eprintln!(
+                    "davy: warning: --ollama expects Ollama on the engine's host, which davy cannot check."
+                );
+            } else if !ollama::answers(&host, port) {
+                bail!(
+                    "--ollama found no Ollama server at {host}:{port}; start one with 'ollama serve'"
+                );
+            } else if cfg!(target_os = "linux")
+                && !engine.maps_host_alias()
+                && ollama::loopback_only(port) == Some(true)
+            {
+                eprintln!(
+                    "davy: warning: Ollama only listens on loopback, which the sandbox cannot reach; restart it with OLLAMA_HOST=0.0.0.0."
+                );
+            }
+            for kv in ollama::sandbox_env(port) {
+                push_env(&mut extra_env_args, kv);
+            }
+        }
+        let mut auth_volumes = Vec::new();
+        match pi_auth {
+            Some(AuthStore::Host) => {
+                add_bind_mount(
+                    &mut extra_docker_args,
+                    &home.join(".pi/agent"),
+                    "/home/dev/.pi/agent",
+                    "Pi auth",
+                    auth_read_only,
+                    allow_missing_auth,
+                )?;
+            }
+            Some(AuthStore::Volume) => auth_volumes.push(&auth::PI),
+            None => {}
+        }
+        let codex_home = match codex_auth {
+            Some(AuthStore::Host) => add_bind_mount(
+                &mut extra_docker_args,
+                &home.join(".codex"),
+                "/home/dev/.codex",
+                "Codex auth",
+                auth_read_only,
+                allow_missing_auth,
+            )?,
+            Some(AuthStore::Volume) => {
+                auth_volumes.push(&auth::CODEX);
+                true
+            }
+            None => false,
+        };
+        if codex_home {
+            push_env(
+                &mut extra_env_args,
+                "CODEX_HOME=/home/dev/.codex".to_owned(),
+            );
+        }
+        match gemini_auth {
+            Some(AuthStore::Host) => {
+                add_bind_mount(
+                    &mut extra_docker_args,
+                    &home.join(".gemini"),
+                    "/home/dev/.gemini",
+                    "Gemini auth",
+                    auth_read_only,
+                    allow_missing_auth,
+                )?;
+            }
+            Some(AuthStore::Volume) => auth_volumes.push(&auth::GEMINI),
+            None => {}
+        }
+        let state_volume = (args.project_state || config.project_state.unwrap_or(false))
+            .then(|| state::volume_name(host_uid, &project_dir));
+        // Without the state volume, only persistent homes can hold stale links.
+        let persistent = state_volume.is_some() || home_volume.is_some();
+        let mut state_dirs = Vec::new();
+        if persistent || with_claude_auth {
+            state_dirs.extend(state::CLAUDE_DIRS);
+        }
+        if codex_auth == Some(AuthStore::Volume) || (persistent && codex_auth.is_none()) {
+            state_dirs.extend(state::CODEX_DIRS);
+        }
+        if auth_read_only && [codex_auth, gemini_auth].contains(&Some(AuthStore::Host)) {
+            note!(
+                "Codex and Gemini can't save refreshed tokens to read-only auth; \
+                 log in again on the host when they expire, or keep auth in a volume."
+            );
+        }
+        if with_gh_auth {
+            let gh_config = env::var_os("GH_CONFIG_DIR")
+                .filter(|dir| !dir.is_empty())
+                .map_or_else(|| home.join(".config/gh"), PathBuf::from);
+            add_bind_mount(
+                &mut extra_docker_args,
+                &gh_config,
+                "/home/dev/.config/gh",
+                "GitHub CLI auth",
+                true,
+                allow_missing_auth,
+            )?;
+            forward_host_env(&mut extra_env_args, &["GH_TOKEN", "GH_ENTERPRISE_TOKEN"]);
+        }
+        if with_glab_auth {
+            // glab rewrites its config when OAuth tokens refresh.
+            let glab_config = env::var_os("GLAB_CONFIG_DIR")
+                .filter(|dir| !dir.is_empty())
+                .map_or_else(|| home.join(".config/glab-cli"), PathBuf::from);
+            add_bind_mount(
+                &mut extra_docker_args,
+                &glab_config,
+                "/home/dev/.config/glab-cli",
+                "GitLab CLI auth",
+                false,
+                allow_missing_auth,
+            )?;
+            forward_host_env(&mut extra_env_args, &["GITLAB_TOKEN", "GITLAB_HOST"]);
+        }
+        if with_aws_auth {
+            let aws_dir = home.join(".aws");
+            let mounted = add_bind_mount(
+                &mut extra_docker_args,
+                &aws_dir,
+                "/home/dev/.aws",
+                "AWS auth",
+                true,
+                allow_missing_auth,
+            )?;
+            match &aws_profile {
+                Some(profile) => {
+                    if mounted && !aws_profile_exists(&aws_dir, profile) {
+                        eprintln!(
+                            "davy: warning: AWS profile '{profile}' is not in {}/config or credentials.",
+                            aws_dir.display()
+                        );
+                    }
+                    push_env(&mut extra_env_args, format!("AWS_PROFILE={profile}"));
+                }
+                None => forward_host_env(&mut extra_env_args, &["AWS_PROFILE"]),
+            }
+            forward_host_env(&mut extra_env_args, &["AWS_REGION", "AWS_DEFAULT_REGION"]);
+        }
+        if with_gcloud_auth {
+            // gcloud refreshes tokens and writes logs into its config dir.
+            let gcloud_config = env::var_os("CLOUDSDK_CONFIG")
+                .filter(|dir| !dir.is_empty())
+                .map_or_else(|| home.join(".config/gcloud"), PathBuf::from);
+            let mounted = add_bind_mount(
+                &mut extra_docker_args,
+                &gcloud_config,
+                GCLOUD_CONFIG_TARGET,
+                "gcloud auth",
+                false,
+                allow_missing_auth,
+            )?;
+            if mounted {
+                push_env(
+                    &mut extra_env_args,
+                    format!("CLOUDSDK_CONFIG={GCLOUD_CONFIG_TARGET}"),
+                );
+            }
+            // A key file named by the host wins over the gcloud ADC file.
+            let host_credentials = env::var_os("GOOGLE_APPLICATION_CREDENTIALS")
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from);
+            if let Some(path) = host_credentials {
+                if add_file_bind_mount(
+                    &mut extra_docker_args,
+                    &path,
+                    GCP_CREDENTIALS_TARGET,
+                    "GOOGLE_APPLICATION_CREDENTIALS",
+                    true,
+                    allow_missing_auth,
+                )? {
+                    push_env(
+                        &mut extra_env_args,
+                        format!("GOOGLE_APPLICATION_CREDENTIALS={GCP_CREDENTIALS_TARGET}"),
+                    );
+                }
+            } else if mounted
+                && gcloud_config
+                    .join("application_default_credentials.json")
+                    .is_file()
+            {
+                push_env(
+                    &mut extra_env_args,
+                    format!(
+                        "GOOGLE_APPLICATION_CREDENTIALS={GCLOUD_CONFIG_TARGET}/application_default_credentials.json"
+                    ),
+                );
+            }
+            forward_host_env(
+                &mut extra_env_args,
+                &["CLOUDSDK_CORE_PROJECT", "GOOGLE_CLOUD_PROJECT"],
+            );
+        }
+        let kube_context = args.kube_context.or(config.auth.kube_context);
+        if args.with_kubeconfig || config.auth.kubeconfig.unwrap_or(false) || kube_context.is_some()
+        {
+            // The copy is written on this machine, so the daemon must see it.
+            if remote_host.is_some() || vm.is_some() {
+                bail!("--kubeconfig needs the container engine on this machine");
+            }
+            let kubeconfig = k8s::sandbox_kubeconfig(kube_context.as_deref())?;
+            for server in k8s::loopback_servers(&kubeconfig) {
+                eprintln!(
+                    "davy: warning: kubeconfig server {server} is on this machine's loopback, which the sandbox cannot reach."
+                );
+            }
+            let file_name = match &kube_context {
+                Some(context) => format!(
+                    "kubeconfig-{}",
+                    context
+                        .chars()
+                        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+                        .collect::<String>()
+                ),
+                None => "kubeconfig".to_owned(),
+            };
+            let path = write_private_file(&file_name, kubeconfig.to_string().as_bytes())?;
+            push_volume(
+                &mut extra_docker_args,
+                format!("{}:{KUBECONFIG_TARGET}:ro", path.display()),
+            );
+            push_env(
+                &mut extra_env_args,
+                format!("KUBECONFIG={KUBECONFIG_TARGET}"),
+            );
+        }
+        let ca_certs = config
+            .ca_certs
+            .iter()
+            .chain(&args.ca_certs)
+            .collect::<Vec<_>>();
+        for (index, path) in ca_certs.iter().enumerate() {
+            // Bind mount sources must be absolute.
+            let path = &std::path::absolute(path)
+                .with_context(|| format!("failed to resolve {}", path.display()))?;
+            add_file_bind_mount(
+                &mut extra_docker_args,
+                path,
+                &certs::target(index, path),
+                "CA certificate",
+                true,
+                false,
+            )?;
+            certs::check(path)?;
+        }
+        if args.with_sops || config.auth.sops.unwrap_or(false) {
+            if remote_host.is_some() || vm.is_some() {
+                bail!("--sops needs the container engine on this machine");
+            }
+            let key_file = match env::var_os("SOPS_AGE_KEY_FILE").filter(|file| !file.is_empty()) {
+                Some(file) => PathBuf::from(file),
+                None => sops_age_key_file(&home),
+            };
+            let source = if key_file.is_file() {
+                key_file
+            } else if let Some(key) = env::var_os("SOPS_AGE_KEY").filter(|key| !key.is_empty()) {
+                write_private_file("sops-age-keys.txt", key.as_encoded_bytes())?
+            } else {
+                bail!(
+                    "--sops found no age key at {}; set SOPS_AGE_KEY_FILE or SOPS_AGE_KEY",
+                    key_file.display()
+                );
+            };
+            push_volume(
+                &mut extra_docker_args,
+                format!("{}:{SOPS_AGE_KEY_TARGET}:ro", source.display()),
+            );
+            push_env(
+                &mut extra_env_args,
+                format!("SOPS_AGE_KEY_FILE={SOPS_AGE_KEY_TARGET}"),
+            );
+        }
+        if with_npm_auth {
+            let npmrc = env::var_os("NPM_CONFIG_USERCONFIG")
+                .filter(|path| !path.is_empty())
+                .map_or_else(|| home.join(".npmrc"), PathBuf::from);
+            add_generated_auth_file(
+                &mut extra_docker_args,
+                &mut extra_env_args,
+                &npmrc,
+                "npm auth",
+                remote_host.is_some() || vm.is_some(),
+                allow_missing_auth,
+                |content| {
+                    let (auth, vars) = registries::npmrc_auth(content);
+                    Ok(GeneratedAuth {
+                        file_name: "npmrc",
+                        content: auth,
+                        target: "/home/dev/.npmrc",
+                        forward_env: vars,
+                    })
+                },
+            )?;
+        }
+        if with_pip_auth {
+            let pip_conf = env::var_os("PIP_CONFIG_FILE")
+                .filter(|path| !path.is_empty())
+                .map_or_else(|| home.join(".config/pip/pip.conf"), PathBuf::from);
+            let index_vars = registries::PYTHON_INDEX_VARS
+                .iter()
+                .filter_map(|key| env::var(key).ok())
+                .collect::<Vec<_>>();
+            let mounted_conf = add_file_bind_mount(
+                &mut extra_docker_args,
+                &pip_conf,
+                "/home/dev/.config/pip/pip.conf",
+                "pip config",
+                true,
+                true,
+            )?;
+            forward_host_env(&mut extra_env_args, &registries::PYTHON_INDEX_VARS);
+            let hosts = registries::url_hosts(&format!(
+                "{}\n{}",
+                fs::read_to_string(&pip_conf).unwrap_or_default(),
+                index_vars.join("\n")
+            ));
+            let netrc = home.join(".netrc");
+            let mounted_netrc = !hosts.is_empty()
+                && netrc.is_file()
+                && add_generated_auth_file(
+                    &mut extra_docker_args,
+                    &mut extra_env_args,
+                    &netrc,
+                    "pip index credentials",
+                    remote_host.is_some() || vm.is_some(),
+                    true,
+                    |content| {
+                        Ok(GeneratedAuth {
+                            file_name: "netrc",
+                            content: registries::netrc_for(content, &hosts),
+                            target: "/home/dev/.netrc",
+                            forward_env: Vec::new(),
+                        })
+                    },
+                )?;
+            if !mounted_conf && !mounted_netrc && index_vars.is_empty() {
+                if !allow_missing_auth {
+                    bail!(
+                        "--auth-pip found no pip config at {}, no matching ~/.netrc entries, and no index variables",
+                        pip_conf.display()
+                    );
+                }
+                eprintln!("davy: warning: no pip config or index credentials found; skipping.");
+            }
+        }
+        if with_cargo_auth {
+            let cargo_home = env::var_os("CARGO_HOME")
+                .filter(|dir| !dir.is_empty())
+                .map_or_else(|| home.join(".cargo"), PathBuf::from);
+            // Cargo still reads the pre-1.39 names.
+            let credentials = ["credentials.toml", "credentials"]
+                .into_iter()
+                .map(|name| cargo_home.join(name))
+                .find(|path| path.is_file())
+                .unwrap_or_else(|| cargo_home.join("credentials.toml"));
+            let mounted_credentials = add_file_bind_mount(
+                &mut extra_docker_args,
+                &credentials,
+                "/home/dev/.cargo/credentials.toml",
+                "Cargo credentials",
+                true,
+                true,
+            )?;
+            let cargo_config = ["config.toml", "config"]
+                .into_iter()
+                .map(|name| cargo_home.join(name))
+                .find(|path| path.is_file());
+            let mounted_config = match cargo_config {
+                Some(cargo_config) => add_generated_auth_file(
+                    &mut extra_docker_args,
+                    &mut extra_env_args,
+                    &cargo_config,
+                    "Cargo registry config",
+                    remote_host.is_some() || vm.is_some(),
+                    true,
+                    |content| {
+                        Ok(GeneratedAuth {
+                            file_name: "cargo-config.toml",
+                            content: registries::cargo_registry_config(content)?,
+                            target: "/home/dev/.cargo/config.toml",
+                            forward_env: Vec::new(),
+                        })
+                    },
+                )?,
+                None => false,
+            };
+            let token_vars = host_env_keys(registries::is_cargo_token_var);
+            let token_vars = token_vars.iter().map(String::as_str).collect::<Vec<_>>();
+            forward_host_env(&mut extra_env_args, &token_vars);
+            if !mounted_credentials && !mounted_config && token_vars.is_empty() {
+                if !allow_missing_auth {
+                    bail!(
+                        "--auth-cargo found no credentials or config in {}, and no registry token variables",
+                        cargo_home.display()
+                    );
+                }
+                eprintln!("davy: warning: no Cargo registry credentials found; skipping.");
+            }
+        }
+        if with_aider_auth {
+            let aider_conf = home.join(".aider.conf.yml");
+            let mounted_conf = add_file_bind_mount(
+                &mut extra_docker_args,
+                &aider_conf,
+                "/home/dev/.aider.conf.yml",
+                "aider config",
+                true,
+                true,
+            )?;
+            let mut aider_vars = host_env_keys(|key| key.starts_with("AIDER_"));
+            aider_vars.push("OPENAI_API_KEY".to_owned());
+            let aider_vars = aider_vars.iter().map(String::as_str).collect::<Vec<_>>();
+            forward_host_env(&mut extra_env_args, &aider_vars);
+            let forwarded_any = aider_vars
+                .iter()
+                .any(|key| env::var_os(key).is_some_and(|value| !value.is_empty()));
+            if !mounted_conf && !forwarded_any {
+                if !allow_missing_auth {
+                    bail!(
+                        "--auth-aider found no config at {}, and no AIDER_* or OPENAI_API_KEY variables",
+                        aider_conf.display()
+                    );
+                }
+                eprintln!("davy: warning: no aider config or keys found; skipping.");
+            }
+        }
+        if with_goose_auth {
+            // Read-write so `goose configure` in the sandbox sticks.
+            let goose_config = home.join(".config/goose");
+            add_bind_mount(
+                &mut extra_docker_args,
+                &goose_config,
+                GOOSE_CONFIG_TARGET,
+                "Goose auth",
+                false,
+                allow_missing_auth,
+            )?;
+            let mut goose_vars = host_env_keys(|key| key.starts_with("GOOSE_"));
+            goose_vars.extend(GOOSE_PROVIDER_VARS.iter().map(|key| (*key).to_owned()));
+            let goose_vars = goose_vars.iter().map(String::as_str).collect::<Vec<_>>();
+            forward_host_env(&mut extra_env_args, &goose_vars);
+            // Secrets the host keeps in its keyring come through the
+            // variables above; there is no keyring in the sandbox.
+            push_env(&mut extra_env_args, "GOOSE_DISABLE_KEYRING=1");
+        }
+        if let Some(store) = opencode_auth {
+            let opencode_config = home.join(".config/opencode");
+            if opencode_config.is_dir() {
+                add_bind_mount(
+                    &mut extra_docker_args,
+                    &opencode_config,
+                    "/home/dev/.config/opencode",
+                    "OpenCode config",
+                    true,
+                    true,
+                )?;
+            }
+            match store {
+                AuthStore::Host => {
+                    add_bind_mount(
+                        &mut extra_docker_args,
+                        &home.join(".local/share/opencode"),
+                        "/home/dev/.local/share/opencode",
+                        "OpenCode auth",
+                        false,
+                        allow_missing_auth,
+                    )?;
+                }
+                AuthStore::Volume => auth_volumes.push(&auth::OPENCODE),
+            }
+        }
+        if with_cursor_auth {
+            auth_volumes.push(&auth::CURSOR);
+            forward_host_env(&mut extra_env_args, &["CURSOR_API_KEY"]);
+        }
+        if with_copilot_auth {
+            // Copilot records logins and trusted folders in its config.
+            let copilot_config = env::var_os("XDG_CONFIG_HOME")
+                .filter(|dir| !dir.is_empty())
+                .map_or_else(
+                    || home.join(".copilot"),
+                    |dir| PathBuf::from(dir).join(".copilot"),
+                );
+            add_bind_mount(
+                &mut extra_docker_args,
+                &copilot_config,
+                "/home/dev/.copilot",
+                "Copilot CLI auth",
+                false,
+                allow_missing_auth,
+            )?;
+            forward_host_env(&mut extra_env_args, &["GITHUB_TOKEN", "GH_TOKEN"]);
+        }
+        for mount in &auth_mounts {
+            let label = format!("auth ({})", mount.target);
+            if mount.source.is_file() {
+                add_file_bind_mount(
+                    &mut extra_docker_args,
+                    &mount.source,
+                    &mount.target,
+                    &label,
+                    mount.read_only,
+                    allow_missing_auth,
+                )?;
+            } else {
+                add_bind_mount(
+                    &mut extra_docker_args,
+                    &mount.source,
+                    &mount.target,
+                    &label,
+                    mount.read_only,
+                    allow_missing_auth,
+                )?;
+            }
+        }
+        if with_azure_auth {
+            // az refreshes its MSAL token cache in place.
+            let azure_config = env::var_os("AZURE_CONFIG_DIR")
+                .filter(|dir| !dir.is_empty())
+                .map_or_else(|| home.join(".azure"), PathBuf::from);
+            if add_bind_mount(
+                &mut extra_docker_args,
+                &azure_config,
+                "/home/dev/.azure",
+                "Azure CLI auth",
+                false,
+                allow_missing_auth,
+            )? {
+                push_env(
+                    &mut extra_env_args,
+                    "AZURE_CONFIG_DIR=/home/dev/.azure".to_owned(),
+                );
+            }
+        }
+        // Host skills and git config live on this machine, not the remote daemon's.
+        let skills_mode = args.skills.or(config.skills).unwrap_or_default();
+        if remote_host.is_none() {
+            let (target, read_only) = match skills_mode {
+                SkillsMode::Rw => (skills::SANDBOX_DIR, false),
+                SkillsMode::Ro => (skills::SANDBOX_DIR, true),
+                SkillsMode::Copy => (skills::HOST_COPY_DIR, true),
+            };
+            if !add_bind_mount(
+                &mut extra_docker_args,
+                &home.join(".agents/skills"),
+                target,
+                "agents skills",
+                read_only,
+                true,
+            )? && skills_mode != SkillsMode::Copy
+            {
+                eprintln!("davy: warning: continuing without host skills mount.");
+            }
+        }
+        let with_dotfiles = match args.dotfiles.or(config.dotfiles) {
+            Some(_) if remote_host.is_some() => {
+                eprintln!("davy: warning: dotfiles are not applied with a remote daemon.");
+                false
+            }
+            Some(source) => {
+                let dir = dotfiles::host_dir(&source)?;
+                add_bind_mount(
+                    &mut extra_docker_args,
+                    &dir,
+                    dotfiles::MOUNT_DIR,
+                    "dotfiles",
+                    true,
+                    false,
+                )?
+            }
+            None => false,
+        };
+        if remote_host.is_none() {
+            add_file_bind_mount(
+                &mut extra_docker_args,
+                &home.join(".config/git/ignore"),
+                "/home/dev/.config/git/ignore",
+                "global gitignore",
+                true,
+                true,
+            )?;
+            add_file_bind_mount(
+                &mut extra_docker_args,
+                &home.join(".gitconfig"),
+                "/home/dev/.gitconfig",
+                "global gitconfig",
+                true,
+                true,
+            )?;
+        }
+
+        let with_docker_sock = args.with_docker_sock || config.docker.unwrap_or(false);
+        let expose_ssh = args
+            .expose_ssh
+            .or(config.expose_ssh)
+            .or(args.open_vscode.then_some(DEFAULT_SSH_PORT));
+        let daemon_host = remote_host
+            .and_then(remote::endpoint_host)
+            .unwrap_or_else(|| "localhost".to_owned());
+        let ssh_target = expose_ssh.map(|port| SshTarget {
+            host: daemon_host.clone(),
+            port,
+        });
+        let web_terminal = match args.web_terminal {
+            Some(address) => Some(address),
+            None => config
+                .web_terminal
+                .as_deref()
+                .map(|address| address.parse().map_err(anyhow::Error::msg))
+                .transpose()
+                .context("invalid web_terminal in config")?,
+        };
+        let code_server = match args.code_server.or(config.code_server) {
+            Some(_) if no_network || egress.is_some() => bail!(
+                "--code-server needs to download code-server and publish a port, which --no-network and --egress-proxy do not allow"
+            ),
+            Some(port) => {
+                let password = ide::random_credential()?;
+                push_env(
+                    &mut extra_env_args,
+                    format!("DAVY_CODE_SERVER_PASSWORD={password}"),
+                );
+                Some((port, password))
+            }
+            None => None,
+        };
+        if let Some(address) = web_terminal {
+            if no_network || egress.is_some() {
+                bail!(
+                    "--web-terminal publishes a port, which --no-network and --egress-proxy do not allow"
+                );
+            }
+            if address.needs_credential() {
+                let credential = format!("dev:{}", ide::random_credential()?);
+                note!("web terminal is reachable beyond this machine; log in as {credential}.");
+                push_env(
+                    &mut extra_env_args,
+                    format!("DAVY_WEB_TERMINAL_CREDENTIAL={credential}"),
+                );
+            }
+        }
+        let publish = config
+            .publish
+            .iter()
+            .map(|mapping| mapping.parse().map_err(anyhow::Error::msg))
+            .chain(args.publish.into_iter().map(Ok))
+            .collect::<Result<Vec<PortMapping>>>()
+            .context("invalid publish entry in config")?;
+        let mut host_ports = HashSet::new();
+        for mapping in &publish {
+            if !host_ports.insert((mapping.host_ip, mapping.host_port, mapping.protocol)) {
+                bail!("host port {} is published twice", mapping.host_port);
+            }
+            if expose_ssh == Some(mapping.host_port) && mapping.protocol == network::Protocol::Tcp {
+                bail!(
+                    "host port {} is used by both --publish and --expose-ssh",
+                    mapping.host_port
+                );
+            }
+        }
+        if let Some((port, _)) = &code_server {
+            let taken = publish.iter().any(|mapping| {
+                mapping.host_port == *port && mapping.protocol == network::Protocol::Tcp
+            }) || expose_ssh == Some(*port)
+                || web_terminal.is_some_and(|address| address.port == *port);
+            if taken {
+                bail!("host port {port} for --code-server is already published");
+            }
+        }
+        if no_network {
+            if expose_ssh.is_some() {
+                bail!("--expose-ssh needs a network; it cannot be used with --no-network");
+            }
+            if !publish.is_empty() {
+                bail!("--publish needs a network; it cannot be used with --no-network");
+            }
+            if with_docker_sock {
+                eprintln!(
+                    "davy: warning: --docker lets the sandbox start containers with network access, which defeats --no-network."
+                );
+            }
+        }
+        if egress.is_some() {
+            if expose_ssh.is_some() || !publish.is_empty() {
+                bail!(
+                    "--expose-ssh and --publish publish ports, which the egress proxy's internal network does not allow"
+                );
+            }
+            if with_docker_sock {
+                eprintln!(
+                    "davy: warning: --docker lets the sandbox start containers that bypass the egress proxy."
+                );
+            }
+        }
+        if expose_ssh.is_some() && (remote_host.is_some() || vm.is_some()) {
+            bail!(
+                "--expose-ssh mounts your authorized keys from this machine, which a remote daemon or VM cannot do"
+            );
+        }
+        if no_lan && with_docker_sock {
+            eprintln!(
+                "davy: warning: --docker lets the sandbox start containers that --no-lan does not firewall."
+            );
+        }
+        if hardened {
+            if expose_ssh.is_some() {
+                bail!(
+                    "--expose-ssh starts sshd with sudo, which --hardened does not allow; drop one of them"
+                );
+            }
+            if with_docker_sock {
+                eprintln!(
+                    "davy: warning: --docker gives the sandbox control of the host's docker daemon, which defeats --hardened."
+                );
+            }
+            push_env(&mut extra_env_args, "DAVY_HARDENED=1");
+        }
+
+        let mut security_opts = Vec::new();
+        if let Some(profile) = args.seccomp.or(config.seccomp) {
+            security_opts.push(security::seccomp_opt(&profile)?);
+        }
+        if let Some(profile) = args.apparmor.or(config.apparmor) {
+            // Profiles are loaded on the docker host, which may not be this machine.
+            if remote_host.is_none() && vm.is_none() {
+                security::check_apparmor(&profile)?;
+            }
+            security_opts.push(format!("apparmor={profile}"));
+        }
+        let docker_sock_path = args.docker_sock.or(config.docker_sock);
+        let (docker_sock, docker_sock_gid) = match (remote_host, &vm) {
+            // The socket lives on the remote host, so it cannot be checked here.
+            (Some(host), _) if with_docker_sock => {
+                let path =
+                    docker_sock_path.unwrap_or_else(|| PathBuf::from("/var/run/docker.sock"));
+                eprintln!(
+                    "davy: warning: mounting {} from the remote docker host {host} without checking it.",
+                    path.display()
+                );
+                (Some(path), None)
+            }
+            // Bind mounts are resolved inside the VM, where the daemon listens on
+            // the standard socket path.
+            (None, Some(_)) if with_docker_sock => (
+                Some(docker_sock_path.unwrap_or_else(|| PathBuf::from("/var/run/docker.sock"))),
+                None,
+            ),
+            _ if with_docker_sock => {
+                let path = resolve_docker_socket_path(docker_sock_path)?;
+                let gid = docker_sock_gid(Some(&path))?;
+                (Some(path), gid)
+            }
+            _ => (None, None),
+        };
+
+        let mut device_gids = Vec::new();
+        for spec in config.devices.iter().chain(&args.devices) {
+            extra_docker_args.push(OsString::from("--device"));
+            extra_docker_args.push(OsString::from(spec));
+            // Devices on a remote host or in a VM cannot be checked from here.
+            if remote_host.is_some() || vm.is_some() {
+                eprintln!(
+                    "davy: warning: passing device {spec} through from the docker host without checking it."
+                );
+                continue;
+            }
+            let gid = devices::device_gid(spec)?;
+            tracing::debug!("device {spec} is owned by group {gid}");
+            if gid == 0 {
+                eprintln!(
+                    "davy: warning: device {} belongs to the root group; the sandbox user may not be able to open it.",
+                    devices::device_host_path(spec).display()
+                );
+            } else if docker_sock_gid != Some(gid) && !device_gids.contains(&gid) {
+                device_gids.push(gid);
+            }
+        }
+        for gid in device_gids {
+            extra_docker_args.push(OsString::from("--group-add"));
+            extra_docker_args.push(OsString::from(gid.to_string()));
+        }
+
+        let compose_network = match args.network_from_compose.as_deref() {
+            Some(project) => {
+                let project = match project {
+                    "" => compose::default_project_name(&project_dir),
+                    project => project.to_owned(),
+                };
+                Some(compose::find_network(engine, &project)?)
+            }
+            None => None,
+        };
+
+        Ok(Sandbox {
+            project_dir,
+            project_mode,
+            project_volume,
+            build,
+            name,
+            host_uid,
+            host_gid,
+            keep: args.keep || config.keep.unwrap_or(false),
+            reuse: args.reuse || config.reuse.unwrap_or(false),
+            tty: args.tty,
+            detach: args.detach,
+            timeout: args.timeout,
+            idle_timeout: args.idle_timeout,
+            resources,
+            hardened,
+            no_network,
+            no_lan,
+            egress,
+            security_opts,
+            gpus: args.gpus,
+            rebuild: args.rebuild,
+            no_build: args.no_build,
+            skip_build,
+            docker_sock,
+            docker_sock_gid,
+            expose_ssh,
+            daemon_host,
+            ssh_target,
+            open_vscode: args.open_vscode,
+            web_terminal,
+            code_server,
+            code_server_volume: code_server_volume(host_uid),
+            history_volume,
+            home_volume,
+            state_volume,
+            state_dirs,
+            cache_volumes,
+            cache_scripts,
+            env_commands,
+            secrets,
+            secret_env: Vec::new(),
+            publish,
+            with_claude_auth,
+            claude_auth_volume,
+            claude_settings,
+            auth_volumes,
+            auth_profile,
+            extra_docker_args,
+            extra_env_args,
+            sidecars: config.sidecars,
+            compose_network,
+            network: managed_network,
+            add_hosts,
+            hostname,
+            dns,
+            dns_search,
+            post_create: devcontainer.and_then(|dc| dc.post_create),
+            copy_skills: skills_mode == SkillsMode::Copy,
+            dotfiles: with_dotfiles,
+            ca_certs: !ca_certs.is_empty(),
+            cmd: if args.cmd.is_empty() {
+                config
+                    .command
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(OsString::from)
+                    .collect()
+            } else {
+                args.cmd
+            },
+        })
+    }
+
+    /// Run the sandbox as a pod: copy the project into a claim, exec into the pod,
+    /// then copy the project back (`copy` mode) and clean up.
+    pub fn run_pod(self) -> Result<ExitStatus> {
+        // Only these settings reach the pod; any other one set is refused.
+        let unsupported = changed_fields(&SandboxBuilder {
+            project_dir: None,
+            name: None,
+            keep: false,
+            backend: Backend::default(),
+            project_mode: None,
+            extra_env: Vec::new(),
+            pass_env: Vec::new(),
+            env_files: Vec::new(),
+            profile: None,
+            image: None,
+            cmd: Vec::new(),
+            ..self.clone()
+        });
+        if !unsupported.is_empty() {
+            bail!("--backend k8s does not support {}", unsupported.join(", "));
+        }
+        if crate::dry_run() {
+            bail!("--dry-run is not supported with --backend k8s");
+        }
+        if self.project_mode == Some(ProjectMode::Bind) {
+            bail!("--project-mode bind is not supported with --backend k8s");
+        }
+        let args = self;
+
+        let host_uid = get_current_uid();
+        let project_dir = resolve_project_dir(args.project_dir)?;
+        let config = load_config(&project_dir, args.profile.as_deref())?;
+        let project_mode = args
+            .project_mode
+            .or(config.project_mode)
+            .unwrap_or(ProjectMode::Auto)
+            .resolve(true);
+        let unsupported_in_config = changed_fields(&Config {
+            engine: None,
+            image: None,
+            keep: None,
+            project_mode: None,
+            k8s: K8sConfig::default(),
+            env: BTreeMap::new(),
+            pass_env: Vec::new(),
+            env_files: Vec::new(),
+            dotenv: None,
+            command: None,
+            profiles: BTreeMap::new(),
+            ..config.clone()
+        });
+        if !unsupported_in_config.is_empty() {
+            bail!(
+                "--backend k8s does not support {} in config",
+                unsupported_in_config.join(", ")
+            );
+        }
+        if project_mode == ProjectMode::Bind {
+            bail!("project_mode = \"bind\" in config is not supported with --backend k8s");
+        }
+        let env = collect_env(
+            &config,
+            &project_dir,
+            &args.pass_env,
+            &args.env_files,
+            args.extra_env,
+        )?;
+        let image = args
+            .image
+            .or(config.image)
+            .unwrap_or_else(|| DEFAULT_IMAGE.to_owned());
+        let cmd = if args.cmd.is_empty() {
+            config
+                .command
+                .unwrap_or_default()
+                .into_iter()
+                .map(OsString::from)
+                .collect()
+        } else {
+            args.cmd
+        };
+        let keep = args.keep || config.keep.unwrap_or(false);
+        let pod = k8s::pod_name(
+            &args
+                .name
+                .unwrap_or_else(|| default_container_name(&project_dir)),
+        );
+        let claim = remote::project_volume_name(host_uid, &project_dir);
+        let kubectl = Kubectl::new(config.k8s.namespace);
+
+        note!("starting pod '{pod}' with image {image} (the cluster must be able to pull it).");
+        let claim_exists = kubectl.exists("pvc", &claim)?;
+        if !claim_exists {
+            kubectl.apply(&k8s::pvc_manifest(
+                &claim,
+                config.k8s.storage.as_deref().unwrap_or("5Gi"),
+                config.k8s.storage_class.as_deref(),
+            ))?;
+        }
+        let (env_keys, env_values) = k8s::split_env(&env);
+        let secret = k8s::env_secret_name(&pod);
+        if !env_keys.is_empty() {
+            kubectl.apply(&k8s::env_secret_manifest(&secret, &env_values, None))?;
+        }
+        let created = kubectl
+            .apply(&k8s::pod_manifest(&k8s::PodSpec {
+                name: &pod,
+                image: &image,
+                project_dir: &project_dir,
+                claim: &claim,
+                env_keys: &env_keys,
+            }))
+            .and_then(|()| {
+                if env_keys.is_empty() {
+                    return Ok(());
+                }
+                // Owned by the pod, the Secret goes when the pod is deleted.
+                let uid = kubectl.uid("pod", &pod)?;
+                kubectl.apply(&k8s::env_secret_manifest(
+                    &secret,
+                    &env_values,
+                    Some((&pod, &uid)),
+                ))
+            });
+        if let Err(err) = created {
+            if !env_keys.is_empty() {
+                let _ = kubectl.delete("secret", &secret);
+            }
+            return Err(err);
+        }
+
+        let status = kubectl.wait_ready(&pod, "300s").and_then(|()| {
+            if project_mode == ProjectMode::Copy || !claim_exists {
+                note!("copying {} into pod '{pod}'.", project_dir.display());
+                kubectl.copy_in(&pod, &project_dir)?;
+            }
+            let status = kubectl.exec(&pod, &cmd)?;
+            if project_mode == ProjectMode::Copy {
+                note!(
+                    "copying /project back to {} (deleted files are not removed).",
+                    project_dir.display()
+                );
+                kubectl.copy_out(&pod, &project_dir)?;
+            }
+            Ok(status)
+        });
+
+        if status.is_err() {
+            // The pod and claim may hold the only copy of the agent's work.
+            let kubectl = kubectl.display();
+            eprintln!(
+                "davy: left pod '{pod}' and claim '{claim}' in place; recover /project with `{kubectl} cp {pod}:/project {}`, then `{kubectl} delete pod {pod}`{}.",
+                project_dir.display(),
+                if project_mode == ProjectMode::Copy {
+                    format!(" and `{kubectl} delete pvc {claim}`")
+                } else {
+                    String::new()
+                }
+            );
+        } else if !keep {
+            kubectl.delete("pod", &pod)?;
+            if project_mode == ProjectMode::Copy {
+                kubectl.delete("pvc", &claim)?;
+            }
+        }
+        status
+    }
+}
+
+/// Top-level fields of `value` that differ from their default, compared by
+/// their debug output.
+fn changed_fields<T: fmt::Debug + Default>(value: &T) -> Vec<String> {
+    fn fields(text: &str) -> BTreeMap<String, String> {
+        let mut fields = BTreeMap::<String, String>::new();
+        let mut current = None;
+        for line in text.lines() {
+            let is_field = line.starts_with("    ") && !line[4..].starts_with(' ');
+            if is_field && let Some((name, _)) = line[4..].split_once(':') {
+                current = Some(name.to_owned());
+            }
+            if let Some(name) = &current {
+                fields.entry(name.clone()).or_default().push_str(line);
+            }
+        }
+        fields
+    }
+    let defaults = fields(&format!("{:#?}", T::default()));
+    fields(&format!("{value:#?}"))
+        .into_iter()
+        .filter(|(name, text)| defaults.get(name) != Some(text))
+        .map(|(name, _)| name)
+        .collect()
+}
+
+impl Sandbox {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn auth_volume_name(&self, volume: &AuthVolume) -> String {
+        volume.volume_name(self.host_uid, self.auth_profile.as_deref())
+    }
+
+    /// Start the sandbox and wait for it to exit (or exec into a running one
+    /// with `reuse`).
+    pub fn run(self, engine: &dyn Engine) -> Result<ExitStatus> {
+        let mut settings = self;
+        let reused = if settings.reuse {
+            containers::find_for_project(engine, &settings.project_dir, false)
+        } else {
+            Ok(None)
+        };
+        // A reused container of the same name still mounts its files.
+        let _private_files = PrivateFiles::remove_on_drop(
+            !settings.keep
+                && !settings.detach
+                && !matches!(&reused, Ok(Some(existing)) if existing.name == settings.name),
+        );
+
+        if let Some(existing) = reused? {
+            note!(
+                "reusing running container '{}' for {}.",
+                existing.name,
+                settings.project_dir.display()
+            );
+            let status = docker_exec(engine, &existing.name, &settings.cmd, settings.tty)?;
+            return Ok(status);
+        }
+
+        for command in std::mem::take(&mut settings.env_commands) {
+            let value = command.value()?;
+            settings.secret_env.push((command.key, value));
+        }
+        let secrets = secrets::resolve_all(std::mem::take(&mut settings.secrets))?;
+        settings.secret_env.extend(secrets);
+
+        maybe_build_image(engine, &settings)?;
+
+        if settings.with_claude_auth {
+            ensure_claude_volume_ready(engine, &settings)?;
+        }
+        for volume in &settings.auth_volumes {
+            ensure_user_volume(
+                engine,
+                &settings,
+                &settings.auth_volume_name(volume),
+                &volume.kind(),
+            )?;
+        }
+        if let Some(volume) = &settings.home_volume {
+            ensure_user_volume(engine, &settings, volume, containers::HOME_VOLUME_KIND)?;
+        }
+        if let Some(volume) = &settings.state_volume {
+            ensure_user_volume(engine, &settings, volume, containers::STATE_VOLUME_KIND)?;
+        }
+        for (volume, _) in &settings.cache_volumes {
+            ensure_user_volume(engine, &settings, volume, containers::CACHE_VOLUME_KIND)?;
+        }
+        if let Some(volume) = &settings.history_volume {
+            ensure_user_volume(engine, &settings, volume, containers::HISTORY_VOLUME_KIND)?;
+        }
+        if settings.code_server.is_some() {
+            ensure_user_volume(
+                engine,
+                &settings,
+                &settings.code_server_volume,
+                CODE_SERVER_VOLUME_KIND,
+            )?;
+        }
+        prepare_project_volume(engine, &settings)?;
+
+        if settings.expose_ssh.is_some() {
+            let ssh_auth_content = collect_ssh_authorized_keys()?;
+            let path = write_private_file(
+                &format!("authorized-keys-{}", settings.name),
+                ssh_auth_content.as_bytes(),
+            )?;
+            push_volume(
+                &mut settings.extra_docker_args,
+                format!("{}:{SSH_AUTHORIZED_KEYS_TARGET}:ro", path.display()),
+            );
+        }
+
+        if settings.cmd.is_empty() {
+            settings.cmd.push(OsString::from("bash"));
+        }
+
+        if let Some(script) = &settings.post_create {
+            settings.cmd = wrap_bash_script(
+                &format!("set -e\n{script}\nexec \"$@\""),
+                std::mem::take(&mut settings.cmd),
+            );
+        }
+
+        for script in &settings.cache_scripts {
+            settings.cmd = wrap_bash_script(script, std::mem::take(&mut settings.cmd));
+        }
+        if settings.dotfiles {
+            settings.cmd =
+                wrap_bash_script(dotfiles::INSTALL_SCRIPT, std::mem::take(&mut settings.cmd));
+        }
+        if settings.copy_skills {
+            settings.cmd = wrap_bash_script(skills::COPY_SCRIPT, std::mem::take(&mut settings.cmd));
+            note!(
+                "skills are a copy; run `davy skills sync` before the sandbox is removed to keep changes."
+            );
+        }
+        // Inside the auth links, so it links within the agents' real directories.
+        if !settings.state_dirs.is_empty() {
+            let script = if settings.state_volume.is_some() {
+                state::link_script(&settings.state_dirs)
+            } else {
+                state::unlink_script(&settings.state_dirs)
+            };
+            settings.cmd = wrap_bash_script(&script, std::mem::take(&mut settings.cmd));
+        }
+        if let Some(json) = &settings.claude_settings {
+            push_env(
+                &mut settings.extra_env_args,
+                format!("DAVY_CLAUDE_SETTINGS_B64={}", STANDARD.encode(json)),
+            );
+            // Inside the Claude link so it lands in the auth volume.
+            settings.cmd =
+                wrap_bash_script(CLAUDE_SETTINGS_SCRIPT, std::mem::take(&mut settings.cmd));
+        }
+        if settings.with_claude_auth {
+            settings.cmd = wrap_bash_script(CLAUDE_LINK_SCRIPT, std::mem::take(&mut settings.cmd));
+        }
+        for volume in &settings.auth_volumes {
+            settings.cmd =
+                wrap_bash_script(&volume.link_script(), std::mem::take(&mut settings.cmd));
+        }
+        if settings.expose_ssh.is_some() {
+            settings.cmd =
+                wrap_bash_script(SSH_BOOTSTRAP_SCRIPT, std::mem::take(&mut settings.cmd));
+        }
+        if let Some((port, password)) = &settings.code_server {
+            settings.cmd = wrap_bash_script(CODE_SERVER_SCRIPT, std::mem::take(&mut settings.cmd));
+            note!(
+                "code-server: http://{}:{port}/?folder=/project (password: {password})",
+                settings.daemon_host
+            );
+        }
+        if let Some(address) = settings.web_terminal {
+            settings.cmd = wrap_bash_script(WEB_TERMINAL_SCRIPT, std::mem::take(&mut settings.cmd));
+            note!("web terminal: {}", address.url(&settings.daemon_host));
+        }
+        // Outside the rest, so later scripts and the command see its exports.
+        if settings.ca_certs {
+            settings.cmd =
+                wrap_bash_script(certs::INSTALL_SCRIPT, std::mem::take(&mut settings.cmd));
+        }
+        if settings.no_lan {
+            settings.cmd = wrap_bash_script(lan::WAIT_SCRIPT, std::mem::take(&mut settings.cmd));
+            note!("blocking private networks and the host gateway (--no-lan).");
+        }
+        if let Some(timeout) = settings.idle_timeout {
+            push_env(
+                &mut settings.extra_env_args,
+                format!("DAVY_IDLE_TIMEOUT_SECS={}", timeout.as_secs().max(1)),
+            );
+            settings.cmd = wrap_bash_script(IDLE_WATCH_SCRIPT, std::mem::take(&mut settings.cmd));
+            note!(
+                "stopping the sandbox after {} without sessions or CPU activity.",
+                humantime::format_duration(timeout)
+            );
+        }
+
+        if let Some(docker_sock) = settings.docker_sock.as_ref() {
+            note!(
+                "docker socket mounted from {}. Container can control host Docker.",
+                docker_sock.display()
+            );
+            if let Some(gid) = settings.docker_sock_gid {
+                note!("adding supplementary group {gid} for docker socket access.");
+            }
+        }
+        if let Some(network) = &settings.compose_network {
+            note!("joining compose network {network}.");
+        }
+        if let Some(network) = &settings.network {
+            network.ensure(engine)?;
+            note!(
+                "joining network {}; other sandboxes on it reach this one as '{}'.",
+                network.network_name(),
+                settings.name
+            );
+        }
+        for mapping in &settings.publish {
+            note!(
+                "publishing container port {} on {}:{}.",
+                mapping.container_port,
+                mapping.host_ip,
+                mapping.host_port
+            );
+        }
+        if let Some(port) = settings.expose_ssh {
+            note!("exposing host port {port} to container port 22.");
+            note!("SSH login user is 'dev' (key auth only).");
+        }
+        if settings.with_claude_auth {
+            note!(
+                "Claude auth volume mounted at /home/dev/.claude-auth ({}).",
+                settings.claude_auth_volume
+            );
+            note!("first use requires running 'claude login' in-container.");
+        }
+        for volume in &settings.auth_volumes {
+            note!(
+                "{} auth volume mounted at {} ({}).",
+                volume.label,
+                volume.mount_point(),
+                settings.auth_volume_name(volume)
+            );
+            note!(
+                "first use requires running '{}' in-container.",
+                volume.login
+            );
+        }
+
+        let sidecars = if settings.sidecars.is_empty() && settings.egress.is_none() {
+            None
+        } else {
+            Some(sidecars::start(
+                engine,
+                &settings.name,
+                &settings.sidecars,
+                settings.egress.as_ref(),
+            )?)
+        };
+
+        let firewall = (settings.no_lan && !crate::dry_run())
+            .then(|| lan::spawn_firewall(engine.binary(), &settings.name, &settings.build.image));
+
+        if settings.detach {
+            let status = docker_run(engine, &settings)?;
+            if let Some(firewall) = firewall
+                && status.success()
+                && !firewall.join().is_ok_and(|result| result.is_ok())
+            {
+                bail!(
+                    "--no-lan could not set up its firewall, so '{}' will stop",
+                    settings.name
+                );
+            }
+            if status.success() && !crate::dry_run() {
+                println!("{}", settings.name);
+                note!(
+                    "started '{}' in the background; attach with `davy attach {}`.",
+                    settings.name,
+                    settings.name
+                );
+                if let Some(proxy) = sidecars.as_ref().and_then(|s| s.proxy.as_ref()) {
+                    note!("egress requests: `{} logs {proxy}`.", engine.binary());
+                }
+                if let Some(target) = &settings.ssh_target {
+                    target.announce(settings.open_vscode);
+                }
+            }
+            return Ok(status);
+        }
+
+        let forwarder = if crate::dry_run() {
+            None
+        } else {
+            Some(SignalForwarder::start(
+                engine.binary(),
+                &settings.name,
+                settings.timeout,
+            )?)
+        };
+        if let Some(target) = settings.ssh_target.clone()
+            && !crate::dry_run()
+        {
+            let open = settings.open_vscode;
+            std::thread::spawn(move || target.announce(open));
+        }
+        let status = docker_run(engine, &settings);
+        let stopped = forwarder.and_then(SignalForwarder::finish);
+        if stopped.is_some() && !settings.keep {
+            signals::remove_container(engine.binary(), &settings.name);
+        }
+        if let Some(proxy) = sidecars.as_ref().and_then(|s| s.proxy.as_ref())
+            && !crate::dry_run()
+        {
+            save_egress_log(engine, &settings.name, proxy);
+        }
+        if let Some(sidecars) = &sidecars {
+            if settings.keep {
+                note!("leaving sidecars running on network {}.", sidecars.network);
+            } else {
+                sidecars.stop(engine)?;
+            }
+        }
+        let status = status?;
+        if settings.project_mode == ProjectMode::Copy {
+            note!(
+                "copying /project back to {} (deleted files are not removed).",
+                settings.project_dir.display()
+            );
+            remote::copy_project_out(
+                engine,
+                &settings.build.image,
+                &settings.project_volume,
+                &settings.project_dir,
+            )?;
+            if !settings.keep {
+                engine.remove_volume(&settings.project_volume)?;
+            }
+        }
+        if stopped == Some(Stopped::Timeout)
+            && let Some(after) = settings.timeout
+        {
+            return Err(DavyError::TimedOut { after }.into());
+        }
+        Ok(status)
+    }
+}
+
+/// Fill the project volume for `copy` and `volume` modes. Copy mode starts
+/// from a fresh volume every run; volume mode only copies on first use.
+fn prepare_project_volume(engine: &dyn Engine, settings: &Sandbox) -> Result<()> {
+    let volume = &settings.project_volume;
+    match settings.project_mode {
+        ProjectMode::Auto | ProjectMode::Bind => return Ok(()),
+        ProjectMode::Copy => {
+            if engine.volume_exists(volume)? {
+                engine.remove_volume(volume)?;
+            }
+        }
+        ProjectMode::Volume => {
+            if engine.volume_exists(volume)? {
+                note!("using existing project volume {volume}.");
+                return Ok(());
+            }
+        }
+    }
+
+    engine.create_volume(
+        volume,
+        &containers::volume_labels(containers::PROJECT_VOLUME_KIND),
+    )?;
+    note!(
+        "copying {} into project volume {volume}.",
+        settings.project_dir.display()
+    );
+    remote::copy_project_in(engine, &settings.build.image, volume, &settings.project_dir)
+}
+
+/// Append the proxy's requests to the sandbox's egress audit log. Failing to
+/// save it only warns, so the run's own result is kept.
+fn save_egress_log(engine: &dyn Engine, sandbox: &str, proxy: &str) {
+    let saved = egress::audit_log_path(sandbox).and_then(|path| {
+        let count = egress::write_audit_log(engine, proxy, &path)?;
+        Ok((path, count))
+    });
+    match saved {
+        Ok((path, count)) => note!("logged {count} egress requests to {}.", path.display()),
+        Err(err) => eprintln!("davy: warning: could not save the egress audit log: {err:#}"),
+    }
+}
+
+pub fn docker_exec(
+    engine: &dyn Engine,
+    name: &str,
+    command: &[OsString],
+    tty: Option<bool>,
+) -> Result<ExitStatus> {
+    let mut cmd = engine.command();
+    cmd.arg("exec").arg("-i");
+    if use_tty(tty) {
+        cmd.arg("-t");
+    }
+    cmd.arg("-w").arg("/project").arg(name);
+    if command.is_empty() {
+        cmd.arg("bash");
+    } else {
+        cmd.args(command);
+    }
+
+    run_status(&mut cmd, &format!("{} exec", engine.name()))
+}
+
+pub fn resolve_project_dir(from_cli: Option<PathBuf>) -> Result<PathBuf> {
+    let project_dir = match from_cli {
+        Some(path) => path,
+        None => env::current_dir().context("failed to read current directory")?,
+    };
+    if !project_dir.is_dir() {
+        return Err(DavyError::ProjectDirMissing { path: project_dir }.into());
+    }
+    fs::canonicalize(&project_dir)
+        .with_context(|| format!("failed to resolve project dir {}", project_dir.display()))
+}
+
+/// Layer the global config, the project config, and the selected profile.
+pub fn load_config(project_dir: &Path, profile: Option<&str>) -> Result<Config> {
+    let mut config = config::load_global()?;
+    if let Some(path) = config::find_project_config(project_dir) {
+        note!("using project config {}.", path.display());
+        config = config.merge(config::load_project(&path)?);
+    }
+    if let Some(profile) = profile {
+        tracing::debug!("applying profile {profile}");
+        config = config.apply_profile(profile)?;
+    }
+    Ok(config)
+}
+
+pub fn build_settings(
+    image: Option<String>,
+    dockerfile: Option<PathBuf>,
+    local_dockerfile: bool,
+) -> Result<BuildSettings> {
+    let dockerfile = resolve_dockerfile(dockerfile, local_dockerfile)?;
+    if !dockerfile.is_file() {
+        return Err(DavyError::DockerfileMissing { path: dockerfile }.into());
+    }
+
+    Ok(BuildSettings {
+        dockerfile,
+        context_dir: None,
+        image: image.unwrap_or_else(|| DEFAULT_IMAGE.to_owned()),
+        build_args: Vec::new(),
+        host_uid: get_current_uid(),
+        host_gid: get_current_gid(),
+    })
+}
+
+/// Sandbox environment as `KEY=VALUE`: config env, then forwarded host
+/// variables, then `-e` values.
+fn collect_env(
+    config: &Config,
+    project_dir: &Path,
+    pass_env: &[String],
+    env_files: &[PathBuf],
+    extra_env: Vec<String>,
+) -> Result<Vec<String>> {
+    let mut env = config
+        .env
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>();
+    let host_env = env::vars_os()
+        .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+        .collect::<BTreeMap<_, _>>();
+    for pattern in config.pass_env.iter().chain(pass_env) {
+        let forwarded = forwarded_env(pattern, &host_env);
+        if forwarded.is_empty() {
+            eprintln!("davy: warning: no host variable matches {pattern}; not forwarding it.");
+        }
+        env.extend(forwarded);
+    }
+    if config.dotenv.unwrap_or(false) {
+        env.extend(envfile::load_dotenv(project_dir)?);
+    }
+    for path in config.env_files.iter().chain(env_files) {
+        env.extend(envfile::read(path)?);
+    }
+    env.extend(extra_env);
+    Ok(env)
+}
+
+/// Host variables named by a `--pass-env` key or `*` glob, as `KEY=VALUE`.
+fn forwarded_env(pattern: &str, host_env: &BTreeMap<String, String>) -> Vec<String> {
+    if !pattern.contains('*') {
+        return host_env
+            .get(pattern)
+            .map(|value| vec![format!("{pattern}={value}")])
+            .unwrap_or_default();
+    }
+    host_env
+        .iter()
+        .filter(|(key, _)| glob_matches(pattern, key))
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect()
+}
+
+/// Whether `name` matches `pattern`, where `*` matches any run of characters.
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.collect::<Vec<_>>();
+    let last = parts.pop().unwrap_or_default();
+    for part in parts {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+fn resolve_dockerfile(from_cli: Option<PathBuf>, local: bool) -> Result<PathBuf> {
+    if let Some(path) = from_cli {
+        tracing::debug!("Dockerfile from --dockerfile/config: {}", path.display());
+        return Ok(path);
+    }
+
+    if local {
+        let cwd = env::current_dir().context("failed to read current directory")?;
+        let rocky = cwd.join("rocky.Dockerfile");
+        if rocky.is_file() {
+            tracing::debug!("Dockerfile from current directory: {}", rocky.display());
+            return Ok(rocky);
+        }
+        let debian = cwd.join("debian.Dockerfile");
+        if debian.is_file() {
+            tracing::debug!("Dockerfile from current directory: {}", debian.display());
+            return Ok(debian);
+        }
+        return Err(DavyError::NoDockerfile {
+            searched: vec![rocky, debian],
+        }
+        .into());
+    }
+
+    let config_dir = dockerfiles::config_dir()?;
+    let rocky = config_dir.join("rocky.Dockerfile");
+    if rocky.is_file() {
+        tracing::debug!("Dockerfile from config dir: {}", rocky.display());
+        return Ok(rocky);
+    }
+    tracing::debug!("{} not found", rocky.display());
+    let debian = config_dir.join("debian.Dockerfile");
+    if debian.is_file() {
+        tracing::debug!("Dockerfile from config dir: {}", debian.display());
+        return Ok(debian);
+    }
+    if let Some(path) = dockerfiles::offer_install(&config_dir)? {
+        return Ok(path);
+    }
+
+    Err(DavyError::NoDockerfile {
+        searched: vec![rocky, debian],
+    }
+    .into())
+}
+
+fn default_container_name(project_dir: &Path) -> String {
+    let base = project_dir
+        .file_name()
+        .map(|s| s.to_string_lossy().into_owned())
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "project".to_owned());
+
+    let timestamp = Local::now().format("%Y%m%d-%H%M%S");
+    format!("davy-{base}-{timestamp}")
+}
+
+/// Whether `~/.aws/config` (`[profile NAME]`, or `[default]`) or
+/// `~/.aws/credentials` (`[NAME]`) defines `profile`.
+fn aws_profile_exists(aws_dir: &Path, profile: &str) -> bool {
+    let sections = |file: &str| {
+        fs::read_to_string(aws_dir.join(file))
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let line = line.trim();
+                line.strip_prefix('[')?
+                    .strip_suffix(']')
+                    .map(|name| name.trim().to_owned())
+            })
+            .collect::<Vec<_>>()
+    };
+    let config_name = if profile == "default" {
+        "default".to_owned()
+    } else {
+        format!("profile {profile}")
+    };
+    sections("config").contains(&config_name)
+        || sections("credentials").iter().any(|name| name == profile)
+}
+
+/// `davy-<project>`, cut down to a valid hostname label.
+fn default_hostname(project_dir: &Path) -> String {
+    let base = project_dir
+        .file_name()
+        .map(|s| s.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let mut hostname = String::from("davy");
+    for part in base
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+    {
+        hostname.push('-');
+        hostname.push_str(part);
+    }
+    hostname.truncate(63);
+    hostname.trim_end_matches('-').to_owned()
+}
+
+/// Name of the persistent Claude auth volume, in an auth profile if given
+/// (`DAVY_CLAUDE_AUTH_VOLUME` overrides the default profile's).
+pub fn claude_auth_volume(host_uid: u32, profile: Option<&str>) -> String {
+    match profile {
+        Some(profile) => format!("davy-claude-auth-{host_uid}-{profile}"),
+        None => env::var("DAVY_CLAUDE_AUTH_VOLUME")
+            .unwrap_or_else(|_| format!("davy-claude-auth-{host_uid}-v1")),
+    }
+}
+
+/// Where the gcloud config dir is mounted.
+const GCLOUD_CONFIG_TARGET: &str = "/home/dev/.config/gcloud";
+/// Where a host `GOOGLE_APPLICATION_CREDENTIALS` key file is mounted.
+const GCP_CREDENTIALS_TARGET: &str = "/home/dev/.config/davy-gcp-credentials.json";
+
+/// Where the Goose config dir is mounted.
+const GOOSE_CONFIG_TARGET: &str = "/home/dev/.config/goose";
+/// Provider keys Goose reads from the environment.
+const GOOSE_PROVIDER_VARS: [&str; 12] = [
+    "ANTHROPIC_API_KEY",
+    "OPENAI_API_KEY",
+    "OPENAI_HOST",
+    "GOOGLE_API_KEY",
+    "GROQ_API_KEY",
+    "OPENROUTER_API_KEY",
+    "DATABRICKS_HOST",
+    "DATABRICKS_TOKEN",
+    "AZURE_OPENAI_API_KEY",
+    "AZURE_OPENAI_ENDPOINT",
+    "XAI_API_KEY",
+    "OLLAMA_HOST",
+];
+
+/// Where the sandbox's kubeconfig copy is mounted.
+const KUBECONFIG_TARGET: &str = "/tmp/.davy-kubeconfig";
+/// Where the sshd bootstrap finds the keys to authorize, with `expose_ssh`.
+const SSH_AUTHORIZED_KEYS_TARGET: &str = "/run/davy/authorized_keys";
+/// Where the host's sops age key is mounted, with `--sops`.
+const SOPS_AGE_KEY_TARGET: &str = "/home/dev/.config/sops/age/keys.txt";
+
+/// Where the history volume is mounted; `HISTFILE` points into it.
+const HISTORY_DIR: &str = "/home/dev/.davy-history";
+
+/// Name of the shell history volume for `host_uid`.
+fn history_volume(host_uid: u32) -> String {
+    format!("davy-history-{host_uid}")
+}
+
+/// Volume kind for the code-server install.
+const CODE_SERVER_VOLUME_KIND: &str = "code-server";
+
+/// Name of the volume code-server is installed into.
+fn code_server_volume(host_uid: u32) -> String {
+    format!("davy-code-server-{host_uid}")
+}
+
+fn maybe_build_image(engine: &dyn Engine, settings: &Sandbox) -> Result<()> {
+    let build = &settings.build;
+    if settings.skip_build {
+        return Ok(());
+    }
+    if settings.no_build {
+        if engine.image_exists(&build.image)? {
+            return Ok(());
+        }
+        return Err(DavyError::ImageMissing {
+            image: build.image.clone(),
+        }
+        .into());
+    }
+
+    if settings.rebuild {
+        return docker_build(engine, build, true, true);
+    }
+
+    if !engine.image_exists(&build.image)? {
+        return docker_build(engine, build, false, false);
+    }
+
+    Ok(())
+}
+
+pub fn docker_build(
+    engine: &dyn Engine,
+    settings: &BuildSettings,
+    pull: bool,
+    no_cache: bool,
+) -> Result<()> {
+    engine.build_image(&BuildRequest {
+        dockerfile: &settings.dockerfile,
+        context_dir: settings.context_dir.as_deref(),
+        image: &settings.image,
+        build_args: [
+            ("USER_UID".to_owned(), settings.host_uid.to_string()),
+            ("USER_GID".to_owned(), settings.host_gid.to_string()),
+        ]
+        .into_iter()
+        .chain(settings.build_args.iter().cloned())
+        .collect(),
+        labels: vec![format!("{}=true", containers::MANAGED_LABEL)],
+        pull,
+        no_cache,
+    })
+}
+
+fn ensure_claude_volume_ready(engine: &dyn Engine, settings: &Sandbox) -> Result<()> {
+    engine.create_volume(
+        &settings.claude_auth_volume,
+        &containers::auth_volume_labels("claude-auth", settings.auth_profile.as_deref()),
+    )?;
+
+    let mut init_volume = engine.command();
+    init_volume
+        .arg("run")
+        .arg("--rm")
+        .arg("--user")
+        .arg("0:0")
+        .arg("-v")
+        .arg(format!("{}:/auth", settings.claude_auth_volume))
+        .arg(&settings.build.image)
+        .arg("bash")
+        .arg("-lc")
+        .arg(format!(
+            "mkdir -p /auth/.claude && touch /auth/.claude.json && chown -R {}:{} /auth",
+            settings.host_uid, settings.host_gid
+        ));
+    run_checked(
+        &mut init_volume,
+        &format!("{} run (initialize Claude auth volume)", engine.name()),
+    )
+}
+
+/// Create `volume` if needed and hand it to the host user, since a fresh
+/// volume mounted outside the image's directories is owned by root.
+fn ensure_user_volume(
+    engine: &dyn Engine,
+    settings: &Sandbox,
+    volume: &str,
+    kind: &str,
+) -> Result<()> {
+    let labels = if containers::is_auth_volume_kind(kind) {
+        containers::auth_volume_labels(kind, settings.auth_profile.as_deref())
+    } else {
+        containers::volume_labels(kind)
+    };
+    engine.create_volume(volume, &labels)?;
+
+    let mut init_volume = engine.command();
+    init_volume
+        .arg("run")
+        .arg("--rm")
+        .arg("--user")
+        .arg("0:0")
+        .arg("-v")
+        .arg(format!("{volume}:/volume"))
+        .arg(&settings.build.image)
+        .arg("chown")
+        .arg(format!("{}:{}", settings.host_uid, settings.host_gid))
+        .arg("/volume");
+    run_checked(
+        &mut init_volume,
+        &format!("{} run (initialize {kind} volume)", engine.name()),
+    )
+}
+
+fn docker_run(engine: &dyn Engine, settings: &Sandbox) -> Result<ExitStatus> {
+    let mut cmd = engine.command();
+    cmd.arg("run").arg("-i");
+    if settings.detach {
+        // Keep a TTY so `davy attach` gets an interactive session.
+        cmd.arg("--detach").stdout(Stdio::null());
+        if settings.tty != Some(false) {
+            cmd.arg("-t");
+        }
+    } else if use_tty(settings.tty) {
+        cmd.arg("-t");
+    }
+    cmd.args(engine.run_args());
+
+    if !settings.keep {
+        cmd.arg("--rm");
+    }
+    if settings.idle_timeout.is_some() {
+        cmd.arg("--init");
+    }
+
+    cmd.arg("--name").arg(&settings.name);
+    for label in containers::run_labels(
+        &settings.project_dir.display().to_string(),
+        Local::now(),
+        settings.expose_ssh,
+    ) {
+        cmd.arg("--label").arg(label);
+    }
+
+    match settings.project_mode {
+        ProjectMode::Copy | ProjectMode::Volume => {
+            cmd.arg("--mount").arg(format!(
+                "type=volume,src={},dst=/project",
+                settings.project_volume
+            ));
+        }
+        ProjectMode::Auto | ProjectMode::Bind => {
+            cmd.arg("-v")
+                .arg(format!("{}:/project", settings.project_dir.display()));
+        }
+    }
+    cmd.arg("-w").arg("/project");
+    cmd.arg("--hostname").arg(&settings.hostname);
+
+    if !settings.sidecars.is_empty() || settings.egress.is_some() {
+        cmd.arg("--network")
+            .arg(sidecars::network_name(&settings.name));
+    }
+    if let Some(network) = &settings.compose_network {
+        cmd.arg("--network").arg(network);
+    }
+    if let Some(network) = &settings.network {
+        cmd.arg("--network").arg(network.network_name());
+    }
+    for entry in &settings.add_hosts {
+        cmd.arg("--add-host").arg(entry.to_string());
+    }
+    for server in &settings.dns {
+        cmd.arg("--dns").arg(server.to_string());
+    }
+    for domain in &settings.dns_search {
+        cmd.arg("--dns-search").arg(domain);
+    }
+    if settings.no_network {
+        cmd.arg("--network").arg("none");
+    }
+
+    if settings.with_claude_auth {
+        cmd.arg("--mount").arg(format!(
+            "type=volume,src={},dst=/home/dev/.claude-auth",
+            settings.claude_auth_volume
+        ));
+    }
+    if let Some(volume) = &settings.home_volume {
+        cmd.arg("--mount")
+            .arg(format!("type=volume,src={volume},dst={}", home::MOUNT_DIR));
+    }
+    for volume in &settings.auth_volumes {
+        cmd.arg("--mount").arg(format!(
+            "type=volume,src={},dst={}",
+            settings.auth_volume_name(volume),
+            volume.mount_point()
+        ));
+    }
+    for (volume, path) in &settings.cache_volumes {
+        cmd.arg("--mount")
+            .arg(format!("type=volume,src={volume},dst={path}"));
+    }
+    if let Some(volume) = &settings.state_volume {
+        cmd.arg("--mount")
+            .arg(format!("type=volume,src={volume},dst={}", state::MOUNT_DIR));
+    }
+    if let Some(volume) = &settings.history_volume {
+        cmd.arg("--mount")
+            .arg(format!("type=volume,src={volume},dst={HISTORY_DIR}"));
+    }
+
+    if let Some(docker_sock) = settings.docker_sock.as_ref() {
+        cmd.arg("-v")
+            .arg(format!("{}:/var/run/docker.sock", docker_sock.display()));
+        if let Some(gid) = settings.docker_sock_gid {
+            cmd.arg("--group-add").arg(gid.to_string());
+        }
+    }
+
+    if let Some(port) = settings.expose_ssh {
+        cmd.arg("-p").arg(format!("{port}:22"));
+    }
+    for mapping in &settings.publish {
+        cmd.arg("-p").arg(mapping.to_string());
+    }
+    if let Some(address) = settings.web_terminal {
+        cmd.arg("-p").arg(address.publish_arg());
+    }
+    if let Some((port, _)) = settings.code_server {
+        cmd.arg("-p").arg(format!("127.0.0.1:{port}:8080"));
+        cmd.arg("--mount").arg(format!(
+            "type=volume,src={},dst=/home/dev/.cache/davy-code-server",
+            settings.code_server_volume
+        ));
+    }
+
+    if let Some(gpus) = settings.gpus {
+        cmd.arg("--gpus").arg(gpus.to_string());
+    }
+    if settings.hardened {
+        cmd.arg("--cap-drop")
+            .arg("ALL")
+            .arg("--security-opt")
+            .arg("no-new-privileges");
+    }
+    for opt in &settings.security_opts {
+        cmd.arg("--security-opt").arg(opt);
+    }
+    cmd.args(settings.resources.run_args())
+        .args(&settings.extra_env_args);
+    for (key, value) in &settings.secret_env {
+        cmd.arg("-e").arg(key).env(key, value);
+    }
+    cmd.args(&settings.extra_docker_args)
+        .arg(&settings.build.image)
+        .args(&settings.cmd);
+
+    run_status(&mut cmd, &format!("{} run", engine.name()))
+}
+
+/// Whether to pass `-t`: forced by `requested`, else only when stdin and
+/// stdout are both terminals (not under CI or with piped input).
+fn use_tty(requested: Option<bool>) -> bool {
+    requested.unwrap_or_else(|| std::io::stdin().is_terminal() && std::io::stdout().is_terminal())
+}
+
+fn wrap_bash_script(script: &str, original_cmd: Vec<OsString>) -> Vec<OsString> {
+    let mut wrapped = vec![
+        OsString::from("bash"),
+        OsString::from("-lc"),
+        OsString::from(script),
+        OsString::from("--"),
+    ];
+    wrapped.extend(original_cmd);
+    wrapped
+}
+
+fn collect_ssh_authorized_keys() -> Result<String> {
+    let mut unique = HashSet::new();
+    let mut keys = Vec::new();
+
+    if let Ok(path) = env::var("DAVY_SSH_AUTHORIZED_KEYS_FILE") {
+        let key_path = PathBuf::from(&path);
+        if !key_path.is_file() {
+            bail!("DAVY_SSH_AUTHORIZED_KEYS_FILE not found: {path}");
+        }
+        collect_key_lines_from_file(&key_path, &mut unique, &mut keys)?;
+    } else {
+        let ssh_dir = home_dir()?.join(".ssh");
+        let authorized_keys = ssh_dir.join("authorized_keys");
+        if authorized_keys.is_file() {
+            collect_key_lines_from_file(&authorized_keys, &mut unique, &mut keys)?;
+        }
+
+        if ssh_dir.is_dir() {
+            let mut pubs = fs::read_dir(&ssh_dir)
+                .with_context(|| format!("failed to read {}", ssh_dir.display()))?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "pub"))
+                .collect::<Vec<_>>();
+            pubs.sort();
+
+            for path in pubs {
+                if path.is_file() {
+                    collect_key_lines_from_file(&path, &mut unique, &mut keys)?;
+                }
+            }
+        }
+    }
+
+    if keys.is_empty() {
+        bail!("no SSH public keys found. Add ~/.ssh/*.pub or set DAVY_SSH_AUTHORIZED_KEYS_FILE");
+    }
+
+    Ok(format!("{}\n", keys.join("\n")))
+}
+
+fn collect_key_lines_from_file(
+    path: &Path,
+    unique: &mut HashSet<String>,
+    output: &mut Vec<String>,
+) -> Result<()> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("failed to read SSH keys from {}", path.display()))?;
+
+    let before = output.len();
+    for line in content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        if unique.insert(line.to_owned()) {
+            output.push(line.to_owned());
+        }
+    }
+    tracing::debug!(
+        "collected {} SSH key(s) from {}",
+        output.len() - before,
+        path.display()
+    );
+
+    Ok(())
+}
+
+fn add_bind_mount(
+    args: &mut Vec<OsString>,
+    source: &Path,
+    target: &str,
+    label: &str,
+    read_only: bool,
+    allow_missing: bool,
+) -> Result<bool> {
+    if source.is_dir() {
+        tracing::debug!("{label} mount: {} -> {target}", source.display());
+        let suffix = if read_only { ":ro" } else { "" };
+        push_volume(args, format!("{}:{target}{suffix}", source.display()));
+        return Ok(true);
+    }
+
+    if source.exists() {
+        return Err(mount_source_error(source, label, Some("directory")));
+    }
+
+    if allow_missing {
+        eprintln!(
+            "davy: warning: {label} mount source not found at {}; skipping.",
+            source.display()
+        );
+        return Ok(false);
+    }
+
+    Err(mount_source_error(source, label, None))
+}
+
+/// A credentials file generated from a host one.
+struct GeneratedAuth {
+    file_name: &'static str,
+    content: String,
+    target: &'static str,
+    /// Host variables the file refers to.
+    forward_env: Vec<String>,
+}
+
+/// Read `source`, turn it into a file holding only what the sandbox needs,
+/// and mount that read-only. Missing sources follow the auth flag rules;
+/// the file is written on this machine, so other daemons cannot see it.
+fn add_generated_auth_file(
+    args: &mut Vec<OsString>,
+    env_args: &mut Vec<OsString>,
+    source: &Path,
+    label: &str,
+    daemon_elsewhere: bool,
+    allow_missing: bool,
+    generate: impl FnOnce(&str) -> Result<GeneratedAuth>,
+) -> Result<bool> {
+    if daemon_elsewhere {
+        if allow_missing {
+            eprintln!("davy: warning: skipping {label}, which needs a local container engine.");
+            return Ok(false);
+        }
+        bail!("{label} needs the container engine on this machine");
+    }
+    if !source.is_file() {
+        if source.exists() {
+            return Err(mount_source_error(source, label, Some("file")));
+        }
+        if allow_missing {
+            eprintln!(
+                "davy: warning: {label} source not found at {}; skipping.",
+                source.display()
+            );
+            return Ok(false);
+        }
+        return Err(mount_source_error(source, label, None));
+    }
+    let content = fs::read_to_string(source)
+        .with_context(|| format!("failed to read {}", source.display()))?;
+    let generated = generate(&content)
+        .with_context(|| format!("failed to read {label} from {}", source.display()))?;
+    let path = write_private_file(generated.file_name, generated.content.as_bytes())?;
+    tracing::debug!(
+        "{label}: {} -> {} -> {}",
+        source.display(),
+        path.display(),
+        generated.target
+    );
+    push_volume(args, format!("{}:{}:ro", path.display(), generated.target));
+    let keys: Vec<&str> = generated.forward_env.iter().map(String::as_str).collect();
+    forward_host_env(env_args, &keys);
+    Ok(true)
+}
+
+fn add_file_bind_mount(
+    args: &mut Vec<OsString>,
+    source: &Path,
+    target: &str,
+    label: &str,
+    read_only: bool,
+    allow_missing: bool,
+) -> Result<bool> {
+    if source.is_file() {
+        tracing::debug!("{label} mount: {} -> {target}", source.display());
+        let suffix = if read_only { ":ro" } else { "" };
+        push_volume(args, format!("{}:{target}{suffix}", source.display()));
+        return Ok(true);
+    }
+
+    if source.exists() {
+        return Err(mount_source_error(source, label, Some("file")));
+    }
+
+    if allow_missing {
+        tracing::debug!(
+            "{label} mount source {} not found; skipping",
+            source.display()
+        );
+        return Ok(false);
+    }
+
+    Err(mount_source_error(source, label, None))
+}
+
+/// Read a Claude settings.json, checking that it holds a JSON object.
+fn read_claude_settings(path: &Path) -> Result<String> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("failed to read Claude settings {}", path.display()))?;
+    match serde_json::from_str::<serde_json::Value>(&text) {
+        Ok(value) if value.is_object() => Ok(text),
+        Ok(_) => bail!("Claude settings {} must be a JSON object", path.display()),
+        Err(err) => bail!("invalid Claude settings {}: {err}", path.display()),
+    }
+}
+
+/// Missing source, or one of the wrong kind when `expected` is given.
+fn mount_source_error(source: &Path, label: &str, expected: Option<&'static str>) -> anyhow::Error {
+    let label = label.to_owned();
+    let path = source.to_path_buf();
+    match expected {
+        Some(expected) => DavyError::MountSourceInvalid {
+            label,
+            path,
+            expected,
+        },
+        None => DavyError::MountSourceMissing { label, path },
+    }
+    .into()
+}
+
+fn resolve_docker_socket_path(from_cli: Option<PathBuf>) -> Result<PathBuf> {
+    let socket = if let Some(path) = from_cli {
+        path
+    } else if let Some(path) = env::var("DOCKER_HOST")
+        .ok()
+        .as_deref()
+        .and_then(parse_unix_socket_from_docker_host)
+    {
+        path
+    } else if let Ok(host) = env::var("DOCKER_HOST") {
+        bail!(
+            "DOCKER_HOST is set to '{host}', but --docker needs a local unix socket. Set --docker-sock or DAVY_DOCKER_SOCK."
+        );
+    } else {
+        PathBuf::from("/var/run/docker.sock")
+    };
+
+    let metadata = fs::metadata(&socket)
+        .with_context(|| format!("docker socket not found: {}", socket.display()))?;
+    #[cfg(unix)]
+    {
+        if !metadata.file_type().is_socket() {
+            bail!(
+                "docker socket path is not a unix socket: {}",
+                socket.display()
+            );
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+    }
+
+    Ok(socket)
+}
+
+fn parse_unix_socket_from_docker_host(docker_host: &str) -> Option<PathBuf> {
+    docker_host
+        .strip_prefix("unix://")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+fn docker_sock_gid(path: Option<&Path>) -> Result<Option<u32>> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+
+    #[cfg(unix)]
+    {
+        let metadata = fs::metadata(path).with_context(|| {
+            format!(
+                "failed to read metadata for docker socket at {}",
+                path.display()
+            )
+        })?;
+        Ok(Some(metadata.gid()))
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        Ok(None)
+    }
+}
+
+/// Names of the host environment variables that match `filter`.
+fn host_env_keys(filter: impl Fn(&str) -> bool) -> Vec<String> {
+    env::vars_os()
+        .filter_map(|(key, _)| key.into_string().ok())
+        .filter(|key| filter(key))
+        .collect()
+}
+
+/// Where sops looks for age keys by default: its config dir under the
+/// user config dir, which is `~/Library/Application Support` on macOS.
+fn sops_age_key_file(home: &Path) -> PathBuf {
+    let config_dir = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None if cfg!(target_os = "macos") => home.join("Library/Application Support"),
+        None => home.join(".config"),
+    };
+    config_dir.join("sops/age/keys.txt")
+}
+
+/// Pass each of `keys` that is set on the host through by name, so the engine
+/// reads the value from its own environment and it stays off the command line.
+fn forward_host_env(args: &mut Vec<OsString>, keys: &[&str]) {
+    for key in keys {
+        if env::var_os(key).is_some_and(|value| !value.is_empty()) {
+            tracing::debug!("forwarding {key} from the host");
+            push_env(args, *key);
+        }
+    }
+}
+
+fn push_env(args: &mut Vec<OsString>, value: impl Into<OsString>) {
+    args.push(OsString::from("-e"));
+    args.push(value.into());
+}
+
+fn push_volume(args: &mut Vec<OsString>, volume: impl Into<OsString>) {
+    args.push(OsString::from("-v"));
+    args.push(volume.into());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_name_has_prefix() {
+        let name = default_container_name(Path::new("/tmp/my-project"));
+        assert!(name.starts_with("davy-my-project-"));
+        assert_eq!(name.len(), "davy-my-project-YYYYMMDD-HHMMSS".len());
+    }
+
+    #[test]
+    fn forwards_host_env_by_name() {
+        let mut args = Vec::new();
+        forward_host_env(&mut args, &["PATH", "DAVY_TEST_UNSET_VARIABLE"]);
+        assert_eq!(args, vec![OsString::from("-e"), OsString::from("PATH")]);
+    }
+
+    #[test]
+    fn parses_auth_mounts() {
+        let mount: AuthMount = "/etc/foo:~/.config/foo:ro".parse().unwrap();
+        assert_eq!(mount.source, PathBuf::from("/etc/foo"));
+        assert_eq!(mount.target, "/home/dev/.config/foo");
+        assert!(mount.read_only);
+        assert!(!"/a:/b".parse::<AuthMount>().unwrap().read_only);
+        for invalid in ["/a", ":/b", "/a:b", "/a:/b:z", "/a:/b:ro:x"] {
+            assert!(invalid.parse::<AuthMount>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn finds_aws_profiles() {
+        let dir = std::env::temp_dir().join(format!("davy-aws-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("config"),
+            "[default]\n[profile prod]\nregion = us-east-1\n",
+        )
+        .unwrap();
+        fs::write(dir.join("credentials"), "[ci]\naws_access_key_id = x\n").unwrap();
+        assert!(aws_profile_exists(&dir, "default"));
+        assert!(aws_profile_exists(&dir, "prod"));
+        assert!(aws_profile_exists(&dir, "ci"));
+        assert!(!aws_profile_exists(&dir, "staging"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn checks_claude_settings() {
+        let dir = std::env::temp_dir().join(format!("davy-claude-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let settings = dir.join("settings.json");
+        fs::write(
+            &settings,
+            r#"{"permissions": {"allow": ["Bash(npm test)"]}}"#,
+        )
+        .unwrap();
+        assert!(read_claude_settings(&settings).is_ok());
+        fs::write(&settings, "[]").unwrap();
+        assert!(read_claude_settings(&settings).is_err());
+        fs::write(&settings, "{permissions: {}}").unwrap();
+        assert!(read_claude_settings(&settings).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn default_hostname_is_a_valid_label() {
+        assert_eq!(
+            default_hostname(Path::new("/src/My_Project.v2")),
+            "davy-my-project-v2"
+        );
+        assert_eq!(default_hostname(Path::new("/")), "davy");
+        let long = default_hostname(&Path::new("/src").join("x".repeat(100)));
+        assert_eq!(long.len(), 63);
+        assert!(network::parse_hostname(&long).is_ok());
+    }
+
+    #[test]
+    fn wrap_script_prefixes_command() {
+        let wrapped = wrap_bash_script("echo hi", vec![OsString::from("bash")]);
+        let expected = vec![
+            OsString::from("bash"),
+            OsString::from("-lc"),
+            OsString::from("echo hi"),
+            OsString::from("--"),
+            OsString::from("bash"),
+        ];
+        assert_eq!(wrapped, expected);
+    }
+
+    #[test]
+    fn parse_unix_docker_host_extracts_socket_path() {
+        let socket = parse_unix_socket_from_docker_host("unix:///run/user/1000/docker.sock");
+        assert_eq!(socket, Some(PathBuf::from("/run/user/1000/docker.sock")));
+    }
+
+    #[test]
+    fn parse_non_unix_docker_host_returns_none() {
+        assert_eq!(
+            parse_unix_socket_from_docker_host("tcp://127.0.0.1:2375"),
+            None
+        );
+    }
+
+    #[test]
+    fn forwards_env_by_glob() {
+        let host_env = BTreeMap::from(
+            [
+                ("AWS_REGION", "eu"),
+                ("AWS_PROFILE", "dev"),
+                ("HOME", "/h"),
+                ("MY_AWS", "x"),
+            ]
+            .map(|(key, value)| (key.to_owned(), value.to_owned())),
+        );
+        assert_eq!(
+            forwarded_env("AWS_*", &host_env),
+            ["AWS_PROFILE=dev", "AWS_REGION=eu"]
+        );
+        assert_eq!(forwarded_env("*AWS*", &host_env).len(), 3);
+        assert_eq!(forwarded_env("HOME", &host_env), ["HOME=/h"]);
+        assert!(forwarded_env("UNSET", &host_env).is_empty());
+        assert!(glob_matches("A*B*C", "AxxBxxC"));
+        assert!(!glob_matches("A*BC", "ABC_"));
+        assert!(!glob_matches("AB*B", "AB"));
+    }
+
+    #[test]
+    fn builder_collects_options() {
+        let builder = SandboxBuilder::new()
+            .env("A=1")
+            .env("B=2")
+            .docker_args(["--cpus", "2"])
+            .command(["npm", "test"])
+            .devcontainer();
+        assert_eq!(builder.extra_env, vec!["A=1".to_owned(), "B=2".to_owned()]);
+        assert_eq!(
+            builder.extra_docker_args,
+            vec![OsString::from("--cpus"), OsString::from("2")]
+        );
+        assert_eq!(builder.cmd.len(), 2);
+        assert_eq!(builder.devcontainer, Some(PathBuf::new()));
+        assert_eq!(builder.backend, Backend::Container);
+    }
+
+    #[test]
+    fn k8s_refuses_settings_it_does_not_support() {
+        assert!(changed_fields(&SandboxBuilder::new()).is_empty());
+        let builder = SandboxBuilder::new()
+            .keep(true)
+            .env("A=1")
+            .docker(true)
+            .memory("1g");
+        assert_eq!(
+            changed_fields(&builder),
+            ["extra_env", "keep", "resources", "with_docker_sock"]
+        );
+
+        let err = builder.backend(Backend::K8s).run_pod().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "--backend k8s does not support resources, with_docker_sock"
+        );
+    }
+}
@@ -0,0 +1,254 @@
+//! `[secrets]` references, resolved by the secret manager's CLI on the host
+//! when a sandbox starts and passed to the engine like `--env-from-cmd`
+//! values.
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::env;
+use std::io::ErrorKind;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+use anyhow::{Context, Result, bail};
+
+use crate::{dry_run, note};
+
+/// Where a secret's value comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretRef {
+    /// A 1Password secret reference, `op://VAULT/ITEM[/SECTION]/FIELD`.
+    OnePassword(String),
+    /// A Vault path and the field to read from it, `vault:PATH#FIELD`.
+    Vault { path: String, field: String },
+}
+
+impl FromStr for SecretRef {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<SecretRef, String> {
+        if let Some(rest) = value.strip_prefix("vault:") {
+            return match rest.rsplit_once('#') {
+                Some((path, field)) if !path.trim_matches('/').is_empty() && !field.is_empty() => {
+                    Ok(SecretRef::Vault {
+                        path: path.trim_matches('/').to_owned(),
+                        field: field.to_owned(),
+                    })
+                }
+                _ => Err(format!(
+                    "invalid Vault reference '{value}' (expected vault:PATH#FIELD)"
+                )),
+            };
+        }
+        match value.strip_prefix("op://") {
+            Some(path) if path.split('/').filter(|part| !part.is_empty()).count() >= 3 => {
+                Ok(SecretRef::OnePassword(value.to_owned()))
+            }
+            Some(_) => Err(format!(
+                "invalid 1Password reference '{value}' (expected op://VAULT/ITEM/FIELD)"
+            )),
+            None => Err(format!(
+                "unsupported secret reference '{value}' (expected op://VAULT/ITEM/FIELD or vault:PATH#FIELD)"
+            )),
+        }
+    }
+}
+
+/// Read the secrets for a sandbox start on the host, in order. Each Vault
+/// path is read once and every field comes from that response, so the
+/// fields of a dynamic secret belong to the same lease. The CLIs keep the
+/// terminal so they can prompt to unlock. Dry runs skip them and return "".
+pub fn resolve_all(secrets: Vec<(String, SecretRef)>) -> Result<Vec<(String, String)>> {
+    resolve_with(secrets, read_vault)
+}
+
+fn resolve_with(
+    secrets: Vec<(String, SecretRef)>,
+    mut read_vault: impl FnMut(&str, &str) -> Result<Option<serde_json::Value>>,
+) -> Result<Vec<(String, String)>> {
+    let mut vault_responses = HashMap::new();
+    let mut values = Vec::new();
+    for (key, reference) in secrets {
+        let value = match reference {
+            SecretRef::OnePassword(reference) => read_1password(&key, &reference)?,
+            SecretRef::Vault { path, field } => {
+                let response = match vault_responses.entry(path.clone()) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => entry.insert(read_vault(&key, &path)?),
+                };
+                match response {
+                    Some(response) => vault_field(response, &field).with_context(|| {
+                        format!("vault:{path} has no field '{field}' for secret {key}")
+                    })?,
+                    None => {
+                        note!("would read vault:{path}#{field} for {key}");
+                        String::new()
+                    }
+                }
+            }
+        };
+        values.push((key, value));
+    }
+    Ok(values)
+}
+
+fn read_1password(key: &str, reference: &str) -> Result<String> {
+    if dry_run() {
+        note!("would read {reference} for {key}");
+        return Ok(String::new());
+    }
+    tracing::debug!("secret {key}: op read {reference}");
+    let output = match Command::new("op")
+        .args(["read", "--no-newline", reference])
+        .stderr(Stdio::piped())
+        .output()
+    {
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            bail!("secret {key} needs the 1Password CLI (op), which is not installed")
+        }
+        result => result.context("failed to run op read")?,
+    };
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if is_locked(&stderr) {
+            bail!(
+                "1Password is locked or signed out, so secret {key} could not be read; unlock the app or run `eval $(op signin)` and try again"
+            );
+        }
+        bail!(
+            "op read {reference} failed for secret {key}: {}",
+            stderr.trim()
+        );
+    }
+    String::from_utf8(output.stdout).with_context(|| format!("secret {key} is not UTF-8"))
+}
+
+/// Read the secret at `path` with the Vault CLI, which uses `VAULT_ADDR` and
+/// `VAULT_TOKEN` (or its token helper). Any engine's path works, so dynamic
+/// credentials are minted per sandbox; KV version 2 paths include `data/`.
+/// Dry runs return `None`.
+fn read_vault(key: &str, path: &str) -> Result<Option<serde_json::Value>> {
+    if env::var_os("VAULT_ADDR").is_none_or(|addr| addr.is_empty()) {
+        bail!("secret {key} is in Vault, but VAULT_ADDR is not set");
+    }
+    if dry_run() {
+        return Ok(None);
+    }
+    tracing::debug!("secret {key}: vault read {path}");
+    let output = match Command::new("vault")
+        .args(["read", "-format=json", path])
+        .stderr(Stdio::piped())
+        .output()
+    {
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            bail!("secret {key} needs the Vault CLI (vault), which is not installed")
+        }
+        result => result.context("failed to run vault read")?,
+    };
+    if !output.status.success() {
+        bail!(
+            "vault read {path} failed for secret {key}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    serde_json::from_slice(&output.stdout)
+        .map(Some)
+        .with_context(|| format!("vault read {path} printed invalid JSON"))
+}
+
+/// `field` of a `vault read` response, looking inside KV version 2's nested
+/// `data` too.
+fn vault_field(response: &serde_json::Value, field: &str) -> Option<String> {
+    let data = &response["data"];
+    let value = match data.get(field) {
+        Some(value) => value,
+        None => data.get("data")?.get(field)?,
+    };
+    match value {
+        serde_json::Value::String(value) => Some(value.clone()),
+        serde_json::Value::Null => None,
+        other => Some(other.to_string()),
+    }
+}
+
+/// Whether `op`'s error output says it needs signing in or unlocking.
+fn is_locked(stderr: &str) -> bool {
+    let stderr = stderr.to_ascii_lowercase();
+    [
+        "not currently signed in",
+        "sign in",
+        "signin",
+        "locked",
+        "authorization prompt",
+    ]
+    .iter()
+    .any(|hint| stderr.contains(hint))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_1password_references() {
+        assert_eq!(
+            "op://dev/openai/api key".parse::<SecretRef>(),
+            Ok(SecretRef::OnePassword("op://dev/openai/api key".to_owned()))
+        );
+        assert!("op://dev/openai".parse::<SecretRef>().is_err());
+        assert!("plain value".parse::<SecretRef>().is_err());
+        assert!(is_locked(
+            "[ERROR] 2024/05/01 10:00:00 You are not currently signed in. Please run `op signin --help`"
+        ));
+        assert_eq!(
+            "vault:/secret/data/openai#api_key".parse::<SecretRef>(),
+            Ok(SecretRef::Vault {
+                path: "secret/data/openai".to_owned(),
+                field: "api_key".to_owned()
+            })
+        );
+        assert!("vault:secret/openai".parse::<SecretRef>().is_err());
+        assert!(!is_locked(
+            "[ERROR] could not find item openai in vault dev"
+        ));
+    }
+
+    #[test]
+    fn reads_vault_fields() {
+        let kv2 = serde_json::json!({"data": {"data": {"api_key": "abc"}, "metadata": {}}});
+        assert_eq!(vault_field(&kv2, "api_key").as_deref(), Some("abc"));
+        let creds = serde_json::json!({"lease_duration": 3600, "data": {"access_key": "AKIA", "ttl": 3600}});
+        assert_eq!(vault_field(&creds, "access_key").as_deref(), Some("AKIA"));
+        assert_eq!(vault_field(&creds, "ttl").as_deref(), Some("3600"));
+        assert_eq!(vault_field(&creds, "secret_key"), None);
+    }
+
+    #[test]
+    fn reads_each_vault_path_once() {
+        let secrets = [
+            ("AWS_ACCESS_KEY_ID", "vault:aws/creds/agent#access_key"),
+            ("AWS_SECRET_ACCESS_KEY", "vault:aws/creds/agent#secret_key"),
+            ("OPENAI_API_KEY", "vault:secret/data/openai#api_key"),
+        ]
+        .map(|(key, reference)| (key.to_owned(), reference.parse::<SecretRef>().unwrap()));
+        let mut reads = Vec::new();
+        let values = resolve_with(secrets.to_vec(), |_, path| {
+            reads.push(path.to_owned());
+            let lease = reads.len();
+            Ok(Some(serde_json::json!({"data": {
+                "access_key": format!("AKIA{lease}"),
+                "secret_key": format!("secret{lease}"),
+                "data": {"api_key": "sk"},
+            }})))
+        })
+        .unwrap();
+        assert_eq!(reads, ["aws/creds/agent", "secret/data/openai"]);
+        assert_eq!(
+            values,
+            [
+                ("AWS_ACCESS_KEY_ID".to_owned(), "AKIA1".to_owned()),
+                ("AWS_SECRET_ACCESS_KEY".to_owned(), "secret1".to_owned()),
+                ("OPENAI_API_KEY".to_owned(), "sk".to_owned()),
+            ]
+        );
+    }
+}
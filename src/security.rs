@@ -0,0 +1,106 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+
+use crate::home_dir;
+
+/// Name of the seccomp profile bundled with davy.
+pub const RESTRICTED_SECCOMP: &str = "davy-restricted";
+
+/// Allows everything except ptrace, mount, namespace creation, keyrings, bpf,
+/// io_uring, and kernel module or clock calls, which fail with EPERM.
+const RESTRICTED_SECCOMP_PROFILE: &str = include_str!("../seccomp/davy-restricted.json");
+
+/// The `--security-opt` value for a seccomp profile: `davy-restricted`,
+/// `unconfined`, or the path to a JSON profile. The engine CLI reads the
+/// file, so it is checked on this machine even for remote daemons.
+pub fn seccomp_opt(profile: &Path) -> Result<String> {
+    if profile == Path::new("unconfined") {
+        return Ok("seccomp=unconfined".to_owned());
+    }
+    let path = if profile == Path::new(RESTRICTED_SECCOMP) {
+        write_restricted_profile()?
+    } else {
+        let content = fs::read_to_string(profile)
+            .with_context(|| format!("failed to read seccomp profile {}", profile.display()))?;
+        serde_json::from_str::<serde_json::Value>(&content)
+            .with_context(|| format!("seccomp profile {} is not valid JSON", profile.display()))?;
+        profile.to_path_buf()
+    };
+    tracing::debug!("seccomp profile: {}", path.display());
+    Ok(format!("seccomp={}", path.display()))
+}
+
+/// Write the bundled profile under the cache dir, where the engine CLI can
+/// read it.
+fn write_restricted_profile() -> Result<PathBuf> {
+    let dir = match env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir).join("davy"),
+        None => home_dir()?.join(".cache/davy"),
+    };
+    let path = dir.join(format!("{RESTRICTED_SECCOMP}.json"));
+    if fs::read_to_string(&path).is_ok_and(|content| content == RESTRICTED_SECCOMP_PROFILE) {
+        return Ok(path);
+    }
+    fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    fs::write(&path, RESTRICTED_SECCOMP_PROFILE)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Check that AppArmor is enabled on this host and `profile` is loaded.
+/// Only meaningful when the daemon runs on this machine.
+pub fn check_apparmor(profile: &str) -> Result<()> {
+    if profile == "unconfined" {
+        return Ok(());
+    }
+    let enabled = fs::read_to_string("/sys/module/apparmor/parameters/enabled")
+        .is_ok_and(|value| value.trim() == "Y");
+    if !enabled {
+        bail!("--apparmor {profile}: AppArmor is not enabled on this host");
+    }
+    // Listing profiles needs root on some systems; trust the name then.
+    let Ok(loaded) = fs::read_to_string("/sys/kernel/security/apparmor/profiles") else {
+        tracing::debug!("cannot list AppArmor profiles; not checking {profile}");
+        return Ok(());
+    };
+    if !is_loaded(&loaded, profile) {
+        bail!(
+            "AppArmor profile '{profile}' is not loaded (load it with `sudo apparmor_parser -r -W FILE`)"
+        );
+    }
+    Ok(())
+}
+
+/// Whether `profile` appears in the kernel's `NAME (MODE)` profile list.
+fn is_loaded(profiles: &str, profile: &str) -> bool {
+    profiles
+        .lines()
+        .any(|line| line.rsplit_once(" (").map_or(line, |(name, _)| name).trim() == profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_seccomp_profile_blocks_ptrace_and_mount() {
+        let profile: serde_json::Value =
+            serde_json::from_str(RESTRICTED_SECCOMP_PROFILE).expect("profile should be JSON");
+        assert_eq!(profile["defaultAction"], "SCMP_ACT_ALLOW");
+        let blocked = profile["syscalls"][0]["names"].as_array().unwrap();
+        for name in ["ptrace", "mount", "unshare", "bpf"] {
+            assert!(blocked.iter().any(|blocked| blocked == name), "{name}");
+        }
+    }
+
+    #[test]
+    fn finds_loaded_apparmor_profiles() {
+        let profiles = "docker-default (enforce)\ndavy agent (complain)\n";
+        assert!(is_loaded(profiles, "docker-default"));
+        assert!(is_loaded(profiles, "davy agent"));
+        assert!(!is_loaded(profiles, "davy"));
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::BTreeMap;
+use std::process::Stdio;
+
+use anyhow::{Result, bail};
+
+use crate::config::SidecarConfig;
+use crate::containers::{self, MANAGED_LABEL};
+use crate::egress::{self, Egress};
+use crate::engine::Engine;
+use crate::note;
+use crate::run_checked;
+
+/// Label naming the sandbox container a sidecar, egress proxy, or sidecar
+/// network belongs to.
+pub const SIDECAR_OF_LABEL: &str = "davy.sidecar_of";
+
+/// Sidecars started for one sandbox, plus the network they share with it.
+pub struct Sidecars {
+    pub network: String,
+    /// The egress proxy container, when egress is filtered.
+    pub proxy: Option<String>,
+    containers: Vec<String>,
+}
+
+pub fn network_name(sandbox: &str) -> String {
+    format!("{sandbox}-net")
+}
+
+pub fn container_name(sandbox: &str, sidecar: &str) -> String {
+    format!("{sandbox}-{sidecar}")
+}
+
+/// Create the private network and start every sidecar on it, reachable from
+/// the sandbox by its config name. With `egress`, the network is internal
+/// and an egress proxy is the only way out.
+pub fn start(
+    engine: &dyn Engine,
+    sandbox: &str,
+    sidecars: &BTreeMap<String, SidecarConfig>,
+    egress: Option<&Egress>,
+) -> Result<Sidecars> {
+    let network = network_name(sandbox);
+    let mut create = engine.command();
+    create
+        .arg("network")
+        .arg("create")
+        .arg("--label")
+        .arg(format!("{MANAGED_LABEL}=true"))
+        .arg("--label")
+        .arg(format!("{SIDECAR_OF_LABEL}={sandbox}"));
+    if egress.is_some() {
+        create.arg("--internal");
+    }
+    create.arg(&network).stdout(Stdio::null());
+    run_checked(&mut create, &format!("{} network create", engine.name()))?;
+
+    let mut started = Sidecars {
+        network,
+        proxy: None,
+        containers: Vec::new(),
+    };
+    if let Some(egress) = egress {
+        match egress::start_proxy(engine, sandbox, &started.network, egress) {
+            Ok(proxy) => {
+                started.containers.push(proxy.clone());
+                started.proxy = Some(proxy);
+            }
+            Err(err) => {
+                let _ = started.stop(engine);
+                return Err(err);
+            }
+        }
+    }
+    for (name, sidecar) in sidecars {
+        if let Err(err) = started.start_one(engine, sandbox, name, sidecar) {
+            // Don't leave half of the services running.
+            let _ = started.stop(engine);
+            return Err(err);
+        }
+    }
+    Ok(started)
+}
+
+impl Sidecars {
+    fn start_one(
+        &mut self,
+        engine: &dyn Engine,
+        sandbox: &str,
+        name: &str,
+        sidecar: &SidecarConfig,
+    ) -> Result<()> {
+        let Some(image) = sidecar.image.as_deref() else {
+            bail!("sidecar '{name}' has no image");
+        };
+        let container = container_name(sandbox, name);
+
+        let mut cmd = engine.command();
+        cmd.arg("run")
+            .arg("--detach")
+            .arg("--rm")
+            .arg("--name")
+            .arg(&container)
+            .arg("--label")
+            .arg(format!("{SIDECAR_OF_LABEL}={sandbox}"))
+            .arg("--network")
+            .arg(&self.network)
+            .arg("--network-alias")
+            .arg(name);
+        for (key, value) in &sidecar.env {
+            cmd.arg("-e").arg(format!("{key}={value}"));
+        }
+        cmd.args(&sidecar.docker_args).arg(image);
+        if let Some(command) = &sidecar.command {
+            cmd.args(command);
+        }
+        cmd.stdout(Stdio::null());
+        run_checked(&mut cmd, &format!("{} run (sidecar {name})", engine.name()))?;
+
+        note!("started sidecar '{name}' ({image}).");
+        self.containers.push(container);
+        Ok(())
+    }
+
+    /// Remove the sidecars and their network.
+    pub fn stop(&self, engine: &dyn Engine) -> Result<()> {
+        if !self.containers.is_empty() {
+            let mut rm = engine.command();
+            rm.arg("rm")
+                .arg("--force")
+                .args(&self.containers)
+                .stdout(Stdio::null());
+            run_checked(&mut rm, &format!("{} rm (sidecars)", engine.name()))?;
+        }
+
+        let mut rm_network = engine.command();
+        rm_network
+            .arg("network")
+            .arg("rm")
+            .arg(&self.network)
+            .stdout(Stdio::null());
+        run_checked(&mut rm_network, &format!("{} network rm", engine.name()))
+    }
+}
+
+/// Sidecar containers and networks left behind by sandboxes that no longer
+/// exist, such as after `--keep` or `--detach`. `sandboxes` are the davy
+/// containers that stay.
+pub fn orphans(engine: &dyn Engine, sandboxes: &[String]) -> Result<(Vec<String>, Vec<String>)> {
+    let filter = format!("label={SIDECAR_OF_LABEL}");
+    let label = engine.label_template(SIDECAR_OF_LABEL);
+    let containers = containers::engine_lines(
+        engine,
+        &[
+            "ps",
+            "--all",
+            "--filter",
+            &filter,
+            "--format",
+            &format!("{{{{.Names}}}}\t{label}"),
+        ],
+    )?;
+    let networks = containers::engine_lines(
+        engine,
+        &[
+            "network",
+            "ls",
+            "--filter",
+            &filter,
+            "--format",
+            &format!("{{{{.Name}}}}\t{label}"),
+        ],
+    )?;
+    Ok((
+        orphaned(&containers, sandboxes),
+        orphaned(&networks, sandboxes),
+    ))
+}
+
+/// Names from `NAME\tSANDBOX` lines whose sandbox is not in `sandboxes`.
+fn orphaned(lines: &[String], sandboxes: &[String]) -> Vec<String> {
+    lines
+        .iter()
+        .filter_map(|line| {
+            let (name, sandbox) = line.split_once('\t').unwrap_or((line.as_str(), ""));
+            (!sandboxes.iter().any(|kept| kept == sandbox)).then(|| name.to_owned())
+        })
+        .collect()
+}
+
+/// Sandbox environment contributed by the sidecars, in `KEY=VALUE` form.
+pub fn sandbox_env(sidecars: &BTreeMap<String, SidecarConfig>) -> Vec<String> {
+    sidecars
+        .values()
+        .flat_map(|sidecar| &sidecar.sandbox_env)
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_derive_from_sandbox() {
+        assert_eq!(network_name("davy-app-1"), "davy-app-1-net");
+        assert_eq!(
+            container_name("davy-app-1", "postgres"),
+            "davy-app-1-postgres"
+        );
+    }
+
+    #[test]
+    fn orphans_are_those_without_their_sandbox() {
+        let lines = [
+            "davy-app-1-postgres\tdavy-app-1".to_owned(),
+            "davy-app-2-postgres\tdavy-app-2".to_owned(),
+            "davy-app-2-egress\tdavy-app-2".to_owned(),
+        ];
+        assert_eq!(
+            orphaned(&lines, &["davy-app-1".to_owned()]),
+            vec![
+                "davy-app-2-postgres".to_owned(),
+                "davy-app-2-egress".to_owned()
+            ]
+        );
+        assert!(orphaned(&lines, &["davy-app-1".to_owned(), "davy-app-2".to_owned()]).is_empty());
+    }
+
+    #[test]
+    fn sandbox_env_collects_all_sidecars() {
+        let mut sidecars = BTreeMap::new();
+        let mut postgres = SidecarConfig::default();
+        postgres
+            .sandbox_env
+            .insert("DATABASE_URL".to_owned(), "postgres://postgres".to_owned());
+        sidecars.insert("postgres".to_owned(), postgres);
+        let mut redis = SidecarConfig::default();
+        redis
+            .sandbox_env
+            .insert("REDIS_URL".to_owned(), "redis://redis".to_owned());
+        sidecars.insert("redis".to_owned(), redis);
+
+        assert_eq!(
+            sandbox_env(&sidecars),
+            vec![
+                "DATABASE_URL=postgres://postgres".to_owned(),
+                "REDIS_URL=redis://redis".to_owned()
+            ]
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+use signal_hook::iterator::{Handle, Signals};
+
+use crate::note;
+
+/// How long a container gets to exit after a forwarded signal before it is
+/// killed.
+pub const STOP_GRACE: Duration = Duration::from_secs(10);
+
+/// Forwards SIGINT, SIGTERM, and SIGHUP (as SIGTERM) to a running container
+/// while davy waits on it, and sends SIGTERM itself once an optional timeout
+/// passes. A second signal, or the grace period running out, kills the
+/// container.
+pub struct SignalForwarder {
+    handle: Handle,
+    thread: JoinHandle<()>,
+    state: Arc<Mutex<State>>,
+    binary: &'static str,
+    container: String,
+}
+
+#[derive(Default)]
+struct State {
+    deadline: Option<Instant>,
+    killed: bool,
+    stopped: Option<Stopped>,
+}
+
+/// Why davy stopped the container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stopped {
+    /// davy itself received a signal.
+    Signal,
+    /// The `--timeout` ran out.
+    Timeout,
+}
+
+impl SignalForwarder {
+    /// Start forwarding to `container`, controlled through the engine CLI
+    /// `binary`. With a `timeout`, the container is stopped once it has run
+    /// that long.
+    pub fn start(
+        binary: &'static str,
+        container: &str,
+        timeout: Option<Duration>,
+    ) -> Result<SignalForwarder> {
+        let mut signals =
+            Signals::new([SIGINT, SIGTERM, SIGHUP]).context("failed to install signal handlers")?;
+        let handle = signals.handle();
+        let state = Arc::new(Mutex::new(State::default()));
+        let expires = timeout.map(|timeout| (Instant::now() + timeout, timeout));
+
+        let thread = {
+            let state = Arc::clone(&state);
+            let container = container.to_owned();
+            thread::spawn(move || {
+                while !signals.is_closed() {
+                    for signal in signals.pending() {
+                        let mut state = lock(&state);
+                        if state.deadline.is_none() {
+                            let name = signal_name(signal);
+                            note!("forwarding {name} to container '{container}'.");
+                            kill(binary, &container, Some(name));
+                            state.deadline = Some(Instant::now() + STOP_GRACE);
+                            state.stopped = Some(Stopped::Signal);
+                        } else if !state.killed {
+                            note!("killing container '{container}'.");
+                            kill(binary, &container, None);
+                            state.killed = true;
+                        }
+                    }
+                    {
+                        let mut state = lock(&state);
+                        if let Some((expires, timeout)) = expires
+                            && state.deadline.is_none()
+                            && Instant::now() >= expires
+                        {
+                            note!(
+                                "container '{container}' timed out after {}; stopping it.",
+                                humantime::format_duration(timeout)
+                            );
+                            kill(binary, &container, Some("SIGTERM"));
+                            state.deadline = Some(Instant::now() + STOP_GRACE);
+                            state.stopped = Some(Stopped::Timeout);
+                        }
+                        if !state.killed
+                            && state
+                                .deadline
+                                .is_some_and(|deadline| Instant::now() >= deadline)
+                        {
+                            note!("container '{container}' did not stop in time; killing it.");
+                            kill(binary, &container, None);
+                            state.killed = true;
+                        }
+                    }
+                    thread::sleep(Duration::from_millis(100));
+                }
+            })
+        };
+
+        Ok(SignalForwarder {
+            handle,
+            thread,
+            state,
+            binary,
+            container: container.to_owned(),
+        })
+    }
+
+    /// Stop forwarding. If davy stopped the container, give it the rest of
+    /// its grace period and kill it if it is still running. Returns why the
+    /// container was stopped, if it was.
+    pub fn finish(self) -> Option<Stopped> {
+        self.handle.close();
+        let _ = self.thread.join();
+
+        let state = lock(&self.state);
+        let deadline = state.deadline?;
+        if !state.killed {
+            while Instant::now() < deadline && is_running(self.binary, &self.container) {
+                thread::sleep(Duration::from_millis(200));
+            }
+            if is_running(self.binary, &self.container) {
+                kill(self.binary, &self.container, None);
+            }
+        }
+        state.stopped
+    }
+}
+
+/// Remove the container if it still exists, ignoring errors.
+pub fn remove_container(binary: &str, container: &str) {
+    let _ = Command::new(binary)
+        .arg("rm")
+        .arg("--force")
+        .arg(container)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+fn lock(state: &Mutex<State>) -> std::sync::MutexGuard<'_, State> {
+    state.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+fn signal_name(signal: i32) -> &'static str {
+    match signal {
+        SIGINT => "SIGINT",
+        // A dead terminal should stop the sandbox, not just hang it up.
+        _ => "SIGTERM",
+    }
+}
+
+fn kill(binary: &str, container: &str, signal: Option<&str>) {
+    let mut cmd = Command::new(binary);
+    cmd.arg("kill");
+    if let Some(signal) = signal {
+        cmd.arg("--signal").arg(signal);
+    }
+    let _ = cmd
+        .arg(container)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+fn is_running(binary: &str, container: &str) -> bool {
+    Command::new(binary)
+        .arg("container")
+        .arg("inspect")
+        .arg("--format")
+        .arg("{{.State.Running}}")
+        .arg(container)
+        .stderr(Stdio::null())
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).trim() == "true")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hangups_are_forwarded_as_sigterm() {
+        assert_eq!(signal_name(SIGINT), "SIGINT");
+        assert_eq!(signal_name(SIGTERM), "SIGTERM");
+        assert_eq!(signal_name(SIGHUP), "SIGTERM");
+    }
+}
@@ -0,0 +1,222 @@
+//! The host's `~/.agents/skills` in the sandbox, and copying skills an agent
+//! wrote there back to the host after review.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::engine::Engine;
+use crate::run_checked;
+
+/// Where skills live in the sandbox.
+pub const SANDBOX_DIR: &str = "/home/dev/.agents/skills";
+/// Where `copy` mode mounts the host's skills.
+pub const HOST_COPY_DIR: &str = "/home/dev/.davy-host-skills";
+
+/// Copies the read-only host skills into a writable directory, then runs
+/// its arguments.
+pub const COPY_SCRIPT: &str = r#"set -e
+mkdir -p /home/dev/.agents/skills
+if [ -d /home/dev/.davy-host-skills ]; then
+  cp -a /home/dev/.davy-host-skills/. /home/dev/.agents/skills/
+fi
+exec "$@""#;
+
+/// How the host's skills reach the sandbox.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SkillsMode {
+    /// Bind mount read-write
+    #[default]
+    Rw,
+    /// Bind mount read-only
+    Ro,
+    /// Start from a copy the agent may change; `davy skills sync` brings changes back
+    Copy,
+}
+
+/// A skill that differs between the sandbox and the host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkillChange {
+    pub name: String,
+    /// Whether the host has no skill by that name yet.
+    pub new: bool,
+}
+
+/// Copy the sandbox's skills out of `container` into `dest`.
+pub fn copy_out(engine: &dyn Engine, container: &str, dest: &Path) -> Result<()> {
+    let mut cmd = engine.command();
+    cmd.arg("cp")
+        .arg(format!("{container}:{SANDBOX_DIR}/."))
+        .arg(dest);
+    run_checked(&mut cmd, &format!("{} cp (skills)", engine.name()))
+}
+
+/// Skills (top-level directories) in `sandbox` that are missing from or
+/// differ from those in `host`. Skills only on the host are left alone.
+pub fn changed_skills(host: &Path, sandbox: &Path) -> Result<Vec<SkillChange>> {
+    let mut changes = Vec::new();
+    for entry in read_dir_sorted(sandbox)? {
+        if !entry.is_dir() {
+            continue;
+        }
+        let Some(name) = entry.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let host_skill = host.join(name);
+        if !host_skill.is_dir() {
+            changes.push(SkillChange {
+                name: name.to_owned(),
+                new: true,
+            });
+        } else if !same_tree(&host_skill, &entry)? {
+            changes.push(SkillChange {
+                name: name.to_owned(),
+                new: false,
+            });
+        }
+    }
+    Ok(changes)
+}
+
+/// Show what installing `name` would change, with `diff -ruN` when present.
+pub fn show_diff(host: &Path, sandbox: &Path, name: &str) {
+    let status = Command::new("diff")
+        .arg("-ruN")
+        .arg(host.join(name))
+        .arg(sandbox.join(name))
+        .status();
+    if status.is_err() {
+        for file in files(&sandbox.join(name)).unwrap_or_default() {
+            println!("  {}", file.display());
+        }
+    }
+}
+
+/// Replace the host's copy of skill `name` with the sandbox's.
+pub fn install(host: &Path, sandbox: &Path, name: &str) -> Result<()> {
+    fs::create_dir_all(host).with_context(|| format!("failed to create {}", host.display()))?;
+    let staged = host.join(format!(".{name}.davy-new"));
+    let old = host.join(format!(".{name}.davy-old"));
+    let target = host.join(name);
+    copy_tree(&sandbox.join(name), &staged)
+        .with_context(|| format!("failed to copy skill '{name}'"))?;
+    if target.exists() {
+        fs::rename(&target, &old)
+            .with_context(|| format!("failed to move {} aside", target.display()))?;
+    }
+    fs::rename(&staged, &target)
+        .with_context(|| format!("failed to install {}", target.display()))?;
+    if old.exists() {
+        fs::remove_dir_all(&old).with_context(|| format!("failed to remove {}", old.display()))?;
+    }
+    Ok(())
+}
+
+fn read_dir_sorted(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut entries = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => return Err(err).with_context(|| format!("failed to read {}", dir.display())),
+    };
+    entries.sort();
+    Ok(entries)
+}
+
+/// Files under `dir`, relative to it, sorted.
+fn files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for path in read_dir_sorted(&current)? {
+            if path.is_dir() && !path.is_symlink() {
+                pending.push(path);
+            } else if let Ok(relative) = path.strip_prefix(dir) {
+                found.push(relative.to_path_buf());
+            }
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+fn same_tree(a: &Path, b: &Path) -> Result<bool> {
+    let files_a = files(a)?;
+    if files_a != files(b)? {
+        return Ok(false);
+    }
+    for file in files_a {
+        if fs::read(a.join(&file))? != fs::read(b.join(&file))? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn copy_tree(from: &Path, to: &Path) -> Result<()> {
+    if to.exists() {
+        fs::remove_dir_all(to)?;
+    }
+    for file in files(from)? {
+        let dest = to.join(&file);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(from.join(&file), &dest)?;
+    }
+    fs::create_dir_all(to)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_and_installs_changed_skills() {
+        let root = std::env::temp_dir().join(format!("davy-skills-test-{}", std::process::id()));
+        let (host, sandbox) = (root.join("host"), root.join("sandbox"));
+        for (dir, body) in [(&host, "v1"), (&sandbox, "v2")] {
+            fs::create_dir_all(dir.join("review")).unwrap();
+            fs::write(dir.join("review/SKILL.md"), body).unwrap();
+            fs::create_dir_all(dir.join("same")).unwrap();
+            fs::write(dir.join("same/SKILL.md"), "same").unwrap();
+        }
+        fs::create_dir_all(sandbox.join("fresh/scripts")).unwrap();
+        fs::write(sandbox.join("fresh/scripts/run.sh"), "echo").unwrap();
+        fs::create_dir_all(host.join("host-only")).unwrap();
+
+        let changes = changed_skills(&host, &sandbox).unwrap();
+        assert_eq!(
+            changes,
+            [
+                SkillChange {
+                    name: "fresh".to_owned(),
+                    new: true
+                },
+                SkillChange {
+                    name: "review".to_owned(),
+                    new: false
+                },
+            ]
+        );
+
+        install(&host, &sandbox, "review").unwrap();
+        install(&host, &sandbox, "fresh").unwrap();
+        assert_eq!(
+            fs::read_to_string(host.join("review/SKILL.md")).unwrap(),
+            "v2"
+        );
+        assert!(host.join("fresh/scripts/run.sh").is_file());
+        assert!(host.join("host-only").is_dir());
+        assert!(changed_skills(&host, &sandbox).unwrap().is_empty());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
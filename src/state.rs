@@ -0,0 +1,86 @@
+//! A per-project volume for agent session history, so conversations and todo
+//! lists from one project follow it across ephemeral sandboxes.
+
+use std::path::Path;
+
+use crate::remote;
+
+/// Where the state volume is mounted.
+pub const MOUNT_DIR: &str = "/home/dev/.local/state";
+/// Where agent state is kept inside the volume.
+const AGENTS_DIR: &str = "/home/dev/.local/state/agents";
+
+/// Claude's session transcripts and todo lists.
+pub const CLAUDE_DIRS: &[&str] = &[".claude/projects", ".claude/todos"];
+/// Codex's session rollouts.
+pub const CODEX_DIRS: &[&str] = &[".codex/sessions"];
+
+/// Name of the state volume for `project_dir`.
+pub fn volume_name(host_uid: u32, project_dir: &Path) -> String {
+    format!(
+        "davy-state-{host_uid}-{}",
+        remote::project_hash(project_dir)
+    )
+}
+
+/// Script that links each directory under /home/dev into the state volume,
+/// then runs its arguments. A directory already there (say, in an auth
+/// volume) seeds the project's copy and is set aside as `DIR.davy-shared`.
+pub fn link_script(dirs: &[&str]) -> String {
+    let mut script = String::from("set -e\n");
+    for dir in dirs {
+        let home = format!("/home/dev/{dir}");
+        let stored = format!("{AGENTS_DIR}/{}", dir.trim_start_matches('.'));
+        script.push_str(&format!(
+            "mkdir -p \"$(dirname {home})\" \"$(dirname {stored})\"\n\
+             if [ -d {home} ] && [ ! -L {home} ]; then\n  \
+             [ -e {stored} ] || cp -a {home} {stored}\n  \
+             rm -rf {home}.davy-shared\n  \
+             mv {home} {home}.davy-shared\n\
+             fi\n\
+             mkdir -p {stored}\n\
+             ln -sfn {stored} {home}\n"
+        ));
+    }
+    script.push_str("exec \"$@\"");
+    script
+}
+
+/// Script that undoes [`link_script`] for runs without the state volume, so
+/// links it left in a persistent volume do not dangle.
+pub fn unlink_script(dirs: &[&str]) -> String {
+    let mut script = String::from("set -e\n");
+    for dir in dirs {
+        let home = format!("/home/dev/{dir}");
+        let stored = format!("{AGENTS_DIR}/{}", dir.trim_start_matches('.'));
+        script.push_str(&format!(
+            "if [ -L {home} ] && [ \"$(readlink {home})\" = {stored} ]; then\n  \
+             rm {home}\n  \
+             if [ -e {home}.davy-shared ]; then mv {home}.davy-shared {home}; fi\n\
+             fi\n"
+        ));
+    }
+    script.push_str("exec \"$@\"");
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_agent_dirs_into_the_volume() {
+        let script = link_script(CLAUDE_DIRS);
+        assert!(script.contains(
+            "ln -sfn /home/dev/.local/state/agents/claude/projects /home/dev/.claude/projects\n"
+        ));
+        assert!(script.contains("mv /home/dev/.claude/todos /home/dev/.claude/todos.davy-shared"));
+        assert!(unlink_script(CODEX_DIRS).contains(
+            "= /home/dev/.local/state/agents/codex/sessions ]; then\n  rm /home/dev/.codex/sessions\n"
+        ));
+        assert_ne!(
+            volume_name(1000, Path::new("/work/app")),
+            volume_name(1000, Path::new("/work/lib"))
+        );
+    }
+}